@@ -0,0 +1,45 @@
+//! Validation of long catch-all paths.
+
+use std::hint::black_box;
+
+use axum_safe_path::SafePathConfig;
+use criterion::{BenchmarkId, Criterion};
+
+/// A path of `depth` directories ending with a file name
+fn long_path(depth: usize) -> String {
+    let mut path = "segment/".repeat(depth);
+    path.push_str("file.txt");
+    path
+}
+
+/// Checks paths of growing depth with a few configs
+fn check(c: &mut Criterion) {
+    let configs = [
+        ("default", SafePathConfig::new()),
+        (
+            "strict",
+            SafePathConfig::new()
+                .deny_hidden(true)
+                .deny_current_dir(true)
+                .deny_percent_encoded(true)
+                .max_depth(1024),
+        ),
+    ];
+    let mut group = c.benchmark_group("check");
+    for depth in [16, 256, 4096] {
+        let path = long_path(depth);
+        for (name, config) in &configs {
+            group.bench_with_input(BenchmarkId::new(*name, depth), &path, |b, path| {
+                b.iter(|| config.check(black_box(path)));
+            });
+        }
+    }
+    group.finish();
+}
+
+// Not `criterion_main!`, as the generated functions lack docs
+fn main() {
+    let mut c = Criterion::default().configure_from_args();
+    check(&mut c);
+    c.final_summary();
+}
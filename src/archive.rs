@@ -0,0 +1,355 @@
+//! Validation of archive entry names against zip-slip.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection, Traversal, config::has_drive_prefix};
+
+/// Validates the name of a zip entry before extraction.
+///
+/// Zip entry names can use both `/` and `\` as separators and come from
+/// whoever made the archive, so names like `../../.bashrc`, `/etc/passwd` or
+/// `C:\Windows\win.ini` are how zip-slip attacks write outside of the
+/// extraction directory. The name is checked with a cross-platform config,
+/// see [`SafePathConfig::cross_platform`], and returned with `/` separators.
+/// Directory entries keep their trailing slash:
+///
+/// ```
+/// use axum_safe_path::validate_archive_entry;
+///
+/// let path = validate_archive_entry("docs\\guide/intro.md").unwrap();
+/// assert_eq!(path.0.to_str(), Some("docs/guide/intro.md"));
+///
+/// assert!(validate_archive_entry("../../.bashrc").is_err());
+/// assert!(validate_archive_entry("/etc/passwd").is_err());
+/// assert!(validate_archive_entry("C:\\Windows\\win.ini").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns the rejection describing the first failed check.
+pub fn validate_archive_entry(name: &str) -> Result<SafePath, SafePathRejection> {
+    SafePathConfig::new()
+        .cross_platform(true)
+        .validate_archive_entry(name)
+}
+
+impl SafePathConfig {
+    /// Validates the name of a zip entry with the config, see
+    /// [`validate_archive_entry`]
+    ///
+    /// Backslashes are always treated as separators, and empty, absolute and
+    /// drive-prefixed names are always rejected.
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn validate_archive_entry(&self, name: &str) -> Result<SafePath, SafePathRejection> {
+        let name = name.replace('\\', "/");
+        let path = Path::new(&name);
+        if name.trim_matches('/').is_empty() {
+            return Err(SafePathRejection::Empty);
+        }
+        if name.starts_with('/') {
+            return Err(SafePathRejection::TraversalAttack(Traversal::RootDir));
+        }
+        if has_drive_prefix(path) {
+            return Err(SafePathRejection::TraversalAttack(Traversal::Prefix));
+        }
+        self.validate(PathBuf::from(name))
+    }
+}
+
+/// The type of a tar entry, see [`TarEntry`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarEntryKind {
+    /// A regular file
+    File,
+    /// A directory
+    Directory,
+    /// A symbolic link
+    Symlink,
+    /// A hard link to another entry of the archive
+    HardLink,
+    /// A character device
+    CharDevice,
+    /// A block device
+    BlockDevice,
+    /// A named pipe
+    Fifo,
+    /// Any other type, like a GNU sparse file
+    Other,
+}
+
+/// The metadata of a tar entry needed to screen it, see [`validate_tar_entry`]
+#[derive(Debug, Clone, Copy)]
+pub struct TarEntry<'a> {
+    /// The path from the header, including the GNU long name if any
+    pub path: &'a str,
+    /// The `path` record of the PAX extended header, which takes precedence
+    /// over the header path
+    pub pax_path: Option<&'a str>,
+    /// The type of the entry
+    pub kind: TarEntryKind,
+    /// The target of a link, including the PAX `linkpath` record if any
+    pub link_target: Option<&'a str>,
+}
+
+/// A dangerous property of a tar entry, see [`validate_tar_entry`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TarFinding {
+    /// The path of the entry is invalid
+    Path(SafePathRejection),
+    /// The header path is invalid while the PAX path overriding it is fine,
+    /// which could fool extractors ignoring PAX headers
+    HeaderPath(SafePathRejection),
+    /// The target of a hard link isn't a valid path inside of the archive
+    LinkTarget(SafePathRejection),
+    /// A symlink is absolute or points outside of the extraction directory
+    SymlinkEscapes,
+    /// The entry is a device or a named pipe
+    Device,
+    /// The entry type isn't supported
+    UnsupportedKind,
+}
+
+/// The result of [`validate_tar_entry`]
+#[derive(Debug)]
+pub struct TarEntryReport {
+    /// The validated path the entry should be extracted to, if it's valid
+    pub path: Option<SafePath>,
+    /// The problems found, empty if the entry is safe to extract
+    pub findings: Vec<TarFinding>,
+}
+
+impl TarEntryReport {
+    /// Checks if nothing dangerous was found
+    #[must_use]
+    pub const fn is_safe(&self) -> bool {
+        self.findings.is_empty()
+    }
+}
+
+/// Screens a tar entry before extraction.
+///
+/// The path, taken from the PAX header if there is one, is checked the same
+/// way as [`validate_archive_entry`] checks zip entries. Besides that, tar
+/// archives can have links and special files: hard link targets must be
+/// valid paths inside of the archive, symlinks must stay inside of the
+/// extraction directory and devices or named pipes are reported. All the
+/// findings are returned, so extraction code can decide whether to skip the
+/// entry or to reject the whole archive:
+///
+/// ```
+/// use axum_safe_path::{TarEntry, TarEntryKind, TarFinding, validate_tar_entry};
+///
+/// let entry = TarEntry {
+///     path: "docs/latest",
+///     pax_path: None,
+///     kind: TarEntryKind::Symlink,
+///     link_target: Some("../../../etc/passwd"),
+/// };
+/// let report = validate_tar_entry(&entry);
+/// assert!(!report.is_safe());
+/// assert!(matches!(report.findings[..], [TarFinding::SymlinkEscapes]));
+/// ```
+#[must_use]
+pub fn validate_tar_entry(entry: &TarEntry<'_>) -> TarEntryReport {
+    let config = SafePathConfig::new().cross_platform(true);
+    let mut findings = Vec::new();
+
+    let path = match config.validate_archive_entry(entry.pax_path.unwrap_or(entry.path)) {
+        Ok(path) => Some(path),
+        Err(rejection) => {
+            findings.push(TarFinding::Path(rejection));
+            None
+        }
+    };
+    if entry.pax_path.is_some()
+        && path.is_some()
+        && let Err(rejection) = config.validate_archive_entry(entry.path)
+    {
+        findings.push(TarFinding::HeaderPath(rejection));
+    }
+
+    match entry.kind {
+        TarEntryKind::File | TarEntryKind::Directory => (),
+        TarEntryKind::HardLink => {
+            if let Err(rejection) =
+                config.validate_archive_entry(entry.link_target.unwrap_or_default())
+            {
+                findings.push(TarFinding::LinkTarget(rejection));
+            }
+        }
+        TarEntryKind::Symlink => {
+            let target = entry.link_target.unwrap_or_default();
+            let parent = path.as_ref().and_then(|path| path.0.parent());
+            if target.is_empty() || !symlink_stays_inside(parent, target) {
+                findings.push(TarFinding::SymlinkEscapes);
+            }
+        }
+        TarEntryKind::CharDevice | TarEntryKind::BlockDevice | TarEntryKind::Fifo => {
+            findings.push(TarFinding::Device);
+        }
+        TarEntryKind::Other => findings.push(TarFinding::UnsupportedKind),
+    }
+
+    TarEntryReport { path, findings }
+}
+
+/// Checks that the symlink target resolved from the directory of the link
+/// doesn't leave the extraction directory
+fn symlink_stays_inside(dir: Option<&Path>, target: &str) -> bool {
+    let target = target.replace('\\', "/");
+    let target = Path::new(&target);
+    if target.has_root() || has_drive_prefix(target) {
+        return false;
+    }
+    let mut depth = 0_usize;
+    for component in dir
+        .into_iter()
+        .flat_map(Path::components)
+        .chain(target.components())
+    {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(parent) => depth = parent,
+                None => return false,
+            },
+            Component::CurDir => (),
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        for (name, expected) in [
+            ("a.txt", "a.txt"),
+            ("docs/", "docs/"),
+            ("docs\\guide\\intro.md", "docs/guide/intro.md"),
+            ("./a/./b", "./a/./b"),
+        ] {
+            assert_eq!(
+                validate_archive_entry(name).unwrap().0,
+                PathBuf::from(expected),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn zip_slip() {
+        for name in [
+            "",
+            "/",
+            "..",
+            "../evil.sh",
+            "a/../../evil.sh",
+            "a\\..\\..\\evil.sh",
+            "/etc/passwd",
+            "\\windows\\win.ini",
+            "C:\\Windows\\win.ini",
+            "c:evil",
+            "\\\\server\\share\\x",
+            "a\0b",
+            "con.txt",
+            "a.txt::$DATA",
+            "evil. ",
+        ] {
+            assert!(validate_archive_entry(name).is_err(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn custom_config() {
+        let config = SafePathConfig::new().deny_hidden(true);
+        assert!(config.validate_archive_entry("a/.git/config").is_err());
+        assert!(config.validate_archive_entry("D:/a").is_err());
+        assert!(config.validate_archive_entry("a\\b").is_ok());
+    }
+
+    fn entry<'a>(path: &'a str, kind: TarEntryKind, link_target: Option<&'a str>) -> TarEntry<'a> {
+        TarEntry {
+            path,
+            pax_path: None,
+            kind,
+            link_target,
+        }
+    }
+
+    #[test]
+    fn tar_entries() {
+        for entry in [
+            entry("a/b.txt", TarEntryKind::File, None),
+            entry("a/", TarEntryKind::Directory, None),
+            entry("a/b/link", TarEntryKind::Symlink, Some("../c.txt")),
+            entry("a/link", TarEntryKind::Symlink, Some("./b/../c")),
+            entry("a/hard", TarEntryKind::HardLink, Some("a/b.txt")),
+        ] {
+            let report = validate_tar_entry(&entry);
+            assert!(report.is_safe(), "{entry:?} {report:?}");
+            assert!(report.path.is_some());
+        }
+
+        for (entry, expected) in [
+            (
+                entry("../a", TarEntryKind::File, None),
+                "[Path(TraversalAttack(ParentDir { index: 0 }))]",
+            ),
+            (
+                entry("link", TarEntryKind::Symlink, Some("../etc")),
+                "[SymlinkEscapes]",
+            ),
+            (
+                entry("a/link", TarEntryKind::Symlink, Some("b/../../..")),
+                "[SymlinkEscapes]",
+            ),
+            (
+                entry("link", TarEntryKind::Symlink, Some("/etc/passwd")),
+                "[SymlinkEscapes]",
+            ),
+            (
+                entry("link", TarEntryKind::Symlink, None),
+                "[SymlinkEscapes]",
+            ),
+            (
+                entry("hard", TarEntryKind::HardLink, Some("/etc/shadow")),
+                "[LinkTarget(TraversalAttack(RootDir))]",
+            ),
+            (
+                entry("dev/sda", TarEntryKind::BlockDevice, None),
+                "[Device]",
+            ),
+            (
+                entry("../x", TarEntryKind::CharDevice, None),
+                "[Path(TraversalAttack(ParentDir { index: 0 })), Device]",
+            ),
+        ] {
+            let report = validate_tar_entry(&entry);
+            assert_eq!(format!("{:?}", report.findings), expected, "{entry:?}");
+        }
+    }
+
+    #[test]
+    fn pax_paths() {
+        let mut pax = entry("safe.txt", TarEntryKind::File, None);
+        pax.pax_path = Some("../../evil.sh");
+        let report = validate_tar_entry(&pax);
+        assert!(report.path.is_none());
+        assert!(matches!(report.findings[..], [TarFinding::Path(_)]));
+
+        let mut smuggled = entry("../../evil.sh", TarEntryKind::File, None);
+        smuggled.pax_path = Some("a/long/name.txt");
+        let report = validate_tar_entry(&smuggled);
+        assert_eq!(report.path.unwrap().0, PathBuf::from("a/long/name.txt"));
+        assert!(matches!(report.findings[..], [TarFinding::HeaderPath(_)]));
+    }
+}
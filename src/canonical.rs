@@ -0,0 +1,230 @@
+//! Tower layer rewriting request paths to the canonical form before routing.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::Request,
+    http::Uri,
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{SafePathRejection, uri};
+
+/// A layer rewriting the request path to its canonical form.
+///
+/// Runs of slashes are collapsed and `.` and `..` segments, including
+/// percent-encoded ones, are removed as described in [RFC 3986][rfc], so
+/// routes match the same string the extractors validate. A `..` going above
+/// the root is rejected as a traversal rather than dropped. The query is kept
+/// as is.
+///
+/// [`Router::layer`](axum::Router::layer) runs middleware after routing, so
+/// the layer has to wrap the whole router:
+///
+/// ```
+/// use axum::{Router, ServiceExt, extract::Request, routing::get};
+/// use axum_safe_path::CanonicalPathLayer;
+/// use tower_layer::Layer;
+///
+/// let router = Router::new().route("/docs/{*path}", get(|| async { "docs" }));
+/// // `/docs//guide/./intro.md` is routed as `/docs/guide/intro.md`
+/// let app = CanonicalPathLayer::new().layer(router);
+/// let service = ServiceExt::<Request>::into_make_service(app);
+/// ```
+///
+/// With [`reject`](Self::reject), non-canonical paths are rejected instead,
+/// for APIs where clients have no reason to send them.
+///
+/// [rfc]: https://www.rfc-editor.org/rfc/rfc3986#section-5.2.4
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CanonicalPathLayer {
+    reject: bool,
+}
+
+impl CanonicalPathLayer {
+    /// Creates a layer rewriting non-canonical paths
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { reject: false }
+    }
+
+    /// Rejects non-canonical paths instead of rewriting them
+    #[must_use]
+    pub const fn reject(mut self, enabled: bool) -> Self {
+        self.reject = enabled;
+        self
+    }
+
+    /// Rewrites the path of the URI to the canonical form
+    fn rewrite(self, uri: &mut Uri) -> Result<(), SafePathRejection> {
+        let Some(path) = canonicalize(uri.path())? else {
+            return Ok(());
+        };
+        if self.reject {
+            return Err(SafePathRejection::NotCanonical);
+        }
+        let path_and_query = match uri.query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        };
+        let mut parts = uri.clone().into_parts();
+        parts.path_and_query = Some(
+            path_and_query
+                .parse()
+                .map_err(|_| SafePathRejection::NotCanonical)?,
+        );
+        *uri = Uri::from_parts(parts).map_err(|_| SafePathRejection::NotCanonical)?;
+        Ok(())
+    }
+}
+
+impl<S> Layer<S> for CanonicalPathLayer {
+    type Service = CanonicalPath<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CanonicalPath {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// The middleware created by [`CanonicalPathLayer`]
+#[derive(Debug, Clone)]
+pub struct CanonicalPath<S> {
+    inner: S,
+    layer: CanonicalPathLayer,
+}
+
+impl<S, B> Service<Request<B>> for CanonicalPath<S>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Err(rejection) = self.layer.rewrite(req.uri_mut()) {
+            let response = rejection.into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(req))
+    }
+}
+
+/// Returns the canonical form of the raw path, or `None` if it's canonical
+/// already
+///
+/// Paths not starting with a slash, like `*` of `OPTIONS *`, are left alone.
+fn canonicalize(path: &str) -> Result<Option<String>, SafePathRejection> {
+    if !path.starts_with('/') {
+        return Ok(None);
+    }
+    let mut collapsed = String::with_capacity(path.len());
+    for c in path.chars() {
+        if !(c == '/' && collapsed.ends_with('/')) {
+            collapsed.push(c);
+        }
+    }
+    let canonical = uri::remove_dot_segments(&collapsed)?;
+    Ok((canonical != path).then_some(canonical))
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{
+        Router,
+        body::{self, Body},
+        extract::OriginalUri,
+        http::StatusCode,
+        routing::get,
+    };
+
+    use super::*;
+    use crate::Traversal;
+
+    async fn send(service: &mut CanonicalPath<Router>, uri: &str) -> (StatusCode, String) {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let res = service.call(req).await.unwrap();
+        let status = res.status();
+        let body = body::to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn canonical_form() {
+        for path in ["/", "/a/b", "/a/b/", "/a/%2Fb", "*"] {
+            assert_eq!(canonicalize(path), Ok(None), "{path:?}");
+        }
+        for (path, expected) in [
+            ("//a///b", "/a/b"),
+            ("/a/./b/", "/a/b/"),
+            ("/a/b/../c", "/a/c"),
+            ("/a/b/..", "/a/"),
+            ("/a//%2E%2e//b", "/b"),
+        ] {
+            assert_eq!(
+                canonicalize(path),
+                Ok(Some(expected.to_owned())),
+                "{path:?}"
+            );
+        }
+        assert_eq!(
+            canonicalize("/a/../../b"),
+            Err(SafePathRejection::TraversalAttack(Traversal::ParentDir {
+                index: 2
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn rewrites_before_routing() {
+        let router = Router::new().route(
+            "/docs/{*path}",
+            get(|uri: Uri, OriginalUri(original): OriginalUri| async move {
+                format!("{uri} {original}")
+            }),
+        );
+        let mut service = CanonicalPathLayer::new().layer(router);
+
+        let (status, body) = send(&mut service, "/docs//guide/./intro.md?v=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "/docs/guide/intro.md?v=1 /docs/guide/intro.md?v=1");
+
+        let (status, body) = send(&mut service, "/static/%2e%2e/docs/a.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "/docs/a.md /docs/a.md");
+
+        let (status, _) = send(&mut service, "/docs/../../etc/passwd").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejects() {
+        let router = Router::new().route("/docs/{*path}", get(|| async { "docs" }));
+        let mut service = CanonicalPathLayer::new().reject(true).layer(router);
+
+        let (status, body) = send(&mut service, "/docs/a.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "docs");
+
+        for uri in ["/docs//a.md", "/docs/./a.md", "/x/../docs/a.md"] {
+            let (status, body) = send(&mut service, uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(body, "Invalid path: not in canonical form");
+        }
+    }
+}
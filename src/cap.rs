@@ -0,0 +1,86 @@
+//! File access confined to a [`Dir`] capability.
+
+use std::io;
+
+use cap_std::fs::{Dir, File};
+
+use crate::SafePath;
+
+#[cfg_attr(docsrs, doc(cfg(feature = "cap-std")))]
+impl SafePath {
+    /// Opens the file in read-only mode relative to the directory
+    ///
+    /// Unlike joining the path onto a root, [`Dir`] lets the OS enforce that
+    /// the file is inside the directory, including through symlinks:
+    ///
+    /// ```
+    /// use axum_safe_path::SafePath;
+    /// use cap_std::{ambient_authority, fs::Dir};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// # let tmp = std::env::temp_dir();
+    /// let dir = Dir::open_ambient_dir(tmp, ambient_authority())?;
+    /// let path: SafePath = "missing/file.txt".parse().unwrap();
+    /// assert!(path.open_in(&dir).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be opened or is outside of the directory.
+    pub fn open_in(&self, dir: &Dir) -> io::Result<File> {
+        dir.open(&self.0)
+    }
+
+    /// Reads the whole file relative to the directory
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be read or is outside of the directory.
+    pub fn read_in(&self, dir: &Dir) -> io::Result<Vec<u8>> {
+        dir.read(&self.0)
+    }
+
+    /// Opens the file in write-only mode relative to the directory, creating
+    /// or truncating it
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be created or is outside of the directory.
+    pub fn create_in(&self, dir: &Dir) -> io::Result<File> {
+        dir.create(&self.0)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::io::Write;
+
+    use cap_std::ambient_authority;
+
+    use super::*;
+
+    #[test]
+    fn confined() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(tmp.path().join("root")).unwrap();
+        std::fs::write(tmp.path().join("secret.txt"), "secret").unwrap();
+        let dir = Dir::open_ambient_dir(tmp.path().join("root"), ambient_authority()).unwrap();
+
+        let path: SafePath = "a.txt".parse().unwrap();
+        path.create_in(&dir).unwrap().write_all(b"content").unwrap();
+        assert_eq!(path.read_in(&dir).unwrap(), b"content");
+        assert!(path.open_in(&dir).is_ok());
+
+        // Bypassing validation doesn't help either
+        assert!(SafePath("../secret.txt".into()).read_in(&dir).is_err());
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("../secret.txt", tmp.path().join("root/link")).unwrap();
+            let link: SafePath = "link".parse().unwrap();
+            assert!(link.read_in(&dir).is_err());
+        }
+    }
+}
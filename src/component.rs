@@ -0,0 +1,239 @@
+//! Checks of path components working on raw bytes.
+//!
+//! They don't depend on `std`, so with default features disabled they can be
+//! used in `no_std` environments, like embedded gateways proxying file
+//! requests. Paths are `/`-separated byte strings there, with backslashes
+//! treated as separators too:
+//!
+//! ```
+//! use axum_safe_path::component::{is_hidden, is_traversal, segments};
+//!
+//! assert!(is_traversal(b"docs/../../etc/passwd"));
+//! assert!(is_traversal(b"docs\\..\\secret"));
+//! assert!(!is_traversal(b"docs/..hidden"));
+//! assert!(segments(b"docs/.env").any(is_hidden));
+//! ```
+//!
+//! The full set of configurable checks is provided by
+//! [`SafePathConfig`](crate::SafePathConfig) when the `std` feature is on.
+
+/// Characters not allowed in Windows file names, in addition to control ones
+pub const WINDOWS_FORBIDDEN_CHARS: &[u8] = b"<>:\"\\|?*";
+
+/// Characters that look like a dot or a sequence of them and may be normalized
+/// into real dots downstream
+const LOOKALIKE_DOTS: &[char] = &[
+    '\u{2024}', // One dot leader
+    '\u{2025}', // Two dot leader
+    '\u{2026}', // Horizontal ellipsis
+    '\u{3002}', // Ideographic full stop
+    '\u{FE52}', // Small full stop
+    '\u{FF0E}', // Fullwidth full stop
+    '\u{FF61}', // Halfwidth ideographic full stop
+];
+
+/// Device names reserved on Windows, with or without an extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "AUX",
+    "CON",
+    "CONIN$",
+    "CONOUT$",
+    "NUL",
+    "PRN",
+    "COM0",
+    "COM1",
+    "COM2",
+    "COM3",
+    "COM4",
+    "COM5",
+    "COM6",
+    "COM7",
+    "COM8",
+    "COM9",
+    "COM\u{B9}",
+    "COM\u{B2}",
+    "COM\u{B3}",
+    "LPT0",
+    "LPT1",
+    "LPT2",
+    "LPT3",
+    "LPT4",
+    "LPT5",
+    "LPT6",
+    "LPT7",
+    "LPT8",
+    "LPT9",
+    "LPT\u{B9}",
+    "LPT\u{B2}",
+    "LPT\u{B3}",
+];
+
+/// Zero-width and bidirectional formatting characters
+const INVISIBLE_CHARS: &[char] = &[
+    '\u{061C}', // Arabic letter mark
+    '\u{180E}', // Mongolian vowel separator
+    '\u{200B}', // Zero width space
+    '\u{200C}', // Zero width non-joiner
+    '\u{200D}', // Zero width joiner
+    '\u{200E}', // Left-to-right mark
+    '\u{200F}', // Right-to-left mark
+    '\u{202A}', // Left-to-right embedding
+    '\u{202B}', // Right-to-left embedding
+    '\u{202C}', // Pop directional formatting
+    '\u{202D}', // Left-to-right override
+    '\u{202E}', // Right-to-left override
+    '\u{2060}', // Word joiner
+    '\u{2066}', // Left-to-right isolate
+    '\u{2067}', // Right-to-left isolate
+    '\u{2068}', // First strong isolate
+    '\u{2069}', // Pop directional isolate
+    '\u{FEFF}', // Zero width no-break space
+];
+
+/// Iterates over the segments of the path separated by `/` or `\`, skipping
+/// empty ones
+pub fn segments(path: &[u8]) -> impl Iterator<Item = &[u8]> {
+    path.split(|b| matches!(b, b'/' | b'\\'))
+        .filter(|segment| !segment.is_empty())
+}
+
+/// Checks if the path is absolute, starts with a drive prefix or has a `..`
+/// segment
+#[must_use]
+pub fn is_traversal(path: &[u8]) -> bool {
+    matches!(path.first(), Some(b'/' | b'\\'))
+        || has_drive_prefix(path)
+        || segments(path).any(|segment| segment == b"..")
+}
+
+/// Checks if the path starts with a Windows drive letter, like `C:` or `c:foo`
+#[must_use]
+pub const fn has_drive_prefix(path: &[u8]) -> bool {
+    matches!(path, [letter, b':', ..] if letter.is_ascii_alphabetic())
+}
+
+/// Checks if the name starts with a dot
+#[must_use]
+pub fn is_hidden(name: &[u8]) -> bool {
+    name.starts_with(b".")
+}
+
+/// Checks if the name consists of POSIX portable filename characters and
+/// doesn't start with a hyphen
+#[must_use]
+pub fn is_portable(name: &[u8]) -> bool {
+    !name.starts_with(b"-")
+        && name
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'-'))
+}
+
+/// Checks if the name has characters not allowed on Windows
+#[must_use]
+pub fn has_windows_forbidden_chars(name: &[u8]) -> bool {
+    name.iter()
+        .any(|b| b.is_ascii_control() || WINDOWS_FORBIDDEN_CHARS.contains(b))
+}
+
+/// Checks if the name refers to an NTFS alternate data stream
+#[must_use]
+pub fn has_alternate_stream(name: &[u8]) -> bool {
+    name.contains(&b':')
+}
+
+/// Checks if the name has control, zero-width or bidirectional formatting
+/// characters
+#[must_use]
+pub fn has_control_chars(name: &[u8]) -> bool {
+    name.utf8_chunks()
+        .any(|chunk| chunk.valid().chars().any(is_control_char))
+}
+
+/// Checks if the character is a control, zero-width or bidirectional
+/// formatting one
+#[must_use]
+pub fn is_control_char(c: char) -> bool {
+    c.is_control() || INVISIBLE_CHARS.contains(&c)
+}
+
+/// Checks if the name ends with a dot or a space
+#[must_use]
+pub const fn has_trailing_dot_or_space(name: &[u8]) -> bool {
+    matches!(name.last(), Some(b'.' | b' '))
+}
+
+/// Checks if the name refers to a Windows device
+#[must_use]
+pub fn is_windows_reserved(name: &[u8]) -> bool {
+    let stem = name.split(|&b| b == b'.').next().unwrap_or_default();
+    let stem = stem.trim_ascii_end();
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| stem.eq_ignore_ascii_case(reserved.as_bytes()))
+}
+
+/// Checks if the name has a percent-escape, i.e. `%` followed by two hex
+/// digits
+#[must_use]
+pub fn has_percent_escape(name: &[u8]) -> bool {
+    name.windows(3)
+        .any(|w| matches!(w, [b'%', a, b] if a.is_ascii_hexdigit() && b.is_ascii_hexdigit()))
+}
+
+/// Checks if the name consists of dots and their look-alikes, having at least
+/// one look-alike
+#[must_use]
+pub fn is_lookalike_dots(name: &[u8]) -> bool {
+    core::str::from_utf8(name).is_ok_and(|name| {
+        name.contains(LOOKALIKE_DOTS)
+            && name
+                .chars()
+                .all(|c| c == '.' || LOOKALIKE_DOTS.contains(&c))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traversal() {
+        for path in [
+            "..",
+            "../a",
+            "a/../b",
+            "a\\..\\b",
+            "/etc/passwd",
+            "\\a",
+            "C:/a",
+            "c:a",
+        ] {
+            assert!(is_traversal(path.as_bytes()), "{path:?}");
+        }
+        for path in ["", "a", "a/b/", "a//b", "..a/b..", "./a", "ab:c"] {
+            assert!(!is_traversal(path.as_bytes()), "{path:?}");
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_hidden(b".env"));
+        assert!(is_portable(b"a-b_c.txt"));
+        assert!(!is_portable(b"-rf"));
+        assert!(has_windows_forbidden_chars(b"a?b"));
+        assert!(has_alternate_stream(b"a.txt::$DATA"));
+        assert!(has_control_chars("a\u{202E}txt".as_bytes()));
+        assert!(has_control_chars(b"a\xFF\n"));
+        assert!(!has_control_chars(b"a\xFF"));
+        assert!(has_trailing_dot_or_space(b"a. "));
+        assert!(is_windows_reserved(b"con"));
+        assert!(is_windows_reserved(b"Nul .txt"));
+        assert!(is_windows_reserved("com\u{B9}.log".as_bytes()));
+        assert!(!is_windows_reserved(b"console"));
+        assert!(has_percent_escape(b"a%2e"));
+        assert!(!has_percent_escape(b"100%"));
+        assert!(is_lookalike_dots("\u{FF0E}\u{FF0E}".as_bytes()));
+        assert!(!is_lookalike_dots(b".."));
+        assert!(!is_lookalike_dots(b"\xFF"));
+    }
+}
@@ -0,0 +1,2346 @@
+//! Configuration of the checks performed by [`SafePath`](crate::SafePath).
+
+use std::{
+    borrow::Cow,
+    ffi::OsStr,
+    fmt,
+    path::{self, Component, PathBuf},
+    sync::Arc,
+};
+
+use axum::http::Extensions;
+
+#[cfg(feature = "arc-swap")]
+pub use self::reloadable::ReloadableConfig;
+use crate::{
+    SafePath, SafePathRejection, Traversal, component, find_traversal, glob,
+    outcome::{SafePathOutcome, Validated},
+    policy::{And, Policy},
+    raw,
+    scan::Scan,
+    trace,
+};
+
+#[cfg(feature = "serde")]
+mod de;
+#[cfg(feature = "arc-swap")]
+mod reloadable;
+
+/// Most filesystems limit a file name to 255 bytes
+pub const DEFAULT_MAX_COMPONENT_LENGTH: usize = 255;
+
+/// Names of well-known sensitive files and directories
+const SENSITIVE_NAMES: &[&str] = &[
+    ".bash_history",
+    ".DS_Store",
+    ".env",
+    ".git",
+    ".hg",
+    ".htaccess",
+    ".htpasswd",
+    ".npmrc",
+    ".pgpass",
+    ".ssh",
+    ".svn",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "id_rsa",
+    "Thumbs.db",
+    "web.config",
+];
+
+/// Suffixes of backup and editor swap files
+const SENSITIVE_SUFFIXES: &[&str] = &["~", ".bak", ".old", ".orig", ".swp"];
+
+/// Extensions of files commonly served as static web assets
+const WEB_ASSET_EXTENSIONS: &[&str] = &[
+    "avif", "css", "eot", "gif", "htm", "html", "ico", "jpeg", "jpg", "js", "json", "map", "mjs",
+    "mp3", "mp4", "ogg", "otf", "pdf", "png", "svg", "ttf", "txt", "wasm", "webm", "webp", "woff",
+    "woff2", "xml",
+];
+
+/// Extensions of raster images, SVG is excluded as it can carry scripts
+const IMAGE_EXTENSIONS: &[&str] = &["avif", "bmp", "gif", "jpeg", "jpg", "png", "webp"];
+
+/// The config used when there's none in request extensions.
+static DEFAULT_CONFIG: SafePathConfig = SafePathConfig::new();
+
+/// Validation settings for [`SafePath`](crate::SafePath).
+///
+/// The extractor looks the config up in request extensions, so it can be set
+/// for a whole router using [`axum::Extension`]:
+///
+/// ```
+/// use axum::{Extension, Router, routing::get};
+/// use axum_safe_path::{SafePath, SafePathConfig};
+///
+/// async fn handler(SafePath(path): SafePath) -> String {
+///     path.display().to_string()
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/files/{*path}", get(handler))
+///     .layer(Extension(SafePathConfig::new().max_depth(4)));
+/// ```
+///
+/// Without a config in extensions, [`SafePathConfig::default`] is used, which
+/// only rejects traversal attempts, NUL bytes and components longer than 255
+/// bytes.
+///
+/// With the `serde` feature, the config can be loaded from the application's
+/// configuration file. Fields are named after the builder methods:
+///
+/// ```
+/// # #[cfg(feature = "json")] {
+/// use axum_safe_path::SafePathConfig;
+///
+/// let config: SafePathConfig = serde_json::from_str(
+///     r#"{
+///         "max_depth": 4,
+///         "deny_hidden": true,
+///         "trailing_slash": "forbid",
+///         "allowed_extensions": ["png", "jpg"]
+///     }"#,
+/// )
+/// .unwrap();
+/// assert!(config.check("photos/cat.png").is_ok());
+/// assert!(config.check("photos/.cat.png").is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SafePathConfig {
+    max_depth: Option<usize>,
+    max_length: Option<usize>,
+    max_component_length: usize,
+    deny_empty: bool,
+    deny_hidden: bool,
+    deny_sensitive: bool,
+    deny_non_portable: bool,
+    deny_current_dir: bool,
+    deny_windows_chars: bool,
+    deny_lookalike_dots: bool,
+    deny_control_chars: bool,
+    deny_trailing_dots: bool,
+    deny_reserved_names: bool,
+    deny_alternate_streams: bool,
+    backslash_separator: bool,
+    cross_platform: bool,
+    remote: bool,
+    allow_absolute: bool,
+    deny_percent_encoded: bool,
+    deny_encoded_separators: bool,
+    require_extension: bool,
+    case_insensitive: bool,
+    allowed_extensions: Option<Arc<[String]>>,
+    denied_extensions: Option<Arc<[String]>>,
+    denied_names: Option<Arc<[String]>>,
+    allowed_globs: Option<Arc<[String]>>,
+    allowed_roots: Option<Arc<[PathBuf]>>,
+    trailing_slash: TrailingSlash,
+    policy: Option<Arc<dyn Policy>>,
+    audit: Option<AuditHook>,
+    rejection_message: Option<Arc<str>>,
+    #[cfg(feature = "regex")]
+    component_pattern: Option<regex::Regex>,
+    #[cfg(feature = "regex")]
+    path_pattern: Option<regex::Regex>,
+    #[cfg(feature = "unicode")]
+    unicode_form: Option<UnicodeForm>,
+}
+
+/// Handling of trailing slashes, see [`SafePathConfig::trailing_slash`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TrailingSlash {
+    /// Paths are accepted with or without a trailing slash
+    #[default]
+    Allow,
+    /// Paths with a trailing slash are rejected
+    Forbid,
+    /// Paths without a trailing slash are rejected
+    Require,
+    /// Trailing slashes are removed before validation
+    Strip,
+}
+
+/// Unicode normalization form, see [`SafePathConfig::normalize_unicode`]
+#[cfg(feature = "unicode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "unicode")))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum UnicodeForm {
+    /// Canonical composition, used by most filesystems and the web
+    Nfc,
+    /// Canonical decomposition, used by macOS filesystems
+    Nfd,
+}
+
+/// The function called in the audit-only mode
+type AuditFn = dyn Fn(&path::Path, &SafePathRejection) + Send + Sync;
+
+/// The hook called in the audit-only mode
+#[derive(Clone)]
+struct AuditHook(Arc<AuditFn>);
+
+impl fmt::Debug for AuditHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuditHook")
+    }
+}
+
+impl PartialEq for AuditHook {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Default for SafePathConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SafePathConfig {
+    /// Creates a config with the default settings
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_depth: None,
+            max_length: None,
+            max_component_length: DEFAULT_MAX_COMPONENT_LENGTH,
+            deny_empty: false,
+            deny_hidden: false,
+            deny_sensitive: false,
+            deny_non_portable: false,
+            deny_current_dir: false,
+            deny_windows_chars: false,
+            deny_lookalike_dots: false,
+            deny_control_chars: false,
+            deny_trailing_dots: false,
+            deny_reserved_names: false,
+            deny_alternate_streams: false,
+            backslash_separator: true,
+            cross_platform: false,
+            remote: false,
+            allow_absolute: false,
+            deny_percent_encoded: false,
+            deny_encoded_separators: false,
+            require_extension: false,
+            case_insensitive: false,
+            allowed_extensions: None,
+            denied_extensions: None,
+            denied_names: None,
+            allowed_globs: None,
+            allowed_roots: None,
+            trailing_slash: TrailingSlash::Allow,
+            policy: None,
+            audit: None,
+            rejection_message: None,
+            #[cfg(feature = "regex")]
+            component_pattern: None,
+            #[cfg(feature = "regex")]
+            path_pattern: None,
+            #[cfg(feature = "unicode")]
+            unicode_form: None,
+        }
+    }
+
+    /// A preset for static web assets
+    ///
+    /// Allows only common web file types and rejects empty paths, hidden and
+    /// sensitive files and invisible characters.
+    #[must_use]
+    pub fn web_assets() -> Self {
+        Self::new()
+            .max_length(1024)
+            .deny_empty(true)
+            .deny_hidden(true)
+            .deny_sensitive(true)
+            .deny_control_chars(true)
+            .allowed_extensions(WEB_ASSET_EXTENSIONS)
+    }
+
+    /// A preset for user-uploaded images
+    ///
+    /// Allows only raster image types, limits the path to portable names and
+    /// rejects empty paths and hidden files.
+    #[must_use]
+    pub fn images_only() -> Self {
+        Self::new()
+            .max_length(1024)
+            .deny_empty(true)
+            .deny_hidden(true)
+            .deny_non_portable(true)
+            .allowed_extensions(IMAGE_EXTENSIONS)
+    }
+
+    /// A preset for names that are valid on Windows regardless of the platform
+    /// the server is running on
+    #[must_use]
+    pub const fn windows_safe() -> Self {
+        Self::new()
+            .max_length(260)
+            .deny_windows_chars(true)
+            .deny_trailing_dots(true)
+            .deny_reserved_names(true)
+            .deny_alternate_streams(true)
+    }
+
+    /// A preset for paths on SFTP or FTP servers
+    ///
+    /// Enables the [remote](Self::remote) mode, rejects control characters
+    /// and limits the path to 4096 bytes, a common `PATH_MAX` of the servers.
+    /// Combine it with [`allow_absolute`](Self::allow_absolute) if clients
+    /// may address the server root explicitly.
+    #[must_use]
+    pub const fn sftp() -> Self {
+        Self::new()
+            .remote(true)
+            .max_length(4096)
+            .deny_control_chars(true)
+    }
+
+    /// Rejects paths having more than `depth` components
+    #[must_use]
+    pub const fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Rejects paths longer than `length` bytes
+    #[must_use]
+    pub const fn max_length(mut self, length: usize) -> Self {
+        self.max_length = Some(length);
+        self
+    }
+
+    /// Rejects paths having a component longer than `length` bytes, 255 by
+    /// default
+    #[must_use]
+    pub const fn max_component_length(mut self, length: usize) -> Self {
+        self.max_component_length = length;
+        self
+    }
+
+    /// Rejects paths without a single normal component, like `""`, `"."` or
+    /// `"./"`
+    #[must_use]
+    pub const fn deny_empty(mut self, deny: bool) -> Self {
+        self.deny_empty = deny;
+        self
+    }
+
+    /// Rejects paths having a hidden component, i.e. one starting with a dot,
+    /// like `.env` or `.git/config`
+    #[must_use]
+    pub const fn deny_hidden(mut self, deny: bool) -> Self {
+        self.deny_hidden = deny;
+        self
+    }
+
+    /// Rejects paths having a component named after a well-known sensitive
+    /// file, like `.env`, `.git`, `id_rsa` or `web.config`, or a backup file,
+    /// like `config.php~` or `db.sqlite.bak`
+    #[must_use]
+    pub const fn deny_sensitive(mut self, deny: bool) -> Self {
+        self.deny_sensitive = deny;
+        self
+    }
+
+    /// Rejects paths having `.` components, like `./foo` or `foo/./bar`
+    ///
+    /// Useful when the path is compared as a string downstream, where
+    /// `foo/./bar` and `foo/bar` are different.
+    #[must_use]
+    pub const fn deny_current_dir(mut self, deny: bool) -> Self {
+        self.deny_current_dir = deny;
+        self
+    }
+
+    /// Sets how trailing slashes are handled, they're allowed by default
+    ///
+    /// Unless they're stripped, handlers can use
+    /// [`SafePath::has_trailing_slash`] to tell directory-intent requests
+    /// from file-intent ones.
+    #[must_use]
+    pub const fn trailing_slash(mut self, trailing_slash: TrailingSlash) -> Self {
+        self.trailing_slash = trailing_slash;
+        self
+    }
+
+    /// Rejects paths having a component outside of the POSIX portable filename
+    /// character set, `[A-Za-z0-9._-]`, or starting with a hyphen
+    ///
+    /// Useful when the path is passed to shell tools or legacy systems.
+    #[must_use]
+    pub const fn deny_non_portable(mut self, deny: bool) -> Self {
+        self.deny_non_portable = deny;
+        self
+    }
+
+    /// Rejects paths having a component with characters not allowed in
+    /// Windows file names: `<>:"\|?*` and control characters
+    #[must_use]
+    pub const fn deny_windows_chars(mut self, deny: bool) -> Self {
+        self.deny_windows_chars = deny;
+        self
+    }
+
+    /// Rejects paths having a component made of Unicode characters looking
+    /// like dots, e.g. `\u{2025}` (two dot leader) or `\u{FF0E}\u{FF0E}`
+    /// (fullwidth full stops), as some normalizers downstream turn them into
+    /// `..`
+    #[must_use]
+    pub const fn deny_lookalike_dots(mut self, deny: bool) -> Self {
+        self.deny_lookalike_dots = deny;
+        self
+    }
+
+    /// Rejects paths having C0 and C1 control characters, zero-width or
+    /// bidirectional formatting characters
+    ///
+    /// Such paths may be lexically safe, but they allow spoofing logs and
+    /// displaying file names differently from what they really are.
+    #[must_use]
+    pub const fn deny_control_chars(mut self, deny: bool) -> Self {
+        self.deny_control_chars = deny;
+        self
+    }
+
+    /// Rejects paths having a component ending with a dot or a space
+    ///
+    /// Windows silently strips them, so `passwd.` and `passwd ` would refer to
+    /// `passwd`.
+    #[must_use]
+    pub const fn deny_trailing_dots(mut self, deny: bool) -> Self {
+        self.deny_trailing_dots = deny;
+        self
+    }
+
+    /// Rejects paths having a component named after a Windows device, like
+    /// `CON`, `NUL`, `COM1` or `lpt1.txt`, case-insensitively and with or
+    /// without an extension
+    #[must_use]
+    pub const fn deny_reserved_names(mut self, deny: bool) -> Self {
+        self.deny_reserved_names = deny;
+        self
+    }
+
+    /// Rejects paths having a component with NTFS alternate data stream
+    /// syntax, like `name.txt::$DATA` or `name:stream`
+    ///
+    /// On NTFS `image.png:payload.php` is a stream of `image.png`, which
+    /// bypasses extension checks.
+    #[must_use]
+    pub const fn deny_alternate_streams(mut self, deny: bool) -> Self {
+        self.deny_alternate_streams = deny;
+        self
+    }
+
+    /// Treats backslashes as separators while checking the path, on by default
+    ///
+    /// On Unix `..\..\secret` is a single file name, but it becomes a
+    /// traversal as soon as the path reaches a Windows file server or a
+    /// library splitting on backslashes. The validated path is returned as is.
+    #[must_use]
+    pub const fn backslash_separator(mut self, enabled: bool) -> Self {
+        self.backslash_separator = enabled;
+        self
+    }
+
+    /// Validates paths under both Unix and Windows semantics, regardless of
+    /// the platform the server is running on
+    ///
+    /// Backslashes are treated as separators, drive prefixes like `C:` are
+    /// rejected as traversals, and so are reserved device names, alternate
+    /// data streams and names with trailing dots or spaces. Use it when paths
+    /// end up on storage of either kind.
+    #[must_use]
+    pub const fn cross_platform(mut self, enabled: bool) -> Self {
+        self.cross_platform = enabled;
+        self
+    }
+
+    /// Validates paths for remote backends, like SFTP or FTP servers, which
+    /// always use `/` as the separator regardless of the platform the service
+    /// is running on
+    ///
+    /// Backslashes are rejected, as they're ordinary characters on the server
+    /// but separators for Windows code handling the path, and so are drive
+    /// prefixes like `C:`. Lengths are counted in bytes, as the servers do.
+    #[must_use]
+    pub const fn remote(mut self, enabled: bool) -> Self {
+        self.remote = enabled;
+        self
+    }
+
+    /// Accepts paths starting with `/` in the [remote](Self::remote) mode,
+    /// checking them as relative to the server root
+    ///
+    /// Has no effect on local paths, as joining an absolute path onto a
+    /// directory replaces the directory.
+    #[must_use]
+    pub const fn allow_absolute(mut self, allow: bool) -> Self {
+        self.allow_absolute = allow;
+        self
+    }
+
+    /// Normalizes the path to the Unicode form before validation, so the
+    /// handler receives it normalized
+    ///
+    /// Visually identical names, like `caf\u{e9}` and `cafe\u{301}`, become
+    /// the same path, so they can't be used to create distinct files or bypass
+    /// allowlists. Paths that aren't valid UTF-8 are left as is.
+    #[cfg(feature = "unicode")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "unicode")))]
+    #[must_use]
+    pub const fn normalize_unicode(mut self, form: UnicodeForm) -> Self {
+        self.unicode_form = Some(form);
+        self
+    }
+
+    /// Rejects paths still having percent-escapes like `%2e` after the
+    /// extractor decoded them
+    ///
+    /// They're remnants of double encoding, e.g. `%252e%252e`, and turn into
+    /// traversals if a proxy or a storage SDK downstream decodes the path
+    /// again.
+    #[must_use]
+    pub const fn deny_percent_encoded(mut self, deny: bool) -> Self {
+        self.deny_percent_encoded = deny;
+        self
+    }
+
+    /// Rejects path parameters having `%2F` or `%5C` in their raw form, i.e.
+    /// before the extractor decoded them
+    ///
+    /// They decode into separators, changing the structure of the path after
+    /// routing decisions were made. As the check needs the raw parameter,
+    /// it's only performed by the extractors, not by [`check`](Self::check).
+    #[must_use]
+    pub const fn deny_encoded_separators(mut self, deny: bool) -> Self {
+        self.deny_encoded_separators = deny;
+        self
+    }
+
+    /// Rejects paths that don't look like a file: the last component must have
+    /// an extension and there must be no trailing slash
+    ///
+    /// To require particular extensions, use
+    /// [`allowed_extensions`](Self::allowed_extensions).
+    #[must_use]
+    pub const fn require_extension(mut self, require: bool) -> Self {
+        self.require_extension = require;
+        self
+    }
+
+    /// Rejects paths whose last component doesn't have one of the extensions
+    ///
+    /// Extensions are compared case-insensitively and may contain dots, so
+    /// `tar.gz` matches `backup.TAR.GZ`.
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    ///
+    /// let config = SafePathConfig::new().allowed_extensions(["png", "jpg", "webp"]);
+    /// assert!(config.check("photos/cat.final.PNG").is_ok());
+    /// assert!(config.check("photos/cat.png.exe").is_err());
+    /// ```
+    #[must_use]
+    pub fn allowed_extensions<I>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.allowed_extensions = Some(normalize_extensions(extensions));
+        self
+    }
+
+    /// Rejects paths whose last component has one of the extensions
+    ///
+    /// Extensions are matched the same way as in
+    /// [`allowed_extensions`](Self::allowed_extensions).
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    ///
+    /// let config = SafePathConfig::new().denied_extensions(["php", "exe", "dll", "sh"]);
+    /// assert!(config.check("uploads/report.pdf").is_ok());
+    /// assert!(config.check("uploads/shell.PHP").is_err());
+    /// ```
+    #[must_use]
+    pub fn denied_extensions<I>(mut self, extensions: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.denied_extensions = Some(normalize_extensions(extensions));
+        self
+    }
+
+    /// Makes name matching case-insensitive, using Unicode lowercasing
+    ///
+    /// Affects [`deny_sensitive`](Self::deny_sensitive),
+    /// [`denied_names`](Self::denied_names) and
+    /// [`allowed_globs`](Self::allowed_globs). Extensions are always compared
+    /// case-insensitively. Enable it when the storage is case-insensitive, so
+    /// `.GiT` can't bypass a denied `.git`.
+    #[must_use]
+    pub const fn case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Rejects paths having a component with one of the names at any depth
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    ///
+    /// let config = SafePathConfig::new().denied_names([".git", "node_modules", "target"]);
+    /// assert!(config.check("src/main.rs").is_ok());
+    /// assert!(config.check("web/node_modules/lib/index.js").is_err());
+    /// ```
+    #[must_use]
+    pub fn denied_names<I>(mut self, names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.denied_names = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Rejects paths that don't match any of the glob patterns
+    ///
+    /// Patterns are matched component-wise: `**` matches any number of
+    /// components, `*` matches any characters within a component and `?`
+    /// matches a single character.
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    ///
+    /// let config = SafePathConfig::new().allowed_globs(["assets/**/*.css", "docs/*.md"]);
+    /// assert!(config.check("assets/vendor/reset.css").is_ok());
+    /// assert!(config.check("docs/index.md").is_ok());
+    /// assert!(config.check("docs/drafts/secret.md").is_err());
+    /// ```
+    #[must_use]
+    pub fn allowed_globs<I>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.allowed_globs = Some(patterns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Allows absolute paths, but only those lexically staying under one of the
+    /// roots
+    ///
+    /// Parent directory components are still rejected, so `/var/data/../etc`
+    /// doesn't pass. Relative paths are checked as usual.
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    ///
+    /// let config = SafePathConfig::new().allowed_roots(["/var/data", "/srv/exports"]);
+    /// # #[cfg(unix)]
+    /// assert!(config.check("/var/data/report.csv").is_ok());
+    /// assert!(config.check("/var/database/report.csv").is_err());
+    /// assert!(config.check("/var/data/../../etc/passwd").is_err());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a root isn't an absolute path, as an empty root would allow
+    /// any absolute path and a relative one none.
+    #[must_use]
+    pub fn allowed_roots<I>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        let roots: Arc<[PathBuf]> = roots.into_iter().map(Into::into).collect();
+        for root in roots.iter() {
+            assert!(
+                root.has_root(),
+                "allowed_roots: `{}` is not an absolute path",
+                root.display()
+            );
+        }
+        self.allowed_roots = Some(roots);
+        self
+    }
+
+    /// Rejects paths having a component that doesn't match the pattern
+    ///
+    /// The pattern isn't anchored implicitly, use `^` and `$` to match whole
+    /// components:
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    /// use regex::Regex;
+    ///
+    /// let pattern = Regex::new(r"^[a-z0-9]+(\.[a-z0-9]+)?$").unwrap();
+    /// let config = SafePathConfig::new().component_pattern(pattern);
+    /// assert!(config.check("reports/2024.csv").is_ok());
+    /// assert!(config.check("reports/Q1 2024.csv").is_err());
+    /// ```
+    #[cfg(feature = "regex")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
+    #[must_use]
+    pub fn component_pattern(mut self, pattern: regex::Regex) -> Self {
+        self.component_pattern = Some(pattern);
+        self
+    }
+
+    /// Rejects paths that don't match the pattern as a whole
+    ///
+    /// The pattern isn't anchored implicitly, use `^` and `$` to match the
+    /// whole path.
+    #[cfg(feature = "regex")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "regex")))]
+    #[must_use]
+    pub fn path_pattern(mut self, pattern: regex::Regex) -> Self {
+        self.path_pattern = Some(pattern);
+        self
+    }
+
+    /// Adds a custom [`Policy`] checked after the built-in ones
+    ///
+    /// Calling it multiple times requires all the policies to pass.
+    #[must_use]
+    pub fn policy<P: Policy>(mut self, policy: P) -> Self {
+        self.policy = Some(match self.policy.take() {
+            Some(existing) => Arc::new(And(existing, policy)),
+            None => Arc::new(policy),
+        });
+        self
+    }
+
+    /// Enables the audit-only mode: instead of rejecting invalid paths, the
+    /// hook is called with the path and the rejection, and the path is
+    /// accepted as is
+    ///
+    /// Useful to measure what would break before enforcing the rules:
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathConfig;
+    ///
+    /// let config = SafePathConfig::new()
+    ///     .deny_hidden(true)
+    ///     .audit_only(|path, rejection| eprintln!("{}: {rejection}", path.display()));
+    /// assert!(config.validate(".env").is_ok());
+    /// assert!(config.validate("../.env").is_err());
+    /// ```
+    ///
+    /// Paths with NUL bytes, traversals and Windows UNC, verbatim and device
+    /// prefixes are still rejected without calling the hook, as letting them
+    /// through would make the rest of the app unsafe.
+    #[must_use]
+    pub fn audit_only<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&path::Path, &SafePathRejection) + Send + Sync + 'static,
+    {
+        self.audit = Some(AuditHook(Arc::new(hook)));
+        self
+    }
+
+    /// Replaces the messages of responses to paths rejected by the config
+    ///
+    /// Useful to show product-specific wording instead of the technical
+    /// default ones. The extractors reject such paths with
+    /// [`SafePathRejection::WithMessage`], keeping the code and the status of
+    /// the actual rejection, while [`check`](Self::check) and
+    /// [`validate`](Self::validate) return the actual one. It works with the
+    /// config set by [`Extension`](axum::Extension) too:
+    ///
+    /// ```
+    /// use axum::{Extension, Router, routing::get};
+    /// use axum_safe_path::{SafePath, SafePathConfig};
+    ///
+    /// async fn handler(SafePath(path): SafePath) -> String {
+    ///     path.display().to_string()
+    /// }
+    ///
+    /// let config = SafePathConfig::new().rejection_message("Sorry, we couldn't find this file");
+    /// let app: Router = Router::new()
+    ///     .route("/files/{*path}", get(handler))
+    ///     .layer(Extension(config));
+    /// ```
+    #[must_use]
+    pub fn rejection_message(mut self, message: impl Into<Arc<str>>) -> Self {
+        self.rejection_message = Some(message.into());
+        self
+    }
+
+    /// Checks the path against the config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn check(&self, path: impl AsRef<path::Path>) -> Result<(), SafePathRejection> {
+        let path = path.as_ref();
+
+        if let Some(max) = self.max_length
+            && path.as_os_str().len() > max
+        {
+            return Err(SafePathRejection::TooLong { max });
+        }
+
+        let mut scan = Scan::new(path.as_os_str().as_encoded_bytes());
+        if scan.nul {
+            return Err(SafePathRejection::NulByte);
+        }
+
+        if let Some(rejection) = windows_prefix(path) {
+            return Err(rejection);
+        }
+
+        let path = if self.remote {
+            if scan.backslash {
+                return Err(SafePathRejection::InvalidCharacter);
+            }
+            if has_drive_prefix(path) {
+                return Err(SafePathRejection::TraversalAttack(Traversal::Prefix));
+            }
+            self.strip_remote_root(path)
+        } else {
+            path
+        };
+
+        let converted;
+        let path = if (self.backslash_separator || self.cross_platform)
+            && !self.remote
+            && !cfg!(windows)
+            && scan.backslash
+        {
+            converted = backslashes_to_slashes(path);
+            scan = Scan::new(converted.as_os_str().as_encoded_bytes());
+            converted.as_path()
+        } else {
+            path
+        };
+
+        self.check_structure(path, &scan)?;
+        self.check_components(path, &scan)?;
+        self.check_extensions(path)?;
+        self.check_patterns(path)?;
+
+        if let Some(policy) = &self.policy {
+            policy.check(path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Validates the path, applying normalizations enabled in the config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn validate(&self, path: impl Into<PathBuf>) -> Result<SafePath, SafePathRejection> {
+        self.validate_encoded(path.into(), None)
+    }
+
+    /// Validates the path and normalizes it lexically
+    ///
+    /// The result consists of normal components only, joined with `/`: `.`
+    /// and empty components and the trailing slash are removed, and so are
+    /// backslashes if they are treated as separators. The leading `/` of
+    /// absolute paths accepted with [`allowed_roots`](Self::allowed_roots) or
+    /// [`allow_absolute`](Self::allow_absolute) is kept. As nothing is ever
+    /// added, normalization can't introduce a traversal, so the result can be
+    /// used as a cache or storage key.
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn normalize(&self, path: impl Into<PathBuf>) -> Result<PathBuf, SafePathRejection> {
+        self.validate(path)
+            .map(|SafePath(path)| self.normalized(&path))
+    }
+
+    /// Normalizes the already validated path, see
+    /// [`normalize`](Self::normalize)
+    pub(crate) fn normalized(&self, path: &path::Path) -> PathBuf {
+        let converted;
+        let path = if (self.backslash_separator || self.cross_platform) && !self.remote {
+            converted = backslashes_to_slashes(path);
+            converted.as_path()
+        } else {
+            path
+        };
+        let mut normalized = std::ffi::OsString::new();
+        // Only absolute paths allowed by the config pass the validation, and
+        // dropping the root would make them relative to the current directory
+        if path.has_root() {
+            normalized.push("/");
+        }
+        for (i, name) in normal_components(path).enumerate() {
+            if i > 0 {
+                normalized.push("/");
+            }
+            normalized.push(name);
+        }
+        PathBuf::from(normalized)
+    }
+
+    /// Normalizes the already validated path to join it onto a root
+    ///
+    /// The leading `/` of absolute paths is stripped, so joining can't
+    /// replace the root.
+    pub(crate) fn normalized_relative(&self, path: &path::Path) -> PathBuf {
+        let normalized = self.normalized(path);
+        normalized
+            .strip_prefix("/")
+            .map(path::Path::to_path_buf)
+            .unwrap_or(normalized)
+    }
+
+    /// Validates the path decoded from the raw path parameter
+    pub(crate) fn validate_encoded(
+        &self,
+        mut path: PathBuf,
+        raw: Option<&str>,
+    ) -> Result<SafePath, SafePathRejection> {
+        if self.trailing_slash == TrailingSlash::Strip {
+            path = strip_trailing_slashes(path);
+        }
+        #[cfg(feature = "unicode")]
+        if let Some(form) = self.unicode_form {
+            path = normalize_unicode(path, form);
+        }
+        let result = raw
+            .map_or(Ok(()), |raw| self.check_raw(raw))
+            .and_then(|()| self.check(&path));
+        match (result, &self.audit) {
+            (Ok(()), _) => Ok(SafePath(path)),
+            (Err(rejection), Some(AuditHook(hook))) => {
+                if let Err(rejection) = self.enforced().check(&path)
+                    && is_enforced(&rejection)
+                {
+                    return Err(rejection);
+                }
+                hook(&path, &rejection);
+                Ok(SafePath(path))
+            }
+            (Err(rejection), None) => Err(rejection),
+        }
+    }
+
+    /// The config checking only how the path is interpreted, for finding the
+    /// rejections the audit-only mode still enforces
+    fn enforced(&self) -> Self {
+        Self {
+            max_component_length: usize::MAX,
+            backslash_separator: self.backslash_separator,
+            cross_platform: self.cross_platform,
+            remote: self.remote,
+            allow_absolute: self.allow_absolute,
+            allowed_roots: self.allowed_roots.clone(),
+            ..Self::new()
+        }
+    }
+
+    /// Strips the leading slashes of an absolute remote path if allowed
+    fn strip_remote_root<'a>(&self, path: &'a path::Path) -> &'a path::Path {
+        if self.remote && self.allow_absolute {
+            path.strip_prefix("/").unwrap_or(path)
+        } else {
+            path
+        }
+    }
+
+    /// Checks the raw path parameter
+    fn check_raw(&self, raw: &str) -> Result<(), SafePathRejection> {
+        if self.deny_encoded_separators && raw::has_encoded_separator(raw) {
+            return Err(SafePathRejection::EncodedSeparator);
+        }
+        Ok(())
+    }
+
+    /// Checks the overall structure of the path
+    fn check_structure(&self, path: &path::Path, scan: &Scan) -> Result<(), SafePathRejection> {
+        // Without dot components, only a root or a prefix can be a traversal
+        let may_traverse = scan.may_have_dot_component() || path.has_root();
+        let within_roots = self
+            .allowed_roots
+            .as_ref()
+            .is_some_and(|roots| is_within_roots(path, roots));
+        if may_traverse
+            && !within_roots
+            && let Some(traversal) = find_traversal(path)
+        {
+            return Err(SafePathRejection::TraversalAttack(traversal));
+        }
+
+        if self.cross_platform && has_drive_prefix(path) {
+            return Err(SafePathRejection::TraversalAttack(Traversal::Prefix));
+        }
+
+        if self.deny_current_dir && scan.may_have_dot_component() && has_current_dir(path) {
+            return Err(SafePathRejection::CurrentDir);
+        }
+
+        match (self.trailing_slash, has_trailing_slash(path)) {
+            (TrailingSlash::Forbid, true) => return Err(SafePathRejection::TrailingSlash),
+            (TrailingSlash::Require, false) => {
+                return Err(SafePathRejection::MissingTrailingSlash);
+            }
+            _ => (),
+        }
+
+        let depth = scan.depth().unwrap_or_else(|| depth(path));
+        if self.deny_empty && depth == 0 {
+            return Err(SafePathRejection::Empty);
+        }
+
+        if let Some(max) = self.max_depth
+            && depth > max
+        {
+            return Err(SafePathRejection::TooDeep { max });
+        }
+
+        Ok(())
+    }
+
+    /// Checks names of the path components
+    fn check_components(&self, path: &path::Path, scan: &Scan) -> Result<(), SafePathRejection> {
+        let max = self.max_component_length;
+        if scan.may_have_component_longer_than(max)
+            && normal_components(path).any(|name| name.len() > max)
+        {
+            return Err(SafePathRejection::ComponentTooLong { max });
+        }
+
+        if self.deny_hidden
+            && scan.may_have_dot_component()
+            && normal_components(path).any(is_hidden)
+        {
+            return Err(SafePathRejection::Hidden);
+        }
+
+        if self.deny_sensitive && normal_components(path).any(|name| self.is_sensitive(name)) {
+            return Err(SafePathRejection::Sensitive);
+        }
+
+        if let Some(denied) = &self.denied_names
+            && normal_components(path).any(|name| {
+                let name = name.to_string_lossy();
+                let name = self.fold_case(&name);
+                denied.iter().any(|denied| self.fold_case(denied) == name)
+            })
+        {
+            return Err(SafePathRejection::DeniedName);
+        }
+
+        if self.deny_non_portable && !normal_components(path).all(is_portable) {
+            return Err(SafePathRejection::NonPortable);
+        }
+
+        if (self.deny_alternate_streams || self.cross_platform)
+            && normal_components(path).any(has_alternate_stream)
+        {
+            return Err(SafePathRejection::AlternateDataStream);
+        }
+
+        if self.deny_windows_chars && normal_components(path).any(has_windows_forbidden_chars) {
+            return Err(SafePathRejection::InvalidCharacter);
+        }
+
+        if self.deny_control_chars && normal_components(path).any(has_control_chars) {
+            return Err(SafePathRejection::InvalidCharacter);
+        }
+
+        if (self.deny_trailing_dots || self.cross_platform)
+            && normal_components(path).any(has_trailing_dot_or_space)
+        {
+            return Err(SafePathRejection::TrailingDotOrSpace);
+        }
+
+        if (self.deny_reserved_names || self.cross_platform)
+            && normal_components(path).any(is_windows_reserved)
+        {
+            return Err(SafePathRejection::ReservedName);
+        }
+
+        if self.deny_percent_encoded
+            && scan.percent
+            && normal_components(path).any(has_percent_escape)
+        {
+            return Err(SafePathRejection::PercentEncoded);
+        }
+
+        if self.deny_lookalike_dots && normal_components(path).any(is_lookalike_dots) {
+            return Err(SafePathRejection::LookalikeDots);
+        }
+
+        Ok(())
+    }
+
+    /// Checks a single file name with the rules applying to names, like
+    /// hidden, sensitive and denied names, skipping the ones about the whole
+    /// path such as extensions or trailing slashes
+    #[cfg(all(feature = "fs", feature = "json"))]
+    pub(crate) fn check_name(&self, name: &OsStr) -> Result<(), SafePathRejection> {
+        let scan = Scan::new(name.as_encoded_bytes());
+        if scan.nul {
+            return Err(SafePathRejection::NulByte);
+        }
+        self.check_components(path::Path::new(name), &scan)
+    }
+
+    /// Checks the extension of the last path component
+    fn check_extensions(&self, path: &path::Path) -> Result<(), SafePathRejection> {
+        if self.require_extension
+            && (has_trailing_slash(path) || path.extension().is_none_or(OsStr::is_empty))
+        {
+            return Err(SafePathRejection::MissingExtension);
+        }
+
+        if let Some(allowed) = &self.allowed_extensions
+            && !file_name(path).is_some_and(|name| has_any_extension(&name, allowed))
+        {
+            return Err(SafePathRejection::ExtensionNotAllowed);
+        }
+
+        if let Some(denied) = &self.denied_extensions
+            && file_name(path).is_some_and(|name| has_any_extension(&name, denied))
+        {
+            return Err(SafePathRejection::ExtensionDenied);
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the name belongs to a well-known sensitive or backup file
+    fn is_sensitive(&self, name: &OsStr) -> bool {
+        let name = name.to_string_lossy();
+        let name = self.fold_case(&name);
+        SENSITIVE_NAMES
+            .iter()
+            .any(|sensitive| self.fold_case(sensitive) == name)
+            || SENSITIVE_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+    }
+
+    /// Lowercases the string if the config is case-insensitive
+    fn fold_case<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        if self.case_insensitive {
+            Cow::Owned(s.to_lowercase())
+        } else {
+            Cow::Borrowed(s)
+        }
+    }
+
+    /// Checks the path against the glob and regex patterns
+    fn check_patterns(&self, path: &path::Path) -> Result<(), SafePathRejection> {
+        if let Some(globs) = &self.allowed_globs {
+            let components: Vec<_> = normal_components(path)
+                .map(|name| self.fold_case(&name.to_string_lossy()).into_owned())
+                .collect();
+            if !globs.iter().any(|pattern| {
+                glob::matches(
+                    &self.fold_case(pattern),
+                    components.iter().map(AsRef::as_ref),
+                )
+            }) {
+                return Err(SafePathRejection::PatternMismatch);
+            }
+        }
+
+        #[cfg(feature = "regex")]
+        {
+            let matches =
+                |pattern: &regex::Regex, s: &OsStr| s.to_str().is_some_and(|s| pattern.is_match(s));
+
+            if let Some(pattern) = &self.component_pattern
+                && !normal_components(path).all(|name| matches(pattern, name))
+            {
+                return Err(SafePathRejection::PatternMismatch);
+            }
+
+            if let Some(pattern) = &self.path_pattern
+                && !matches(pattern, path.as_os_str())
+            {
+                return Err(SafePathRejection::PatternMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the closure with the config stored in request extensions or the
+    /// default one
+    pub(crate) fn with_current<T>(extensions: &Extensions, f: impl FnOnce(&Self) -> T) -> T {
+        #[cfg(feature = "arc-swap")]
+        if let Some(config) = extensions.get::<ReloadableConfig>() {
+            return config.with(f);
+        }
+        f(extensions.get().unwrap_or(&DEFAULT_CONFIG))
+    }
+
+    /// Checks if both configs validate paths the same way, comparing custom
+    /// policies, hooks and patterns by identity
+    pub(crate) fn same_as(&self, other: &Self) -> bool {
+        let Self {
+            max_depth,
+            max_length,
+            max_component_length,
+            deny_empty,
+            deny_hidden,
+            deny_sensitive,
+            deny_non_portable,
+            deny_current_dir,
+            deny_windows_chars,
+            deny_lookalike_dots,
+            deny_control_chars,
+            deny_trailing_dots,
+            deny_reserved_names,
+            deny_alternate_streams,
+            backslash_separator,
+            cross_platform,
+            remote,
+            allow_absolute,
+            deny_percent_encoded,
+            deny_encoded_separators,
+            require_extension,
+            case_insensitive,
+            allowed_extensions,
+            denied_extensions,
+            denied_names,
+            allowed_globs,
+            allowed_roots,
+            trailing_slash,
+            policy,
+            audit,
+            rejection_message,
+            #[cfg(feature = "regex")]
+            component_pattern,
+            #[cfg(feature = "regex")]
+            path_pattern,
+            #[cfg(feature = "unicode")]
+            unicode_form,
+        } = self;
+
+        #[cfg(feature = "regex")]
+        {
+            fn pattern(regex: Option<&regex::Regex>) -> Option<&str> {
+                regex.map(regex::Regex::as_str)
+            }
+            if pattern(component_pattern.as_ref()) != pattern(other.component_pattern.as_ref())
+                || pattern(path_pattern.as_ref()) != pattern(other.path_pattern.as_ref())
+            {
+                return false;
+            }
+        }
+        #[cfg(feature = "unicode")]
+        if *unicode_form != other.unicode_form {
+            return false;
+        }
+
+        *max_depth == other.max_depth
+            && *max_length == other.max_length
+            && *max_component_length == other.max_component_length
+            && *deny_empty == other.deny_empty
+            && *deny_hidden == other.deny_hidden
+            && *deny_sensitive == other.deny_sensitive
+            && *deny_non_portable == other.deny_non_portable
+            && *deny_current_dir == other.deny_current_dir
+            && *deny_windows_chars == other.deny_windows_chars
+            && *deny_lookalike_dots == other.deny_lookalike_dots
+            && *deny_control_chars == other.deny_control_chars
+            && *deny_trailing_dots == other.deny_trailing_dots
+            && *deny_reserved_names == other.deny_reserved_names
+            && *deny_alternate_streams == other.deny_alternate_streams
+            && *backslash_separator == other.backslash_separator
+            && *cross_platform == other.cross_platform
+            && *remote == other.remote
+            && *allow_absolute == other.allow_absolute
+            && *deny_percent_encoded == other.deny_percent_encoded
+            && *deny_encoded_separators == other.deny_encoded_separators
+            && *require_extension == other.require_extension
+            && *case_insensitive == other.case_insensitive
+            && *allowed_extensions == other.allowed_extensions
+            && *denied_extensions == other.denied_extensions
+            && *denied_names == other.denied_names
+            && *allowed_globs == other.allowed_globs
+            && *allowed_roots == other.allowed_roots
+            && *trailing_slash == other.trailing_slash
+            && *audit == other.audit
+            && *rejection_message == other.rejection_message
+            && match (policy, &other.policy) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
+    }
+
+    /// Runs the validating closure with the current config, see
+    /// [`trace::validation`], and stores the [`SafePathOutcome`]
+    pub(crate) fn validate_current<T: Validated>(
+        extensions: &mut Extensions,
+        input: &str,
+        f: impl FnOnce(&Self) -> Result<T, SafePathRejection>,
+    ) -> Result<T, SafePathRejection> {
+        let result = trace::validation(extensions, input, || {
+            Self::with_current(extensions, |config| {
+                f(config).map_err(|rejection| config.with_message(rejection))
+            })
+        });
+        SafePathOutcome::record(extensions, &result);
+        result
+    }
+
+    /// Runs the validating closure with this config instead of the current
+    /// one, see [`validate_current`](Self::validate_current)
+    #[cfg(feature = "fs")]
+    pub(crate) fn validate_with<T: Validated>(
+        &self,
+        extensions: &mut Extensions,
+        input: &str,
+        f: impl FnOnce(&Self) -> Result<T, SafePathRejection>,
+    ) -> Result<T, SafePathRejection> {
+        let result = trace::validation(extensions, input, || {
+            f(self).map_err(|rejection| self.with_message(rejection))
+        });
+        SafePathOutcome::record(extensions, &result);
+        result
+    }
+
+    /// Attaches the message of the config to the rejection, see
+    /// [`rejection_message`](Self::rejection_message)
+    fn with_message(&self, rejection: SafePathRejection) -> SafePathRejection {
+        match (&self.rejection_message, rejection) {
+            (_, rejection @ SafePathRejection::PathExtraction(_)) | (None, rejection) => rejection,
+            (Some(message), rejection) => SafePathRejection::WithMessage {
+                rejection: Arc::new(rejection),
+                message: message.clone(),
+            },
+        }
+    }
+}
+
+/// Checks if the rejection is enforced in the audit-only mode, as the path
+/// could reach outside of the root or be truncated at a NUL byte
+const fn is_enforced(rejection: &SafePathRejection) -> bool {
+    matches!(
+        rejection,
+        SafePathRejection::NulByte
+            | SafePathRejection::TraversalAttack(_)
+            | SafePathRejection::UncPath
+            | SafePathRejection::VerbatimPath
+            | SafePathRejection::DevicePath
+    )
+}
+
+/// Counts normal components of the path, ignoring `.` and separators
+pub fn depth(path: &path::Path) -> usize {
+    normal_components(path).count()
+}
+
+/// Checks if the path ends with a separator
+pub fn has_trailing_slash(path: &path::Path) -> bool {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .last()
+        .is_some_and(|&b| path::is_separator(b.into()))
+}
+
+/// Detects Windows UNC, verbatim and device namespace prefixes in the raw
+/// path, with either kind of slashes, regardless of the platform
+fn windows_prefix(path: &path::Path) -> Option<SafePathRejection> {
+    let is_separator = |b: &u8| matches!(b, b'/' | b'\\');
+    match path.as_os_str().as_encoded_bytes() {
+        [a, b'?', b'?', b, ..] if is_separator(a) && is_separator(b) => {
+            Some(SafePathRejection::VerbatimPath)
+        }
+        [a, b, rest @ ..] if is_separator(a) && is_separator(b) => match rest {
+            [b'?', c, ..] if is_separator(c) => Some(SafePathRejection::VerbatimPath),
+            [b'.', c, ..] if is_separator(c) => Some(SafePathRejection::DevicePath),
+            _ => Some(SafePathRejection::UncPath),
+        },
+        _ => None,
+    }
+}
+
+/// Converts the path into the Unicode normalization form
+#[cfg(feature = "unicode")]
+fn normalize_unicode(path: PathBuf, form: UnicodeForm) -> PathBuf {
+    use unicode_normalization::UnicodeNormalization;
+
+    let Some(s) = path.to_str() else {
+        return path;
+    };
+    match form {
+        UnicodeForm::Nfc => PathBuf::from(s.nfc().collect::<String>()),
+        UnicodeForm::Nfd => PathBuf::from(s.nfd().collect::<String>()),
+    }
+}
+
+/// Replaces backslashes with slashes
+fn backslashes_to_slashes(path: &path::Path) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+
+        let bytes = path
+            .as_os_str()
+            .as_encoded_bytes()
+            .iter()
+            .map(|&b| if b == b'\\' { b'/' } else { b })
+            .collect();
+        PathBuf::from(std::ffi::OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    PathBuf::from(path.to_string_lossy().replace('\\', "/"))
+}
+
+/// Removes trailing separators, keeping a lone root one
+fn strip_trailing_slashes(path: PathBuf) -> PathBuf {
+    let Some(s) = path.to_str() else {
+        return path;
+    };
+    let stripped = s.trim_end_matches(path::is_separator);
+    if stripped.is_empty() {
+        PathBuf::from(s.get(..1).unwrap_or_default())
+    } else {
+        PathBuf::from(stripped)
+    }
+}
+
+/// Checks if the path lexically stays under one of the roots
+fn is_within_roots(path: &path::Path, roots: &[PathBuf]) -> bool {
+    !path
+        .components()
+        .any(|component| component == Component::ParentDir)
+        && roots.iter().any(|root| path.starts_with(root))
+}
+
+/// Checks if the path has `.` components
+///
+/// [`path::Path::components`] skips them everywhere except the beginning, so
+/// the raw path is scanned.
+fn has_current_dir(path: &path::Path) -> bool {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .split(|&b| path::is_separator(b.into()))
+        .any(|segment| segment == b".")
+}
+
+/// Checks if the path starts with a Windows drive letter, like `C:` or `c:foo`
+pub fn has_drive_prefix(path: &path::Path) -> bool {
+    component::has_drive_prefix(path.as_os_str().as_encoded_bytes())
+}
+
+/// Checks if the name starts with a dot
+pub fn is_hidden(name: &OsStr) -> bool {
+    component::is_hidden(name.as_encoded_bytes())
+}
+
+/// Checks if the name consists of POSIX portable filename characters and
+/// doesn't start with a hyphen
+fn is_portable(name: &OsStr) -> bool {
+    component::is_portable(name.as_encoded_bytes())
+}
+
+/// Checks if the name has characters not allowed on Windows
+fn has_windows_forbidden_chars(name: &OsStr) -> bool {
+    component::has_windows_forbidden_chars(name.as_encoded_bytes())
+}
+
+/// Checks if the name refers to an NTFS alternate data stream
+fn has_alternate_stream(name: &OsStr) -> bool {
+    component::has_alternate_stream(name.as_encoded_bytes())
+}
+
+/// Checks if the name has control, zero-width or bidirectional formatting
+/// characters
+fn has_control_chars(name: &OsStr) -> bool {
+    component::has_control_chars(name.as_encoded_bytes())
+}
+
+/// Checks if the name ends with a dot or a space
+fn has_trailing_dot_or_space(name: &OsStr) -> bool {
+    component::has_trailing_dot_or_space(name.as_encoded_bytes())
+}
+
+/// Checks if the name refers to a Windows device
+fn is_windows_reserved(name: &OsStr) -> bool {
+    component::is_windows_reserved(name.as_encoded_bytes())
+}
+
+/// Checks if the name has a percent-escape, i.e. `%` followed by two hex
+/// digits
+fn has_percent_escape(name: &OsStr) -> bool {
+    component::has_percent_escape(name.as_encoded_bytes())
+}
+
+/// Checks if the name consists of dots and their look-alikes, having at least
+/// one look-alike
+fn is_lookalike_dots(name: &OsStr) -> bool {
+    component::is_lookalike_dots(name.as_encoded_bytes())
+}
+
+/// Lowercases extensions and strips leading dots from them
+fn normalize_extensions<I>(extensions: I) -> Arc<[String]>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    extensions
+        .into_iter()
+        .map(|ext| ext.as_ref().trim_start_matches('.').to_lowercase())
+        .collect()
+}
+
+/// Returns the lowercased name of the last path component
+pub fn file_name(path: &path::Path) -> Option<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+}
+
+/// Checks if the lowercased file name ends with one of the extensions
+fn has_any_extension(name: &str, extensions: &[String]) -> bool {
+    extensions.iter().any(|ext| has_extension(name, ext))
+}
+
+/// Checks if the lowercased file name ends with the extension and has a
+/// non-empty stem
+pub fn has_extension(name: &str, ext: &str) -> bool {
+    name.strip_suffix(ext)
+        .and_then(|stem| stem.strip_suffix('.'))
+        .is_some_and(|stem| !stem.is_empty())
+}
+
+/// Iterates over names of the path components, skipping `.`, `..` and roots
+pub fn normal_components(path: &path::Path) -> impl Iterator<Item = &OsStr> {
+    path.components().filter_map(|component| match component {
+        Component::Normal(name) => Some(name),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_depth() {
+        let config = SafePathConfig::new().max_depth(2);
+        assert!(config.check("foo").is_ok());
+        assert!(config.check("foo/bar").is_ok());
+        assert!(config.check("./foo/./bar/").is_ok());
+        assert!(matches!(
+            config.check("foo/bar/baz"),
+            Err(SafePathRejection::TooDeep { max: 2 })
+        ));
+    }
+
+    #[test]
+    fn max_length() {
+        let config = SafePathConfig::new().max_length(7);
+        assert!(config.check("foo/bar").is_ok());
+        assert!(matches!(
+            config.check("foo/bar2"),
+            Err(SafePathRejection::TooLong { max: 7 })
+        ));
+    }
+
+    #[test]
+    fn length_is_checked_before_traversal() {
+        let config = SafePathConfig::new().max_length(2);
+        assert!(matches!(
+            config.check("../secret"),
+            Err(SafePathRejection::TooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn max_component_length() {
+        let config = SafePathConfig::new().max_component_length(3);
+        assert!(config.check("foo/bar").is_ok());
+        assert!(matches!(
+            config.check("foo/barz"),
+            Err(SafePathRejection::ComponentTooLong { max: 3 })
+        ));
+    }
+
+    #[test]
+    fn default_component_length() {
+        let config = SafePathConfig::default();
+        assert!(config.check("a".repeat(255)).is_ok());
+        assert!(matches!(
+            config.check(format!("foo/{}", "a".repeat(256))),
+            Err(SafePathRejection::ComponentTooLong { max: 255 })
+        ));
+    }
+
+    #[test]
+    fn deny_empty() {
+        let config = SafePathConfig::new().deny_empty(true);
+        assert!(config.check("foo").is_ok());
+        assert!(config.check("./foo").is_ok());
+        for path in ["", ".", "./", "./."] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::Empty)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_is_allowed_by_default() {
+        assert!(SafePathConfig::default().check("").is_ok());
+        assert!(SafePathConfig::default().check(".").is_ok());
+    }
+
+    #[test]
+    fn deny_current_dir() {
+        let config = SafePathConfig::new().deny_current_dir(true);
+        for path in ["", "foo", "foo/bar", "foo/.bar", "foo/..."] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [".", "./foo", "foo/./bar", "foo/.", "foo//./bar"] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::CurrentDir)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let config = SafePathConfig::new();
+        assert!(config.check("foo/").is_ok());
+        assert!(config.check("foo").is_ok());
+
+        let config = SafePathConfig::new().trailing_slash(TrailingSlash::Forbid);
+        assert!(config.check("foo/bar").is_ok());
+        assert!(matches!(
+            config.check("foo/bar/"),
+            Err(SafePathRejection::TrailingSlash)
+        ));
+
+        let config = SafePathConfig::new().trailing_slash(TrailingSlash::Require);
+        assert!(config.check("foo/bar/").is_ok());
+        assert!(matches!(
+            config.check("foo/bar"),
+            Err(SafePathRejection::MissingTrailingSlash)
+        ));
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn strip_trailing_slash() {
+        let config = SafePathConfig::new().trailing_slash(TrailingSlash::Strip);
+        let path = config.validate("foo/bar//").unwrap();
+        assert_eq!(path.0.as_os_str(), "foo/bar");
+        assert!(!path.has_trailing_slash());
+
+        assert_eq!(strip_trailing_slashes("/".into()).as_os_str(), "/");
+        assert_eq!(strip_trailing_slashes("".into()).as_os_str(), "");
+    }
+
+    #[test]
+    fn deny_hidden() {
+        let config = SafePathConfig::new().deny_hidden(true);
+        assert!(config.check("foo/bar.txt").is_ok());
+        assert!(config.check("./foo/bar.txt").is_ok());
+        for path in [".env", ".git/config", "foo/.htaccess", "foo/.../bar"] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::Hidden)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn deny_sensitive() {
+        let config = SafePathConfig::new().deny_sensitive(true);
+        assert!(config.check("foo/bar.txt").is_ok());
+        assert!(config.check(".well-known/security.txt").is_ok());
+        for path in [
+            ".env",
+            ".git/config",
+            "home/.ssh/id_rsa",
+            "web.config",
+            "foo/.DS_Store",
+            "config.php~",
+            "db.sqlite.bak",
+        ] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::Sensitive)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn denied_names() {
+        let config = SafePathConfig::new().denied_names([".git", "__MACOSX"]);
+        for path in ["", "git", "a/.gitignore", "a/b/MACOSX"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [".git", "a/.git/config", "a/b/__MACOSX/c"] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::DeniedName)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let config = SafePathConfig::new()
+            .deny_sensitive(true)
+            .denied_names(["node_modules", "\u{130}nc"])
+            .allowed_globs(["Docs/**"]);
+        assert!(config.check("Docs/.GIT/config").is_ok());
+        assert!(config.check("Docs/Node_Modules").is_ok());
+        assert!(config.check("docs/a").is_err());
+
+        let config = config.case_insensitive(true);
+        assert!(config.check("docs/a").is_ok());
+        assert!(matches!(
+            config.check("Docs/.GIT/config"),
+            Err(SafePathRejection::Sensitive)
+        ));
+        assert!(matches!(
+            config.check("DOCS/ID_RSA"),
+            Err(SafePathRejection::Sensitive)
+        ));
+        assert!(matches!(
+            config.check("Docs/Node_Modules"),
+            Err(SafePathRejection::DeniedName)
+        ));
+        assert!(matches!(
+            config.check("Docs/i\u{307}NC"),
+            Err(SafePathRejection::DeniedName)
+        ));
+    }
+
+    #[test]
+    fn deny_non_portable() {
+        let config = SafePathConfig::new().deny_non_portable(true);
+        for path in ["", "foo/bar.txt", "Foo_Bar-1.2/baz-", "./a"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [
+            "-rf",
+            "foo/-n",
+            "foo bar",
+            "foo/b;r",
+            "caf\u{e9}",
+            "a=b",
+            "$HOME",
+        ] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::NonPortable)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn deny_windows_chars() {
+        let config = SafePathConfig::new().deny_windows_chars(true);
+        assert!(config.check("foo/bar baz.txt").is_ok());
+        for path in ["a<b", "a>b", "foo:bar", "a\"b", "a|b", "a?b", "a*b", "a\tb"] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::InvalidCharacter)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn presets() {
+        let config = SafePathConfig::web_assets();
+        assert!(config.check("css/main.css").is_ok());
+        assert!(config.check("index.php").is_err());
+        assert!(config.check(".git/config").is_err());
+
+        let config = SafePathConfig::images_only();
+        assert!(config.check("avatars/me.PNG").is_ok());
+        assert!(config.check("avatars/me.svg").is_err());
+        assert!(config.check("avatars/my avatar.png").is_err());
+
+        let config = SafePathConfig::windows_safe();
+        assert!(config.check("docs/report.txt").is_ok());
+        assert!(config.check("docs/report?.txt").is_err());
+    }
+
+    #[test]
+    fn deny_control_chars() {
+        let config = SafePathConfig::new().deny_control_chars(true);
+        assert!(config.check("caf\u{e9}/\u{1F600}.txt").is_ok());
+        for path in [
+            "a\u{1}b",
+            "a\nb",
+            "a\u{7F}",
+            "a\u{85}",
+            "a\u{200B}b",
+            "a\u{200D}b",
+            "invoice\u{202E}fdp.exe",
+            "a\u{2067}b",
+            "\u{FEFF}a",
+        ] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::InvalidCharacter)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn deny_trailing_dots() {
+        let config = SafePathConfig::new().deny_trailing_dots(true);
+        for path in ["passwd", ".env", "a b/c.d", "./a"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [
+            "passwd.",
+            "passwd ",
+            "dir./file",
+            "dir /file",
+            "...",
+            "a. . ",
+        ] {
+            assert!(
+                matches!(
+                    config.check(path),
+                    Err(SafePathRejection::TrailingDotOrSpace)
+                ),
+                "{path:?}"
+            );
+        }
+        assert!(SafePathConfig::windows_safe().check("passwd.").is_err());
+    }
+
+    #[test]
+    fn deny_reserved_names() {
+        let config = SafePathConfig::new().deny_reserved_names(true);
+        for path in ["console", "a/nul2", "com10", "lpt", "icon.png", "CON_1"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [
+            "CON",
+            "con",
+            "a/Nul.txt",
+            "aux.tar.gz",
+            "COM1",
+            "lpt9.log",
+            "prn /a",
+            "com\u{B9}",
+            "conin$",
+        ] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::ReservedName)),
+                "{path:?}"
+            );
+        }
+        assert!(SafePathConfig::windows_safe().check("nul.txt").is_err());
+    }
+
+    #[test]
+    fn deny_alternate_streams() {
+        let config = SafePathConfig::new().deny_alternate_streams(true);
+        assert!(config.check("a/name.txt").is_ok());
+        for path in ["name.txt::$DATA", "a/image.png:payload.php", "dir:$i30/a"] {
+            assert!(
+                matches!(
+                    config.check(path),
+                    Err(SafePathRejection::AlternateDataStream)
+                ),
+                "{path:?}"
+            );
+        }
+        assert!(matches!(
+            SafePathConfig::windows_safe().check("a.txt::$DATA"),
+            Err(SafePathRejection::AlternateDataStream)
+        ));
+    }
+
+    #[test]
+    fn deny_percent_encoded() {
+        let config = SafePathConfig::new().deny_percent_encoded(true);
+        for path in ["a/b.txt", "100%", "50%off", "%zz", "a%2"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in ["%2e%2e/secret", "a/%2Fetc", "name%00.txt", "%252e"] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::PercentEncoded)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn deny_lookalike_dots() {
+        let config = SafePathConfig::new().deny_lookalike_dots(true);
+        for path in ["foo", "...", "a\u{2026}b", "foo/\u{FF0E}env"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [
+            "\u{2025}",
+            "foo/\u{FF0E}\u{FF0E}/bar",
+            ".\u{2024}",
+            "\u{2026}",
+            "\u{3002}\u{FF61}",
+            "\u{FE52}",
+        ] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::LookalikeDots)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn require_extension() {
+        let config = SafePathConfig::new().require_extension(true);
+        for path in ["a.txt", "foo/a.tar.gz", "foo/.env.local"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in ["", ".", "foo", "foo/", "a.txt/", "foo/.env", "foo."] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::MissingExtension)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn allowed_extensions() {
+        let config = SafePathConfig::new().allowed_extensions(["png", ".JPG", "tar.gz"]);
+        for path in ["cat.png", "a/b/cat.PNG", "cat.final.jpg", "backup.Tar.Gz"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in ["", "cat", "cat.gif", "cat.png.exe", ".png", "png"] {
+            assert!(
+                matches!(
+                    config.check(path),
+                    Err(SafePathRejection::ExtensionNotAllowed)
+                ),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn denied_extensions() {
+        let config = SafePathConfig::new().denied_extensions(["php", "exe"]);
+        for path in [
+            "",
+            "report.pdf",
+            "php",
+            ".php",
+            "php/index.html",
+            "shell.php.txt",
+        ] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in ["shell.php", "a/b/SHELL.PhP", "setup.tar.exe"] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::ExtensionDenied)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn allowed_globs() {
+        let config = SafePathConfig::new().allowed_globs(["assets/**/*.css", "docs/*.md"]);
+        for path in ["assets/main.css", "./assets/css/main.css", "docs/index.md"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [
+            "",
+            "assets",
+            "assets/main.js",
+            "docs/api/index.md",
+            "main.css",
+        ] {
+            assert!(
+                matches!(config.check(path), Err(SafePathRejection::PatternMismatch)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn normalize_allowed_roots() {
+        let config = SafePathConfig::new().allowed_roots(["/var/data"]);
+        for (path, expected) in [
+            ("/var/data/x", "/var/data/x"),
+            ("/var//data/./x/", "/var/data/x"),
+            ("x/y", "x/y"),
+        ] {
+            assert!(
+                config
+                    .normalize(path)
+                    .is_ok_and(|normalized| normalized.as_os_str() == expected),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn allowed_roots() {
+        let config = SafePathConfig::new().allowed_roots(["/var/data", "/srv/exports/"]);
+        for path in ["foo", "/var/data", "/var/data/foo", "/srv/exports/a/b"] {
+            assert!(config.check(path).is_ok(), "{path:?}");
+        }
+        for path in [
+            "/",
+            "/etc/passwd",
+            "/var/database",
+            "/var/data/../../etc/passwd",
+            "../var/data",
+        ] {
+            assert!(
+                matches!(
+                    config.check(path),
+                    Err(SafePathRejection::TraversalAttack(_))
+                ),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "allowed_roots: `` is not an absolute path")]
+    fn empty_allowed_root() {
+        let _config = SafePathConfig::new().allowed_roots([""]);
+    }
+
+    #[test]
+    #[should_panic(expected = "allowed_roots: `data` is not an absolute path")]
+    fn relative_allowed_root() {
+        let _config = SafePathConfig::new().allowed_roots(["data"]);
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    #[allow(clippy::unwrap_used)]
+    fn component_pattern() {
+        let config =
+            SafePathConfig::new().component_pattern(regex::Regex::new("^[a-z]+$").unwrap());
+        assert!(config.check("foo/bar").is_ok());
+        assert!(config.check("./foo/").is_ok());
+        assert!(matches!(
+            config.check("foo/bar1"),
+            Err(SafePathRejection::PatternMismatch)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    #[allow(clippy::unwrap_used)]
+    fn path_pattern() {
+        let config = SafePathConfig::new().path_pattern(regex::Regex::new(r"^docs/\w+").unwrap());
+        assert!(config.check("docs/index.md").is_ok());
+        assert!(matches!(
+            config.check("src/lib.rs"),
+            Err(SafePathRejection::PatternMismatch)
+        ));
+    }
+
+    #[test]
+    #[allow(clippy::unwrap_used)]
+    fn audit_only() {
+        let audited = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let config = SafePathConfig::new().deny_hidden(true).audit_only({
+            let audited = audited.clone();
+            move |path, rejection| {
+                if let Ok(mut audited) = audited.lock() {
+                    audited.push(format!("{}: {rejection}", path.display()));
+                }
+            }
+        });
+
+        assert!(config.validate("foo").is_ok());
+        assert!(config.validate(".env").is_ok());
+        assert_eq!(
+            config.validate("../.env").err(),
+            Some(SafePathRejection::TraversalAttack(Traversal::ParentDir {
+                index: 0
+            }))
+        );
+        assert_eq!(
+            config.validate(".env\0../x").err(),
+            Some(SafePathRejection::NulByte)
+        );
+        assert_eq!(
+            *audited.lock().unwrap(),
+            [".env: Invalid path: hidden files are not allowed"]
+        );
+
+        // Traversals are found even behind checks running before them
+        let config = SafePathConfig::new().max_length(8).audit_only(|_, _| ());
+        assert!(config.validate("a/very/long/path").is_ok());
+        assert!(matches!(
+            config.validate("a/../../etc/passwd"),
+            Err(SafePathRejection::TraversalAttack(_))
+        ));
+    }
+
+    #[test]
+    fn backslash_separator() {
+        let config = SafePathConfig::new();
+        assert!(config.check("a\\b.txt").is_ok());
+        for path in ["..\\..\\secret", "a\\..\\..\\b", "\\etc\\passwd"] {
+            assert!(
+                matches!(
+                    config.check(path),
+                    Err(SafePathRejection::TraversalAttack(_))
+                ),
+                "{path:?}"
+            );
+        }
+        assert!(matches!(
+            config.clone().max_depth(1).check("a\\b"),
+            Err(SafePathRejection::TooDeep { max: 1 })
+        ));
+        assert!(matches!(
+            config.clone().deny_hidden(true).check("a\\.env"),
+            Err(SafePathRejection::Hidden)
+        ));
+        assert!(
+            config
+                .validate("a\\")
+                .is_ok_and(|path| path.0.as_os_str() == "a\\")
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn backslash_separator_disabled() {
+        let config = SafePathConfig::new().backslash_separator(false);
+        assert!(config.check("..\\..\\secret").is_ok());
+    }
+
+    #[test]
+    fn windows_prefixes() {
+        use std::mem;
+
+        let config = SafePathConfig::new().backslash_separator(false);
+        for (path, expected) in [
+            ("\\\\server\\share\\a", SafePathRejection::UncPath),
+            ("//server/share", SafePathRejection::UncPath),
+            ("\\\\?\\C:\\a", SafePathRejection::VerbatimPath),
+            ("\\\\?\\UNC\\server\\share", SafePathRejection::VerbatimPath),
+            ("//?/C:/a", SafePathRejection::VerbatimPath),
+            ("\\??\\C:\\a", SafePathRejection::VerbatimPath),
+            ("\\\\.\\PhysicalDrive0", SafePathRejection::DevicePath),
+            ("\\/./COM1", SafePathRejection::DevicePath),
+        ] {
+            assert_eq!(
+                config.check(path).as_ref().map_err(mem::discriminant),
+                Err(mem::discriminant(&expected)),
+                "{path:?}"
+            );
+        }
+        assert!(config.check("a//b").is_ok());
+    }
+
+    #[test]
+    fn remote() {
+        let config = SafePathConfig::sftp();
+        assert!(config.check("home/user/a.txt").is_ok());
+        for (path, expected) in [
+            (
+                "/etc/passwd",
+                SafePathRejection::TraversalAttack(Traversal::RootDir),
+            ),
+            (
+                "a/../../b",
+                SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 1 }),
+            ),
+            (
+                "C:/Windows",
+                SafePathRejection::TraversalAttack(Traversal::Prefix),
+            ),
+            ("a\\b.txt", SafePathRejection::InvalidCharacter),
+            ("a\u{1b}b", SafePathRejection::InvalidCharacter),
+            ("//server/share", SafePathRejection::UncPath),
+        ] {
+            assert_eq!(
+                config.check(path).as_ref().map_err(std::mem::discriminant),
+                Err(std::mem::discriminant(&expected)),
+                "{path:?}"
+            );
+        }
+        let long = "a/".repeat(2048) + "b";
+        assert!(matches!(
+            config.check(long),
+            Err(SafePathRejection::TooLong { max: 4096 })
+        ));
+        // Reserved Windows names are fine on the server
+        assert!(config.check("aux.txt").is_ok());
+
+        let config = config.allow_absolute(true);
+        assert!(config.check("/home/user/a.txt").is_ok());
+        assert!(config.check("/../etc/passwd").is_err());
+        for (path, expected) in [
+            ("/home//user/./a.txt", "/home/user/a.txt"),
+            ("home/a.txt", "home/a.txt"),
+        ] {
+            assert!(
+                config
+                    .normalize(path)
+                    .is_ok_and(|normalized| normalized.as_os_str() == expected),
+                "{path:?}"
+            );
+        }
+        // Local paths stay relative
+        assert!(
+            SafePathConfig::new()
+                .allow_absolute(true)
+                .check("/etc/passwd")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn cross_platform() {
+        let config = SafePathConfig::new()
+            .backslash_separator(false)
+            .cross_platform(true);
+        assert!(config.check("docs/a.txt").is_ok());
+        for (path, expected) in [
+            (
+                "a\\..\\..\\b",
+                SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 1 }),
+            ),
+            (
+                "C:/Windows",
+                SafePathRejection::TraversalAttack(Traversal::Prefix),
+            ),
+            (
+                "c:secret",
+                SafePathRejection::TraversalAttack(Traversal::Prefix),
+            ),
+            ("a/aux.txt", SafePathRejection::ReservedName),
+            ("a.txt::$DATA", SafePathRejection::AlternateDataStream),
+            ("passwd.", SafePathRejection::TrailingDotOrSpace),
+        ] {
+            assert_eq!(
+                config.check(path).as_ref().map_err(std::mem::discriminant),
+                Err(std::mem::discriminant(&expected)),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "unicode")]
+    fn normalize_unicode() {
+        let nfc = "caf\u{e9}.txt";
+        let nfd = "cafe\u{301}.txt";
+        let config = SafePathConfig::new().normalize_unicode(UnicodeForm::Nfc);
+        for path in [nfc, nfd] {
+            assert!(
+                config
+                    .validate(path)
+                    .is_ok_and(|path| path.0.as_os_str() == nfc)
+            );
+        }
+        let config = SafePathConfig::new().normalize_unicode(UnicodeForm::Nfd);
+        assert!(
+            config
+                .validate(nfc)
+                .is_ok_and(|path| path.0.as_os_str() == nfd)
+        );
+
+        let config = SafePathConfig::new()
+            .normalize_unicode(UnicodeForm::Nfc)
+            .denied_names([nfc]);
+        assert!(matches!(
+            config.validate(nfd),
+            Err(SafePathRejection::DeniedName)
+        ));
+    }
+
+    #[test]
+    fn deny_encoded_separators() {
+        let config = SafePathConfig::new().deny_encoded_separators(true);
+        assert!(config.validate_encoded("a/b".into(), Some("a/b")).is_ok());
+        assert!(config.validate_encoded("a%b".into(), Some("a%25b")).is_ok());
+        for raw in ["a%2Fb", "a%2fb", "a%5Cb", "a%5cb"] {
+            assert!(
+                matches!(
+                    config.validate_encoded("a/b".into(), Some(raw)),
+                    Err(SafePathRejection::EncodedSeparator)
+                ),
+                "{raw:?}"
+            );
+        }
+        assert!(config.validate("a/b").is_ok());
+        assert!(
+            SafePathConfig::new()
+                .validate_encoded("a/b".into(), Some("a%2Fb"))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn normalize() {
+        let config = SafePathConfig::new();
+        for (path, expected) in [
+            ("a/b.txt", "a/b.txt"),
+            ("./a//b/./c/", "a/b/c"),
+            ("a\\.\\b", "a/b"),
+            ("", ""),
+        ] {
+            assert!(
+                config
+                    .normalize(path)
+                    .is_ok_and(|normalized| normalized.as_os_str() == expected),
+                "{path:?}"
+            );
+        }
+        assert!(config.normalize("a/../b").is_err());
+
+        let config = SafePathConfig::new()
+            .deny_hidden(true)
+            .audit_only(|_, _| ());
+        assert!(
+            config
+                .normalize(".a/./b")
+                .is_ok_and(|normalized| normalized.as_os_str() == ".a/b")
+        );
+    }
+
+    #[test]
+    fn nul_byte() {
+        for path in ["\0", "safe.txt\0../../etc/passwd", "foo/\0/bar"] {
+            assert!(
+                matches!(
+                    SafePathConfig::default().check(path),
+                    Err(SafePathRejection::NulByte)
+                ),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unlimited_depth_by_default() {
+        assert!(SafePathConfig::default().check("a/b/c/d/e/f/g/h").is_ok());
+    }
+}
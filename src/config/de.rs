@@ -0,0 +1,312 @@
+//! Loading [`SafePathConfig`] from configuration files.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Deserializer, de::Error};
+
+#[cfg(feature = "unicode")]
+use super::UnicodeForm;
+use super::{SafePathConfig, TrailingSlash};
+
+/// The representation of [`SafePathConfig`] in configuration files
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+#[allow(clippy::struct_excessive_bools)]
+struct ConfigFile {
+    max_depth: Option<usize>,
+    max_length: Option<usize>,
+    max_component_length: Option<usize>,
+    deny_empty: bool,
+    deny_hidden: bool,
+    deny_sensitive: bool,
+    deny_current_dir: bool,
+    deny_non_portable: bool,
+    deny_windows_chars: bool,
+    deny_lookalike_dots: bool,
+    deny_control_chars: bool,
+    deny_trailing_dots: bool,
+    deny_reserved_names: bool,
+    deny_alternate_streams: bool,
+    backslash_separator: bool,
+    cross_platform: bool,
+    remote: bool,
+    allow_absolute: bool,
+    deny_percent_encoded: bool,
+    deny_encoded_separators: bool,
+    require_extension: bool,
+    case_insensitive: bool,
+    trailing_slash: TrailingSlash,
+    allowed_extensions: Option<Vec<String>>,
+    denied_extensions: Option<Vec<String>>,
+    denied_names: Option<Vec<String>>,
+    allowed_globs: Option<Vec<String>>,
+    allowed_roots: Option<Vec<PathBuf>>,
+    rejection_message: Option<String>,
+    #[cfg(feature = "regex")]
+    component_pattern: Option<String>,
+    #[cfg(feature = "regex")]
+    path_pattern: Option<String>,
+    #[cfg(feature = "unicode")]
+    normalize_unicode: Option<UnicodeForm>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        let config = SafePathConfig::new();
+        Self {
+            max_depth: config.max_depth,
+            max_length: config.max_length,
+            max_component_length: None,
+            deny_empty: config.deny_empty,
+            deny_hidden: config.deny_hidden,
+            deny_sensitive: config.deny_sensitive,
+            deny_current_dir: config.deny_current_dir,
+            deny_non_portable: config.deny_non_portable,
+            deny_windows_chars: config.deny_windows_chars,
+            deny_lookalike_dots: config.deny_lookalike_dots,
+            deny_control_chars: config.deny_control_chars,
+            deny_trailing_dots: config.deny_trailing_dots,
+            deny_reserved_names: config.deny_reserved_names,
+            deny_alternate_streams: config.deny_alternate_streams,
+            backslash_separator: config.backslash_separator,
+            cross_platform: config.cross_platform,
+            remote: config.remote,
+            allow_absolute: config.allow_absolute,
+            deny_percent_encoded: config.deny_percent_encoded,
+            deny_encoded_separators: config.deny_encoded_separators,
+            require_extension: config.require_extension,
+            case_insensitive: config.case_insensitive,
+            trailing_slash: config.trailing_slash,
+            allowed_extensions: None,
+            denied_extensions: None,
+            denied_names: None,
+            allowed_globs: None,
+            allowed_roots: None,
+            rejection_message: None,
+            #[cfg(feature = "regex")]
+            component_pattern: None,
+            #[cfg(feature = "regex")]
+            path_pattern: None,
+            #[cfg(feature = "unicode")]
+            normalize_unicode: None,
+        }
+    }
+}
+
+impl TryFrom<ConfigFile> for SafePathConfig {
+    type Error = String;
+
+    fn try_from(file: ConfigFile) -> Result<Self, Self::Error> {
+        let mut config = Self::new()
+            .deny_empty(file.deny_empty)
+            .deny_hidden(file.deny_hidden)
+            .deny_sensitive(file.deny_sensitive)
+            .deny_current_dir(file.deny_current_dir)
+            .deny_non_portable(file.deny_non_portable)
+            .deny_windows_chars(file.deny_windows_chars)
+            .deny_lookalike_dots(file.deny_lookalike_dots)
+            .deny_control_chars(file.deny_control_chars)
+            .deny_trailing_dots(file.deny_trailing_dots)
+            .deny_reserved_names(file.deny_reserved_names)
+            .deny_alternate_streams(file.deny_alternate_streams)
+            .backslash_separator(file.backslash_separator)
+            .cross_platform(file.cross_platform)
+            .remote(file.remote)
+            .allow_absolute(file.allow_absolute)
+            .deny_percent_encoded(file.deny_percent_encoded)
+            .deny_encoded_separators(file.deny_encoded_separators)
+            .require_extension(file.require_extension)
+            .case_insensitive(file.case_insensitive)
+            .trailing_slash(file.trailing_slash);
+
+        if let Some(max) = file.max_depth {
+            config = config.max_depth(positive("max_depth", max)?);
+        }
+        if let Some(max) = file.max_length {
+            config = config.max_length(positive("max_length", max)?);
+        }
+        if let Some(max) = file.max_component_length {
+            config = config.max_component_length(positive("max_component_length", max)?);
+        }
+        if let Some(extensions) = file.allowed_extensions {
+            config = config.allowed_extensions(extensions_list("allowed_extensions", extensions)?);
+        }
+        if let Some(extensions) = file.denied_extensions {
+            config = config.denied_extensions(extensions_list("denied_extensions", extensions)?);
+        }
+        if let Some(names) = file.denied_names {
+            if let Some(name) = names.iter().find(|name| !is_single_component(name)) {
+                return Err(format!("denied_names: invalid name `{name}`"));
+            }
+            config = config.denied_names(names);
+        }
+        if let Some(globs) = file.allowed_globs {
+            if globs.iter().any(String::is_empty) {
+                return Err("allowed_globs: patterns can't be empty".into());
+            }
+            config = config.allowed_globs(globs);
+        }
+        if let Some(roots) = file.allowed_roots {
+            if let Some(root) = roots.iter().find(|root| !root.has_root()) {
+                return Err(format!(
+                    "allowed_roots: `{}` is not an absolute path",
+                    root.display()
+                ));
+            }
+            config = config.allowed_roots(roots);
+        }
+        if let Some(message) = file.rejection_message {
+            config = config.rejection_message(message);
+        }
+        #[cfg(feature = "regex")]
+        {
+            if let Some(pattern) = file.component_pattern {
+                config = config.component_pattern(compile("component_pattern", &pattern)?);
+            }
+            if let Some(pattern) = file.path_pattern {
+                config = config.path_pattern(compile("path_pattern", &pattern)?);
+            }
+        }
+        #[cfg(feature = "unicode")]
+        if let Some(form) = file.normalize_unicode {
+            config = config.normalize_unicode(form);
+        }
+
+        Ok(config)
+    }
+}
+
+impl<'de> Deserialize<'de> for SafePathConfig {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ConfigFile::deserialize(deserializer)?
+            .try_into()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Ensures the limit isn't zero
+fn positive(field: &str, value: usize) -> Result<usize, String> {
+    if value == 0 {
+        Err(format!("{field}: must be greater than zero"))
+    } else {
+        Ok(value)
+    }
+}
+
+/// Ensures extensions are non-empty and have no separators
+fn extensions_list(field: &str, extensions: Vec<String>) -> Result<Vec<String>, String> {
+    if let Some(ext) = extensions
+        .iter()
+        .find(|ext| ext.trim_start_matches('.').is_empty() || ext.contains(['/', '\\']))
+    {
+        return Err(format!("{field}: invalid extension `{ext}`"));
+    }
+    Ok(extensions)
+}
+
+/// Checks that the name is a single normal path component
+fn is_single_component(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
+
+/// Compiles a regex pattern
+#[cfg(feature = "regex")]
+fn compile(field: &str, pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(pattern).map_err(|e| format!("{field}: {e}"))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::SafePathRejection;
+
+    fn from_json(value: serde_json::Value) -> Result<SafePathConfig, String> {
+        serde_json::from_value(value).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn empty() {
+        let config = from_json(json!({})).unwrap();
+        assert!(config.check("foo/bar").is_ok());
+        assert!(config.check("../bar").is_err());
+        assert!(config.check("a".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn full() {
+        let config = from_json(json!({
+            "max_depth": 2,
+            "max_length": 64,
+            "max_component_length": 16,
+            "deny_hidden": true,
+            "deny_sensitive": true,
+            "trailing_slash": "forbid",
+            "allowed_extensions": ["png", "jpg"],
+            "denied_extensions": ["php.png"],
+            "allowed_roots": ["/var/data"],
+            "rejection_message": "Nope",
+        }))
+        .unwrap();
+        assert!(config.check("images/cat.png").is_ok());
+        assert_eq!(config.rejection_message.as_deref(), Some("Nope"));
+        assert!(matches!(
+            config.check("a/b/cat.png"),
+            Err(SafePathRejection::TooDeep { max: 2 })
+        ));
+        assert!(matches!(
+            config.check(".cat.png"),
+            Err(SafePathRejection::Hidden)
+        ));
+        assert!(matches!(
+            config.check("images/"),
+            Err(SafePathRejection::TrailingSlash)
+        ));
+        assert!(matches!(
+            config.check("cat.gif"),
+            Err(SafePathRejection::ExtensionNotAllowed)
+        ));
+        assert!(matches!(
+            config.check("shell.php.png"),
+            Err(SafePathRejection::ExtensionDenied)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let error = from_json(json!({ "max_depth": 0 })).unwrap_err();
+        assert!(
+            error.contains("max_depth: must be greater than zero"),
+            "{error}"
+        );
+
+        let error = from_json(json!({ "allowed_extensions": ["png", ""] })).unwrap_err();
+        assert!(
+            error.contains("allowed_extensions: invalid extension ``"),
+            "{error}"
+        );
+
+        let error = from_json(json!({ "denied_names": ["a/b"] })).unwrap_err();
+        assert!(
+            error.contains("denied_names: invalid name `a/b`"),
+            "{error}"
+        );
+
+        let error = from_json(json!({ "allowed_roots": ["data"] })).unwrap_err();
+        assert!(error.contains("`data` is not an absolute path"), "{error}");
+
+        let error = from_json(json!({ "allowed_roots": ["/var/data", ""] })).unwrap_err();
+        assert!(error.contains("`` is not an absolute path"), "{error}");
+
+        let error = from_json(json!({ "trailing_slash": "sometimes" })).unwrap_err();
+        assert!(error.contains("unknown variant `sometimes`"), "{error}");
+
+        let error = from_json(json!({ "max_deep": 3 })).unwrap_err();
+        assert!(error.contains("unknown field `max_deep`"), "{error}");
+    }
+}
@@ -0,0 +1,85 @@
+//! Hot-reloadable config.
+
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+
+use super::SafePathConfig;
+
+/// A [`SafePathConfig`] that can be replaced while the service is running.
+///
+/// Put it into request extensions instead of the config itself and keep a
+/// clone to update the rules later. The extractor always reads the latest
+/// snapshot, and it takes precedence over a plain [`SafePathConfig`] in
+/// extensions.
+///
+/// ```
+/// use axum::{Extension, Router, routing::get};
+/// use axum_safe_path::{ReloadableConfig, SafePath, SafePathConfig};
+///
+/// async fn handler(SafePath(path): SafePath) -> String {
+///     path.display().to_string()
+/// }
+///
+/// let config = ReloadableConfig::new(SafePathConfig::new());
+/// let app: Router = Router::new()
+///     .route("/files/{*path}", get(handler))
+///     .layer(Extension(config.clone()));
+///
+/// // Later, e.g. on a config file change
+/// config.store(SafePathConfig::new().denied_extensions(["php"]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReloadableConfig(Arc<ArcSwap<SafePathConfig>>);
+
+impl ReloadableConfig {
+    /// Creates a reloadable config with the initial value
+    #[must_use]
+    pub fn new(config: SafePathConfig) -> Self {
+        Self(Arc::new(ArcSwap::from_pointee(config)))
+    }
+
+    /// Returns the current config
+    #[must_use]
+    pub fn load(&self) -> Arc<SafePathConfig> {
+        self.0.load_full()
+    }
+
+    /// Replaces the config, requests extracted after this call use the new one
+    pub fn store(&self, config: SafePathConfig) {
+        self.0.store(Arc::new(config));
+    }
+
+    /// Runs the closure with the current config without cloning the pointer
+    pub(crate) fn with<T>(&self, f: impl FnOnce(&SafePathConfig) -> T) -> T {
+        f(&self.0.load())
+    }
+}
+
+impl Default for ReloadableConfig {
+    fn default() -> Self {
+        Self::new(SafePathConfig::default())
+    }
+}
+
+impl From<SafePathConfig> for ReloadableConfig {
+    fn from(config: SafePathConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store() {
+        let config = ReloadableConfig::new(SafePathConfig::new());
+        let handle = config.clone();
+        assert!(config.load().check("index.php").is_ok());
+
+        handle.store(SafePathConfig::new().denied_extensions(["php"]));
+        assert!(config.load().check("index.php").is_err());
+        assert!(config.with(|config| config.check("index.php").is_err()));
+    }
+}
@@ -0,0 +1,157 @@
+//! Extractor with a custom rejection type.
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    path::{self, PathBuf},
+};
+
+use axum::{extract::FromRequestParts, http::request::Parts, response::IntoResponse};
+
+use crate::{SafePath, SafePathRejection};
+
+/// A [`SafePath`] rejecting requests with the error type of the app.
+///
+/// The rejection is converted with [`From`], so the app can answer with its
+/// own error envelope without wrapping the extractor:
+///
+/// ```
+/// use axum::{
+///     Json, Router,
+///     http::StatusCode,
+///     response::{IntoResponse, Response},
+///     routing::get,
+/// };
+/// use axum_safe_path::{SafePathRejection, SafePathWith};
+///
+/// struct AppError {
+///     code: &'static str,
+/// }
+///
+/// impl From<SafePathRejection> for AppError {
+///     fn from(rejection: SafePathRejection) -> Self {
+///         Self {
+///             code: rejection.code(),
+///         }
+///     }
+/// }
+///
+/// impl IntoResponse for AppError {
+///     fn into_response(self) -> Response {
+///         let body = serde_json::json!({ "error": self.code });
+///         (StatusCode::BAD_REQUEST, Json(body)).into_response()
+///     }
+/// }
+///
+/// async fn handler(path: SafePathWith<AppError>) -> String {
+///     path.path().display().to_string()
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(handler));
+/// ```
+///
+/// A [`SafePathPolicyLayer`](crate::SafePathPolicyLayer) still sets the
+/// config, but doesn't adjust the responses of custom rejections.
+pub struct SafePathWith<R> {
+    path: PathBuf,
+    rejection: PhantomData<fn() -> R>,
+}
+
+impl<R> SafePathWith<R> {
+    /// Returns the validated path
+    #[must_use]
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// Returns the validated path
+    #[must_use]
+    pub fn into_inner(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl<R> fmt::Debug for SafePathWith<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SafePathWith").field(&self.path).finish()
+    }
+}
+
+impl<R> Clone for SafePathWith<R> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            rejection: PhantomData,
+        }
+    }
+}
+
+impl<R> AsRef<path::Path> for SafePathWith<R> {
+    fn as_ref(&self) -> &path::Path {
+        self.path.as_ref()
+    }
+}
+
+impl<R> From<SafePath> for SafePathWith<R> {
+    fn from(SafePath(path): SafePath) -> Self {
+        Self {
+            path,
+            rejection: PhantomData,
+        }
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S, R> FromRequestParts<S> for SafePathWith<R>
+where
+    S: Send + Sync,
+    R: From<SafePathRejection> + IntoResponse,
+{
+    type Rejection = R;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let path = SafePath::from_request_parts(parts, state).await?;
+        Ok(path.into())
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Router, http::StatusCode, response::Response, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    struct AppError(&'static str);
+
+    impl From<SafePathRejection> for AppError {
+        fn from(rejection: SafePathRejection) -> Self {
+            Self(rejection.code())
+        }
+    }
+
+    impl IntoResponse for AppError {
+        fn into_response(self) -> Response {
+            (StatusCode::IM_A_TEAPOT, format!("app error: {}", self.0)).into_response()
+        }
+    }
+
+    async fn handler(path: SafePathWith<AppError>) -> String {
+        path.path().display().to_string()
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let app = Router::new().route("/{*path}", get(handler));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/a/b.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a/b.txt");
+
+        let res = server.get("/a/..%2F..%2Fb").await;
+        assert_eq!(res.status_code(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(res.text(), "app error: path_traversal");
+    }
+}
@@ -0,0 +1,144 @@
+//! Safe `Content-Disposition` headers for downloads.
+
+use std::fmt::Write;
+
+use axum::http::HeaderValue;
+
+use crate::sanitize::sanitize_component;
+
+/// The name used when nothing is left of the original one
+const FALLBACK_NAME: &str = "download";
+
+/// How the browser should present the response, see [`content_disposition`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    /// Displayed in the browser if possible
+    Inline,
+    /// Downloaded and saved under the file name
+    #[default]
+    Attachment,
+}
+
+/// Builds a `Content-Disposition` header value from an arbitrary display file
+/// name.
+///
+/// Only the last component of the name is used, sanitized with the rules of
+/// [`sanitize`](crate::sanitize), so it can't inject header parameters or make
+/// the browser save outside of the downloads directory. The `filename`
+/// parameter gets an ASCII fallback with other characters replaced by `_`,
+/// and non-ASCII names are also passed in the RFC 5987 `filename*` parameter:
+///
+/// ```
+/// use axum::http::header::CONTENT_DISPOSITION;
+/// use axum_safe_path::{Disposition, content_disposition};
+///
+/// let value = content_disposition(Disposition::Attachment, "../Résumé \"final\".pdf");
+/// assert_eq!(
+///     value,
+///     "attachment; filename=\"R_sum_ final.pdf\"; filename*=UTF-8''R%C3%A9sum%C3%A9%20final.pdf"
+/// );
+///
+/// let response = ([(CONTENT_DISPOSITION, value)], "content");
+/// ```
+#[must_use]
+pub fn content_disposition(disposition: Disposition, filename: &str) -> HeaderValue {
+    let name = filename
+        .rsplit(['/', '\\'])
+        .find_map(sanitize_component)
+        .unwrap_or_else(|| FALLBACK_NAME.to_owned());
+
+    let mut value = String::from(match disposition {
+        Disposition::Inline => "inline",
+        Disposition::Attachment => "attachment",
+    });
+    let fallback: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '%' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let _ = write!(value, "; filename=\"{fallback}\"");
+    if fallback != name {
+        value.push_str("; filename*=UTF-8''");
+        for b in name.bytes() {
+            if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                value.push(char::from(b));
+            } else {
+                let _ = write!(value, "%{b:02X}");
+            }
+        }
+    }
+
+    // Only visible ASCII is left, so it's always a valid header value
+    HeaderValue::try_from(value).unwrap_or_else(|_| HeaderValue::from_static("attachment"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(filename: &str) -> HeaderValue {
+        content_disposition(Disposition::Attachment, filename)
+    }
+
+    #[test]
+    fn ascii() {
+        assert_eq!(
+            attachment("report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition(Disposition::Inline, "a b.png"),
+            "inline; filename=\"a b.png\""
+        );
+    }
+
+    #[test]
+    fn injection() {
+        assert_eq!(
+            attachment("a.txt\"; filename=evil.exe"),
+            "attachment; filename=\"a.txt; filename=evil.exe\""
+        );
+        assert_eq!(
+            attachment("a\r\nSet-Cookie: x=1"),
+            "attachment; filename=\"aSet-Cookie x=1\""
+        );
+        assert_eq!(
+            attachment("../../etc/passwd"),
+            "attachment; filename=\"passwd\""
+        );
+        assert_eq!(
+            attachment("C:\\Users\\a.txt"),
+            "attachment; filename=\"a.txt\""
+        );
+        assert_eq!(attachment(".bashrc"), "attachment; filename=\"bashrc\"");
+    }
+
+    #[test]
+    fn fallback() {
+        for name in ["", "..", "/", "\u{202E}"] {
+            assert_eq!(
+                attachment(name),
+                "attachment; filename=\"download\"",
+                "{name:?}"
+            );
+        }
+        assert_eq!(attachment("a/.."), "attachment; filename=\"a\"");
+    }
+
+    #[test]
+    fn non_ascii() {
+        assert_eq!(
+            attachment("\u{444}\u{430}\u{439}\u{43b}.txt"),
+            "attachment; filename=\"____.txt\"; filename*=UTF-8''%D1%84%D0%B0%D0%B9%D0%BB.txt"
+        );
+        assert_eq!(
+            attachment("100%.txt"),
+            "attachment; filename=\"100_.txt\"; filename*=UTF-8''100%25.txt"
+        );
+    }
+}
@@ -0,0 +1,652 @@
+//! Archive extraction confined to a root directory.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::OsStr,
+    fmt,
+    fs::OpenOptions,
+    io::{self, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use tokio::runtime::Handle;
+
+use crate::{
+    SafePath, SafePathRejection,
+    save::{create_dirs, create_parents},
+};
+#[cfg(feature = "tar")]
+use crate::{TarEntry, TarEntryKind, TarFinding, validate_tar_entry};
+
+/// An archive to extract, see [`extract_into`]
+#[derive(Debug)]
+pub enum Archive<R> {
+    /// A zip archive
+    #[cfg(feature = "zip")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "zip")))]
+    Zip(R),
+    /// An uncompressed tar archive
+    #[cfg(feature = "tar")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tar")))]
+    Tar(R),
+}
+
+/// Error type for [`extract_into`].
+#[derive(Debug)]
+pub enum ExtractError {
+    /// The name of an entry is invalid
+    InvalidEntry {
+        /// The name of the entry as stored in the archive
+        name: String,
+        /// The reason the name was rejected
+        rejection: SafePathRejection,
+    },
+    /// A tar entry is a link pointing outside of the archive, a device or of
+    /// an unsupported type, see [`validate_tar_entry`]
+    #[cfg(feature = "tar")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tar")))]
+    DangerousEntry {
+        /// The name of the entry as stored in the archive
+        name: String,
+        /// The problems found
+        findings: Vec<TarFinding>,
+    },
+    /// The archive has more entries than allowed
+    TooManyEntries,
+    /// The extracted contents are larger than allowed
+    TooLarge,
+    /// The archive is malformed, an entry already exists or another IO error
+    /// happened
+    Io(io::Error),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidEntry { name, rejection } => write!(f, "{rejection}: {name:?}"),
+            #[cfg(feature = "tar")]
+            Self::DangerousEntry { name, .. } => write!(f, "Dangerous archive entry: {name:?}"),
+            Self::TooManyEntries => f.write_str("Too many archive entries"),
+            Self::TooLarge => f.write_str("Extracted archive is too large"),
+            Self::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for ExtractError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidEntry { rejection, .. } => Some(rejection),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl IntoResponse for ExtractError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::TooManyEntries | Self::TooLarge => StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            Self::Io(err)
+                if !matches!(
+                    err.kind(),
+                    io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+                ) =>
+            {
+                // Could expose server paths, so the details aren't shown
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+            err => (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+        }
+    }
+}
+
+/// Limits applied while extracting an archive, see [`extract_into`]
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+    max_entries: usize,
+    max_size: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_size: 1 << 30,
+        }
+    }
+}
+
+impl ExtractLimits {
+    /// Creates the default limits: 10 000 entries and 1 GiB of contents
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of entries, including directories and
+    /// skipped ones
+    #[must_use]
+    pub const fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Sets the maximum total size of the extracted files in bytes
+    #[must_use]
+    pub const fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Extracts the archive into the root with these limits, see
+    /// [`extract_into`]
+    ///
+    /// # Errors
+    ///
+    /// See [`extract_into`].
+    pub async fn extract_into<R>(
+        &self,
+        root: impl AsRef<Path>,
+        archive: Archive<R>,
+    ) -> Result<Vec<SafePath>, ExtractError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let mut extractor = Extractor {
+            root: root.as_ref().to_path_buf(),
+            limits: *self,
+            handle: Handle::current(),
+            entries: 0,
+            size: 0,
+            files: HashMap::new(),
+            extracted: Vec::new(),
+        };
+        tokio::task::spawn_blocking(move || {
+            match archive {
+                #[cfg(feature = "zip")]
+                Archive::Zip(reader) => extractor.zip(reader)?,
+                #[cfg(feature = "tar")]
+                Archive::Tar(reader) => extractor.tar(reader)?,
+            }
+            Ok(extractor.extracted)
+        })
+        .await
+        .map_err(io::Error::other)?
+    }
+}
+
+/// Extracts an uploaded archive into the root directory.
+///
+/// Every entry name is checked the same way as [`validate_archive_entry`]
+/// and tar entries are screened with [`validate_tar_entry`], so the first
+/// invalid or dangerous entry stops the extraction. Parent directories are
+/// created without going through symlinks and files are never overwritten.
+/// The number of entries and the total size of the decompressed contents are
+/// limited, protecting against zip bombs, see [`ExtractLimits`] to change the
+/// defaults:
+///
+/// ```
+/// # #[cfg(feature = "zip")] {
+/// use axum::{body::Bytes, http::StatusCode, response::IntoResponse};
+/// use axum_safe_path::{Archive, extract_into};
+/// use std::io::Cursor;
+///
+/// async fn upload(body: Bytes) -> impl IntoResponse {
+///     match extract_into("/var/uploads", Archive::Zip(Cursor::new(body))).await {
+///         Ok(paths) => (StatusCode::CREATED, format!("{} entries", paths.len())).into_response(),
+///         Err(err) => err.into_response(),
+///     }
+/// }
+/// # }
+/// ```
+///
+/// Symlinks are skipped, as even the ones pointing inside of the root can be
+/// chained to escape it, while tar hard links are extracted as copies of the
+/// files they point to. File permissions aren't restored. The entries
+/// extracted before a failure are left in place.
+///
+/// Returns the paths of the extracted files and directories relative to the
+/// root.
+///
+/// [`validate_archive_entry`]: crate::validate_archive_entry
+///
+/// # Errors
+///
+/// Fails on the first invalid entry, when a limit is exceeded, if an entry
+/// already exists or if the archive can't be read.
+pub async fn extract_into<R>(
+    root: impl AsRef<Path>,
+    archive: Archive<R>,
+) -> Result<Vec<SafePath>, ExtractError>
+where
+    R: Read + Seek + Send + 'static,
+{
+    ExtractLimits::default().extract_into(root, archive).await
+}
+
+/// The state of a running extraction
+struct Extractor {
+    root: PathBuf,
+    limits: ExtractLimits,
+    handle: Handle,
+    entries: usize,
+    size: u64,
+    /// Full paths of the extracted files, for hard links to copy
+    files: HashMap<PathBuf, PathBuf>,
+    extracted: Vec<SafePath>,
+}
+
+impl Extractor {
+    #[cfg(feature = "zip")]
+    fn zip(&mut self, reader: impl Read + Seek) -> Result<(), ExtractError> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(io::Error::from)?;
+        if archive.len() > self.limits.max_entries {
+            return Err(ExtractError::TooManyEntries);
+        }
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(io::Error::from)?;
+            self.count()?;
+            let name = file.name().to_owned();
+            let path = validate(&name)?;
+            if file.is_symlink() {
+                continue;
+            }
+            if file.is_dir() {
+                self.dir(path)?;
+            } else {
+                self.file(path, &mut file)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "tar")]
+    fn tar(&mut self, reader: impl Read) -> Result<(), ExtractError> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            self.count()?;
+
+            let mut pax_path = None;
+            if let Some(extensions) = entry.pax_extensions()? {
+                for extension in extensions {
+                    let extension = extension?;
+                    if extension.key_bytes() == b"path" {
+                        pax_path = Some(utf8(extension.value_bytes())?);
+                    }
+                }
+            }
+            // Without a PAX path the entry path includes the GNU long name
+            let path = if pax_path.is_some() {
+                utf8(&entry.header().path_bytes())?
+            } else {
+                utf8(&entry.path_bytes())?
+            };
+            let link_target = entry
+                .link_name_bytes()
+                .map(|name| utf8(&name))
+                .transpose()?;
+            let kind = tar_kind(entry.header().entry_type());
+
+            let report = validate_tar_entry(&TarEntry {
+                path: &path,
+                pax_path: pax_path.as_deref(),
+                kind,
+                link_target: link_target.as_deref(),
+            });
+            let name = pax_path.unwrap_or(path);
+            let safe_path = match report.path {
+                Some(safe_path) if report.findings.is_empty() => safe_path,
+                _ => {
+                    return Err(ExtractError::DangerousEntry {
+                        name,
+                        findings: report.findings,
+                    });
+                }
+            };
+            match kind {
+                TarEntryKind::File => self.file(safe_path, &mut entry)?,
+                TarEntryKind::Directory => self.dir(safe_path)?,
+                TarEntryKind::HardLink => {
+                    let target = validate(link_target.as_deref().unwrap_or_default())?;
+                    let source = self
+                        .files
+                        .get(&target.0)
+                        .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+                    self.file(safe_path, &mut std::fs::File::open(source)?)?;
+                }
+                // Symlinks are skipped and other kinds are reported above
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts an entry against the limit
+    const fn count(&mut self) -> Result<(), ExtractError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(ExtractError::TooManyEntries);
+        }
+        Ok(())
+    }
+
+    fn dir(&mut self, path: SafePath) -> Result<(), ExtractError> {
+        let dirs: Vec<&OsStr> = path
+            .0
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        self.handle.block_on(create_dirs(&self.root, &dirs))?;
+        self.extracted.push(path);
+        Ok(())
+    }
+
+    fn file(&mut self, path: SafePath, contents: &mut impl Read) -> Result<(), ExtractError> {
+        let full = self.handle.block_on(create_parents(&self.root, &path))?;
+        // Fails on existing entries, including symlinks
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&full)?;
+
+        let remaining = self.limits.max_size - self.size;
+        let written = io::copy(&mut contents.take(remaining.saturating_add(1)), &mut file);
+        let result = match written {
+            Ok(written) if written > remaining => Err(ExtractError::TooLarge),
+            Ok(written) => {
+                self.size += written;
+                Ok(())
+            }
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            drop(file);
+            // The original error is more useful than a failed cleanup
+            std::fs::remove_file(&full).ok();
+            return Err(err);
+        }
+
+        self.files.insert(path.0.clone(), full);
+        self.extracted.push(path);
+        Ok(())
+    }
+}
+
+/// Validates an entry name, keeping it in the error
+fn validate(name: &str) -> Result<SafePath, ExtractError> {
+    crate::validate_archive_entry(name).map_err(|rejection| ExtractError::InvalidEntry {
+        name: name.to_owned(),
+        rejection,
+    })
+}
+
+/// Converts a tar name, rejecting non-UTF-8 ones
+#[cfg(feature = "tar")]
+fn utf8(name: &[u8]) -> Result<String, ExtractError> {
+    String::from_utf8(name.to_vec()).map_err(|err| ExtractError::InvalidEntry {
+        name: String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        rejection: SafePathRejection::InvalidCharacter,
+    })
+}
+
+#[cfg(feature = "tar")]
+const fn tar_kind(kind: tar::EntryType) -> TarEntryKind {
+    match kind {
+        tar::EntryType::Regular | tar::EntryType::Continuous => TarEntryKind::File,
+        tar::EntryType::Directory => TarEntryKind::Directory,
+        tar::EntryType::Symlink => TarEntryKind::Symlink,
+        tar::EntryType::Link => TarEntryKind::HardLink,
+        tar::EntryType::Char => TarEntryKind::CharDevice,
+        tar::EntryType::Block => TarEntryKind::BlockDevice,
+        tar::EntryType::Fifo => TarEntryKind::Fifo,
+        _ => TarEntryKind::Other,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn paths(extracted: &[SafePath]) -> Vec<&str> {
+        extracted
+            .iter()
+            .map(|path| path.0.to_str().unwrap())
+            .collect()
+    }
+
+    #[cfg(feature = "zip")]
+    fn zip(entries: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for (name, contents) in entries {
+            if name.ends_with('/') {
+                writer.add_directory(*name, options).unwrap();
+            } else {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(contents).unwrap();
+            }
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    #[cfg(feature = "tar")]
+    fn tar(entries: &[(&str, tar::EntryType, &str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, kind, link, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            // Set directly, as the builder refuses names with `..`
+            header
+                .as_gnu_mut()
+                .unwrap()
+                .name
+                .get_mut(..name.len())
+                .unwrap()
+                .copy_from_slice(name.as_bytes());
+            header.set_entry_type(*kind);
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            if !link.is_empty() {
+                header.set_link_name(link).unwrap();
+            }
+            header.set_cksum();
+            builder.append(&header, *contents).unwrap();
+        }
+        Cursor::new(builder.into_inner().unwrap())
+    }
+
+    #[cfg(feature = "zip")]
+    #[tokio::test]
+    async fn zip_archive() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = zip(&[
+            ("docs/", b""),
+            ("docs/intro.md", b"intro"),
+            ("docs\\guide\\setup.md", b"setup"),
+        ]);
+        let extracted = extract_into(tmp.path(), Archive::Zip(archive))
+            .await
+            .unwrap();
+        assert_eq!(
+            paths(&extracted),
+            ["docs/", "docs/intro.md", "docs/guide/setup.md"]
+        );
+        let read = |path| std::fs::read_to_string(tmp.path().join(path)).unwrap();
+        assert_eq!(read("docs/intro.md"), "intro");
+        assert_eq!(read("docs/guide/setup.md"), "setup");
+    }
+
+    #[cfg(feature = "zip")]
+    #[tokio::test]
+    async fn zip_slip() {
+        for name in [
+            "../evil.sh",
+            "/etc/passwd",
+            "C:\\evil.sh",
+            "a/../../evil.sh",
+        ] {
+            let tmp = tempfile::tempdir().unwrap();
+            let root = tmp.path().join("root");
+            std::fs::create_dir(&root).unwrap();
+            let archive = zip(&[("ok.txt", b"ok"), (name, b"evil")]);
+            let err = extract_into(&root, Archive::Zip(archive))
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&err, ExtractError::InvalidEntry { name: n, .. } if n == name),
+                "{name}: {err:?}"
+            );
+            assert!(!tmp.path().join("evil.sh").exists(), "{name}");
+        }
+    }
+
+    #[cfg(feature = "zip")]
+    #[tokio::test]
+    async fn limits() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = zip(&[("a.txt", b"12345"), ("b.txt", b"67890")]);
+        let limits = ExtractLimits::new().max_size(8);
+        let err = limits
+            .extract_into(tmp.path(), Archive::Zip(archive))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ExtractError::TooLarge), "{err:?}");
+        assert_eq!(err.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+        // The file going over the limit is removed
+        assert!(tmp.path().join("a.txt").exists());
+        assert!(!tmp.path().join("b.txt").exists());
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = zip(&[("a.txt", b""), ("b.txt", b""), ("c.txt", b"")]);
+        let limits = ExtractLimits::new().max_entries(2);
+        let err = limits
+            .extract_into(tmp.path(), Archive::Zip(archive))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ExtractError::TooManyEntries), "{err:?}");
+        assert!(!tmp.path().join("a.txt").exists());
+    }
+
+    #[cfg(feature = "zip")]
+    #[tokio::test]
+    async fn existing() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "old").unwrap();
+        let archive = zip(&[("a.txt", b"new")]);
+        let err = extract_into(tmp.path(), Archive::Zip(archive))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, ExtractError::Io(err) if err.kind() == io::ErrorKind::AlreadyExists),
+            "{err:?}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("a.txt")).unwrap(),
+            "old"
+        );
+
+        let err = extract_into(tmp.path(), Archive::Zip(Cursor::new(b"not a zip".to_vec())))
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[cfg(all(feature = "zip", unix))]
+    #[tokio::test]
+    async fn symlinked_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), tmp.path().join("link")).unwrap();
+        let archive = zip(&[("link/evil.sh", b"evil")]);
+        assert!(
+            extract_into(tmp.path(), Archive::Zip(archive))
+                .await
+                .is_err()
+        );
+        assert!(!outside.path().join("evil.sh").exists());
+    }
+
+    #[cfg(feature = "tar")]
+    #[tokio::test]
+    async fn tar_archive() {
+        use tar::EntryType;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tar(&[
+            ("docs/", EntryType::Directory, "", b""),
+            ("docs/intro.md", EntryType::Regular, "", b"intro"),
+            ("docs/copy.md", EntryType::Link, "docs/intro.md", b""),
+            ("docs/latest", EntryType::Symlink, "intro.md", b""),
+        ]);
+        let extracted = extract_into(tmp.path(), Archive::Tar(archive))
+            .await
+            .unwrap();
+        assert_eq!(
+            paths(&extracted),
+            ["docs/", "docs/intro.md", "docs/copy.md"]
+        );
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("docs/copy.md")).unwrap(),
+            "intro"
+        );
+        assert!(!tmp.path().join("docs/latest").exists());
+    }
+
+    #[cfg(feature = "tar")]
+    #[tokio::test]
+    async fn dangerous_tar() {
+        use tar::EntryType;
+
+        for (entry, name) in [
+            (
+                ("../evil.sh", EntryType::Regular, "", &b"evil"[..]),
+                "../evil.sh",
+            ),
+            (("evil", EntryType::Symlink, "../../etc", b""), "evil"),
+            (("passwd", EntryType::Link, "/etc/passwd", b""), "passwd"),
+            (("null", EntryType::Char, "", b""), "null"),
+        ] {
+            let tmp = tempfile::tempdir().unwrap();
+            let err = extract_into(tmp.path(), Archive::Tar(tar(&[entry])))
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&err, ExtractError::DangerousEntry { name: n, .. } if n == name),
+                "{name}: {err:?}"
+            );
+            assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+        }
+
+        // Hard links can only point to files extracted before
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tar(&[("copy", EntryType::Link, "missing", b"")]);
+        assert!(
+            extract_into(tmp.path(), Archive::Tar(archive))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,585 @@
+//! Filesystem helpers checking the real location of files.
+
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Component, Path, PathBuf},
+};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use tokio::fs::{File, OpenOptions};
+
+use crate::{
+    SafePath, SafePathRejection, find_traversal,
+    save::{Staged, create_dirs, create_parents, create_unique},
+};
+
+/// Error type for [`canonicalize_within`].
+#[derive(Debug)]
+pub enum CanonicalizeError {
+    /// The path doesn't exist
+    Missing,
+    /// The path resolves to a location outside of the root, e.g. through a
+    /// symlink
+    EscapesRoot,
+    /// The root can't be resolved or another IO error happened
+    Io(io::Error),
+}
+
+impl fmt::Display for CanonicalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => f.write_str("Path not found"),
+            Self::EscapesRoot => f.write_str("Path escapes the root directory"),
+            Self::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for CanonicalizeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Missing | Self::EscapesRoot => None,
+        }
+    }
+}
+
+impl IntoResponse for CanonicalizeError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Missing => StatusCode::NOT_FOUND.into_response(),
+            Self::EscapesRoot => StatusCode::FORBIDDEN.into_response(),
+            // Could expose server paths, so the details aren't shown
+            Self::Io(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
+
+/// Resolves the path joined onto the root, following symlinks, and checks
+/// that the result is really inside the root.
+///
+/// Lexical checks, like the ones of [`SafePath`](crate::SafePath), can't see
+/// symlinks inside the root pointing outside of it. Both paths are
+/// canonicalized, so the returned one is absolute and has no symlinks:
+///
+/// ```
+/// use axum::{Router, response::IntoResponse, routing::get};
+/// use axum_safe_path::{SafePath, canonicalize_within};
+///
+/// async fn download(SafePath(path): SafePath) -> impl IntoResponse {
+///     match canonicalize_within("/var/www", path).await {
+///         Ok(path) => path.display().to_string().into_response(),
+///         Err(err) => err.into_response(),
+///     }
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(download));
+/// ```
+///
+/// The check only holds at the moment of the call, as the filesystem can
+/// change before the file is opened.
+///
+/// On Windows, canonical paths use the `\\?\` verbatim form, which many
+/// programs can't handle. With the `dunce` feature, they're simplified to
+/// the familiar `C:\` form whenever that's lossless.
+///
+/// # Errors
+///
+/// Returns [`CanonicalizeError::Missing`] if the path doesn't exist,
+/// [`CanonicalizeError::EscapesRoot`] if it resolves outside of the root, and
+/// [`CanonicalizeError::Io`] if the root can't be resolved.
+pub async fn canonicalize_within(
+    root: impl AsRef<Path>,
+    path: impl AsRef<Path>,
+) -> Result<PathBuf, CanonicalizeError> {
+    let root = root.as_ref();
+    let canonical_root = tokio::fs::canonicalize(root)
+        .await
+        .map(simplified)
+        .map_err(CanonicalizeError::Io)?;
+    let canonical = tokio::fs::canonicalize(root.join(path))
+        .await
+        .map(simplified)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound | io::ErrorKind::NotADirectory => CanonicalizeError::Missing,
+            _ => CanonicalizeError::Io(err),
+        })?;
+    if canonical.starts_with(&canonical_root) {
+        Ok(canonical)
+    } else {
+        Err(CanonicalizeError::EscapesRoot)
+    }
+}
+
+/// Converts a verbatim Windows path to the usual form if possible
+#[cfg(feature = "dunce")]
+// Takes ownership like the version without `dunce`, which returns the path
+#[allow(clippy::needless_pass_by_value)]
+fn simplified(path: PathBuf) -> PathBuf {
+    dunce::simplified(&path).to_path_buf()
+}
+
+#[cfg(not(feature = "dunce"))]
+const fn simplified(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Opens the file at the path relative to the root in read-only mode without
+/// following symlinks.
+///
+/// Each directory on the way is checked not to be a symlink and the file
+/// itself is opened with `O_NOFOLLOW`, or `FILE_FLAG_OPEN_REPARSE_POINT` on
+/// Windows. The root itself is trusted and may be a symlink. It's a portable
+/// alternative to the `cap-std` and `openat2` features, though a directory
+/// replaced with a symlink between the checks can still be followed:
+///
+/// ```
+/// use axum::{Router, http::StatusCode, routing::get};
+/// use axum_safe_path::{SafePath, secure_open};
+///
+/// async fn download(path: SafePath) -> Result<String, StatusCode> {
+///     let file = secure_open("/var/www", &path)
+///         .await
+///         .map_err(|_| StatusCode::NOT_FOUND)?;
+///     Ok(format!("{:?}", file.metadata().await.ok()))
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(download));
+/// ```
+///
+/// # Errors
+///
+/// Fails if the file can't be opened, or with
+/// [`io::ErrorKind::PermissionDenied`] if the path goes through a symlink.
+pub async fn secure_open(root: impl AsRef<Path>, path: &SafePath) -> io::Result<File> {
+    let mut current = root.as_ref().to_path_buf();
+    let mut components = path.0.components().peekable();
+    while let Some(component) = components.next() {
+        let Component::Normal(name) = component else {
+            continue;
+        };
+        current.push(name);
+        if components.peek().is_some()
+            && tokio::fs::symlink_metadata(&current)
+                .await?
+                .file_type()
+                .is_symlink()
+        {
+            return Err(symlink_error());
+        }
+    }
+
+    let mut options = OpenOptions::new();
+    options.read(true);
+    #[cfg(unix)]
+    options.custom_flags(libc::O_NOFOLLOW);
+    #[cfg(windows)]
+    options.custom_flags(FILE_FLAG_OPEN_REPARSE_POINT);
+    let file = options.open(&current).await.map_err(|err| {
+        // `O_NOFOLLOW` fails with `ELOOP` on symlinks
+        #[cfg(unix)]
+        if err.raw_os_error() == Some(libc::ELOOP) {
+            return symlink_error();
+        }
+        err
+    })?;
+    // A reparse point is opened as is, so it's checked after opening
+    if file.metadata().await?.file_type().is_symlink() {
+        return Err(symlink_error());
+    }
+    Ok(file)
+}
+
+/// Removes the file, symlink or empty directory at the path relative to the
+/// root.
+///
+/// The path is checked to stay inside of the root and not to go through
+/// symlinks. The root itself can't be removed.
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the path isn't a relative
+/// one with a file name, with [`io::ErrorKind::PermissionDenied`] if it goes
+/// through a symlink, or if the entry can't be removed.
+pub async fn remove_in(root: impl AsRef<Path>, path: &SafePath) -> io::Result<()> {
+    let path = resolve_in(root.as_ref(), path).await?;
+    if tokio::fs::symlink_metadata(&path).await?.is_dir() {
+        tokio::fs::remove_dir(path).await
+    } else {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+/// Moves the entry between paths relative to the root, returning the full
+/// path of the destination.
+///
+/// Both paths are checked the same way as with [`remove_in`], missing parent
+/// directories of the destination are created:
+///
+/// ```
+/// # #[cfg(feature = "json")] {
+/// use axum::{Json, http::StatusCode};
+/// use axum_safe_path::{SafePath, rename_in};
+///
+/// #[derive(serde::Deserialize)]
+/// struct Move {
+///     from: SafePath,
+///     to: SafePath,
+/// }
+///
+/// async fn move_file(Json(req): Json<Move>) -> StatusCode {
+///     match rename_in("/var/files", &req.from, &req.to).await {
+///         Ok(_) => StatusCode::NO_CONTENT,
+///         Err(_) => StatusCode::NOT_FOUND,
+///     }
+/// }
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Fails for the same reasons as [`remove_in`] or if the entry can't be
+/// moved.
+pub async fn rename_in(
+    root: impl AsRef<Path>,
+    from: &SafePath,
+    to: &SafePath,
+) -> io::Result<PathBuf> {
+    let root = root.as_ref();
+    let from = resolve_in(root, from).await?;
+    let to = create_parents(root, to).await?;
+    tokio::fs::rename(from, &to).await?;
+    Ok(to)
+}
+
+/// Copies the file between paths relative to the root, returning the full
+/// path of the destination.
+///
+/// The source is opened with [`secure_open`], so it can't be a symlink, and
+/// the copy is written atomically like with
+/// [`SafePath::save_in`](crate::SafePath::save_in).
+///
+/// # Errors
+///
+/// Fails for the same reasons as [`remove_in`] or if the file can't be
+/// copied.
+pub async fn copy_in(
+    root: impl AsRef<Path>,
+    from: &SafePath,
+    to: &SafePath,
+) -> io::Result<PathBuf> {
+    let root = root.as_ref();
+    resolve_in(root, from).await?;
+    let mut source = secure_open(root, from).await?;
+    let (mut file, staged) = Staged::create(root, to).await?;
+    let written = tokio::io::copy(&mut source, &mut file).await.map(drop);
+    staged.finish(file, written).await
+}
+
+/// Creates a new uniquely named file in the directory relative to the root,
+/// returning it opened for writing along with its path relative to the root.
+///
+/// Meant for staging uploads or exports without leaving the root for `/tmp`.
+/// Missing directories are created, while symlinks on the way are refused.
+/// The file isn't removed automatically:
+///
+/// ```
+/// use axum::http::StatusCode;
+/// use axum_safe_path::{SafePath, temp_file_in};
+/// use tokio::io::AsyncWriteExt;
+///
+/// async fn export() -> Result<String, StatusCode> {
+///     let staging: SafePath = "staging".parse().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+///     let (mut file, path) = temp_file_in("/var/exports", &staging)
+///         .await
+///         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+///     file.write_all(b"report")
+///         .await
+///         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+///     Ok(path.0.display().to_string())
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] if the path isn't a relative
+/// one, with [`io::ErrorKind::PermissionDenied`] if it goes through a
+/// symlink, or if the file can't be created.
+pub async fn temp_file_in(root: impl AsRef<Path>, dir: &SafePath) -> io::Result<(File, SafePath)> {
+    let names: Vec<_> = checked(dir)?
+        .0
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    let full = create_dirs(root.as_ref(), &names).await?;
+    let (file, path) = create_unique(&full, "upload").await?;
+    let mut relative: PathBuf = names.iter().collect();
+    relative.extend(path.file_name());
+    Ok((file, SafePath(relative)))
+}
+
+/// Resolves the path relative to the root, checking that it has a file name
+/// and doesn't go through symlinks
+async fn resolve_in(root: &Path, path: &SafePath) -> io::Result<PathBuf> {
+    let mut names = checked(path)?
+        .0
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .peekable();
+    let mut current = root.to_path_buf();
+    while let Some(name) = names.next() {
+        current.push(name);
+        if names.peek().is_some()
+            && tokio::fs::symlink_metadata(&current)
+                .await?
+                .file_type()
+                .is_symlink()
+        {
+            return Err(symlink_error());
+        }
+    }
+    if current == root {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the path has no file name",
+        ));
+    }
+    Ok(current)
+}
+
+/// Checks the path again, as [`SafePath`] can be created without validation
+pub fn checked(path: &SafePath) -> io::Result<&SafePath> {
+    if let Some(traversal) = find_traversal(&path.0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            SafePathRejection::TraversalAttack(traversal),
+        ));
+    }
+    Ok(path)
+}
+
+/// Opens the reparse point itself instead of its target
+#[cfg(windows)]
+const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x0020_0000;
+
+/// The error of a path going through a symlink
+pub fn symlink_error() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "symlinks are not followed")
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn canonicalize() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "a").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "s").unwrap();
+
+        let path = canonicalize_within(&root, "sub/../sub/a.txt")
+            .await
+            .unwrap();
+        assert_eq!(
+            path,
+            simplified(root.canonicalize().unwrap()).join("sub/a.txt")
+        );
+
+        assert!(matches!(
+            canonicalize_within(&root, "missing.txt").await,
+            Err(CanonicalizeError::Missing)
+        ));
+        assert!(matches!(
+            canonicalize_within(&root, "sub/a.txt/x").await,
+            Err(CanonicalizeError::Missing)
+        ));
+        assert!(matches!(
+            canonicalize_within(&root, "../secret.txt").await,
+            Err(CanonicalizeError::EscapesRoot)
+        ));
+        assert!(matches!(
+            canonicalize_within(dir.path().join("nope"), "a").await,
+            Err(CanonicalizeError::Io(_))
+        ));
+    }
+
+    #[cfg(all(windows, feature = "dunce"))]
+    #[tokio::test]
+    async fn verbatim() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let path = canonicalize_within(dir.path(), "a.txt").await.unwrap();
+        assert!(!path.to_string_lossy().starts_with(r"\\?\"), "{path:?}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "s").unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("../secret.txt", root.join("escape")).unwrap();
+        std::os::unix::fs::symlink("a.txt", root.join("inside")).unwrap();
+
+        assert!(matches!(
+            canonicalize_within(&root, "escape").await,
+            Err(CanonicalizeError::EscapesRoot)
+        ));
+        let path = canonicalize_within(&root, "inside").await.unwrap();
+        assert!(path.ends_with("a.txt"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn no_follow() {
+        use tokio::io::AsyncReadExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "a").unwrap();
+        std::os::unix::fs::symlink("sub/a.txt", root.join("file_link")).unwrap();
+        std::os::unix::fs::symlink("sub", root.join("dir_link")).unwrap();
+
+        let mut content = String::new();
+        let path: SafePath = "sub/a.txt".parse().unwrap();
+        secure_open(&root, &path)
+            .await
+            .unwrap()
+            .read_to_string(&mut content)
+            .await
+            .unwrap();
+        assert_eq!(content, "a");
+
+        for name in ["file_link", "dir_link/a.txt"] {
+            let path: SafePath = name.parse().unwrap();
+            let err = secure_open(&root, &path).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::PermissionDenied, "{name}");
+        }
+        let path: SafePath = "missing.txt".parse().unwrap();
+        let err = secure_open(&root, &path).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[tokio::test]
+    async fn remove_rename_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("a")).unwrap();
+        std::fs::write(root.join("a/1.txt"), "1").unwrap();
+        let path = |p: &str| p.parse::<SafePath>().unwrap();
+
+        let copied = copy_in(root, &path("a/1.txt"), &path("b/2.txt"))
+            .await
+            .unwrap();
+        assert_eq!(copied, root.join("b/2.txt"));
+        assert_eq!(std::fs::read_to_string(&copied).unwrap(), "1");
+
+        let moved = rename_in(root, &path("b/2.txt"), &path("c/d/3.txt"))
+            .await
+            .unwrap();
+        assert_eq!(moved, root.join("c/d/3.txt"));
+        assert!(!root.join("b/2.txt").exists());
+
+        remove_in(root, &path("c/d/3.txt")).await.unwrap();
+        remove_in(root, &path("c/d")).await.unwrap();
+        assert!(!root.join("c/d").exists());
+        // Only empty directories are removed
+        assert!(remove_in(root, &path("a")).await.is_err());
+
+        for (from, to) in [("../x", "a/y"), ("a/1.txt", "../y"), ("a/1.txt", "/tmp/y")] {
+            let from = SafePath(from.into());
+            let to = SafePath(to.into());
+            assert_eq!(
+                rename_in(root, &from, &to).await.unwrap_err().kind(),
+                io::ErrorKind::InvalidInput,
+                "{from:?} {to:?}"
+            );
+            assert_eq!(
+                copy_in(root, &from, &to).await.unwrap_err().kind(),
+                io::ErrorKind::InvalidInput,
+                "{from:?} {to:?}"
+            );
+        }
+        for root_path in ["", "."] {
+            assert_eq!(
+                remove_in(root, &path(root_path)).await.unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn confined_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(dir.path().join("outside")).unwrap();
+        std::fs::write(dir.path().join("outside/secret.txt"), "s").unwrap();
+        std::fs::create_dir(&root).unwrap();
+        std::os::unix::fs::symlink("../outside", root.join("out")).unwrap();
+        std::os::unix::fs::symlink("../outside/secret.txt", root.join("secret")).unwrap();
+        let path = |p: &str| p.parse::<SafePath>().unwrap();
+
+        for (from, to) in [
+            ("out/secret.txt", "x"),
+            ("secret", "x"),
+            ("secret", "out/x"),
+        ] {
+            assert!(
+                copy_in(&root, &path(from), &path(to)).await.is_err(),
+                "{from} {to}"
+            );
+        }
+        assert!(
+            rename_in(&root, &path("out/secret.txt"), &path("x"))
+                .await
+                .is_err()
+        );
+        assert!(remove_in(&root, &path("out/secret.txt")).await.is_err());
+
+        // The symlink itself can be removed
+        remove_in(&root, &path("secret")).await.unwrap();
+        assert!(dir.path().join("outside/secret.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn temp_files() {
+        use tokio::io::AsyncWriteExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let staging: SafePath = "a/./staging".parse().unwrap();
+
+        let (mut file, first) = temp_file_in(dir.path(), &staging).await.unwrap();
+        file.write_all(b"data").await.unwrap();
+        let (_, second) = temp_file_in(dir.path(), &staging).await.unwrap();
+        assert_ne!(first.0, second.0);
+        assert!(first.0.starts_with("a/staging"));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(&first.0)).unwrap(),
+            "data"
+        );
+
+        let (_, in_root) = temp_file_in(dir.path(), &"".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(in_root.0.components().count(), 1);
+
+        let err = temp_file_in(dir.path(), &SafePath("../x".into()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
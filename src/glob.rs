@@ -0,0 +1,529 @@
+//! Glob patterns: validation of untrusted ones and minimal matching.
+//!
+//! Patterns are matched component by component: `**` matches any number of
+//! components, `*` matches any sequence of characters within a component and
+//! `?` matches a single character.
+
+use std::{
+    path::{self, Path},
+    str::FromStr,
+};
+
+use crate::{SafePathRejection, Traversal};
+
+/// The default maximum pattern length in bytes
+const MAX_LENGTH: usize = 1024;
+
+/// The default maximum number of pattern segments
+const MAX_DEPTH: usize = 32;
+
+/// The default maximum number of `**` segments
+const MAX_GLOBSTARS: usize = 2;
+
+/// Characters with a meaning in the `glob` or `globset` syntax that the
+/// minimal matcher doesn't share
+const UNSUPPORTED_CHARACTERS: &[char] = &['[', ']', '{', '}', '\\'];
+
+/// A glob pattern that can only match paths inside the root.
+///
+/// Meant for search endpoints accepting patterns like `logs/**/*.txt`. The
+/// pattern is checked with the default [`SafeGlobConfig`], which rejects:
+///
+/// - empty patterns and patterns longer than 1024 bytes
+/// - absolute patterns and Windows drive prefixes
+/// - `.` and `..` segments
+/// - NUL bytes and other control characters
+/// - more than 32 segments and more than two `**` segments
+/// - `**` mixed with other characters in a segment, like `a**`
+///
+/// Only `*`, `?` and `**` are supported. Character classes, alternatives
+/// and escapes are rejected, so the pattern means the same to
+/// [`matches`](Self::matches) and the `glob` and `globset` crates.
+///
+/// ```
+/// # #[cfg(feature = "json")] {
+/// use axum::{Json, Router, routing::post};
+/// use axum_safe_path::SafeGlob;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Search {
+///     pattern: SafeGlob,
+/// }
+///
+/// async fn search(Json(search): Json<Search>) -> String {
+///     search.pattern.as_str().to_owned()
+/// }
+///
+/// let app: Router = Router::new().route("/search", post(search));
+///
+/// let pattern: SafeGlob = "logs/**/*.txt".parse().unwrap();
+/// assert!(pattern.matches("logs/2024/01/app.txt"));
+/// assert!("../logs/*.txt".parse::<SafeGlob>().is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SafeGlob(String);
+
+impl SafeGlob {
+    /// Returns the pattern
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the pattern
+    #[must_use]
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    /// Checks if the relative path matches the pattern
+    ///
+    /// Paths with `..`, a root or components that aren't valid UTF-8 never
+    /// match.
+    #[must_use]
+    pub fn matches(&self, path: impl AsRef<Path>) -> bool {
+        let mut components = Vec::new();
+        for component in path.as_ref().components() {
+            match component {
+                path::Component::Normal(name) => match name.to_str() {
+                    Some(name) => components.push(name),
+                    None => return false,
+                },
+                path::Component::CurDir => {}
+                _ => return false,
+            }
+        }
+        matches(&self.0, components)
+    }
+
+    /// Converts the pattern into a [`glob::Pattern`](::glob::Pattern)
+    ///
+    /// Match it with [`match_options`](Self::match_options), so `*` doesn't
+    /// cross separators, as it doesn't in [`matches`](Self::matches).
+    ///
+    /// # Errors
+    ///
+    /// Never fails for a validated pattern, the error is kept to avoid
+    /// panicking if the crates diverge.
+    #[cfg(feature = "glob")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "glob")))]
+    pub fn to_pattern(&self) -> Result<::glob::Pattern, ::glob::PatternError> {
+        ::glob::Pattern::new(&self.0)
+    }
+
+    /// Options matching [`glob::Pattern`](::glob::Pattern) the same way as
+    /// [`matches`](Self::matches)
+    #[cfg(feature = "glob")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "glob")))]
+    #[must_use]
+    pub const fn match_options() -> ::glob::MatchOptions {
+        ::glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        }
+    }
+
+    /// Iterates over the paths inside `root` matching the pattern
+    ///
+    /// The root is escaped, so its own metacharacters match literally. The
+    /// walk follows symlinks, which may point outside of the root, so open
+    /// the results with [`canonicalize_within`](crate::canonicalize_within)
+    /// or [`secure_open`](crate::secure_open) when the tree isn't trusted.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the root isn't valid UTF-8.
+    #[cfg(feature = "glob")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "glob")))]
+    pub fn walk(&self, root: impl AsRef<Path>) -> Result<::glob::Paths, ::glob::PatternError> {
+        let root = root.as_ref().to_str().ok_or(::glob::PatternError {
+            pos: 0,
+            msg: "the root is not valid UTF-8",
+        })?;
+        let root = ::glob::Pattern::escape(root.trim_end_matches('/'));
+        ::glob::glob_with(&format!("{root}/{}", self.0), Self::match_options())
+    }
+
+    /// Converts the pattern into a [`globset::Glob`](::globset::Glob)
+    ///
+    /// The glob is built with `literal_separator`, so `*` doesn't cross
+    /// separators, as it doesn't in [`matches`](Self::matches).
+    ///
+    /// # Errors
+    ///
+    /// Never fails for a validated pattern, the error is kept to avoid
+    /// panicking if the crates diverge.
+    #[cfg(feature = "globset")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "globset")))]
+    pub fn to_glob(&self) -> Result<::globset::Glob, ::globset::Error> {
+        ::globset::GlobBuilder::new(&self.0)
+            .literal_separator(true)
+            .backslash_escape(false)
+            .build()
+    }
+}
+
+impl AsRef<str> for SafeGlob {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for SafeGlob {
+    type Err = SafePathRejection;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SafeGlobConfig::default().validate(s)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SafeGlob {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+
+        SafeGlobConfig::default()
+            .validate(pattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Limits for [`SafeGlob`]
+///
+/// Each `**` multiplies the work of matching, so the limits bound the cost
+/// of a pattern as well as its reach:
+///
+/// ```
+/// use axum_safe_path::{SafeGlobConfig, SafePathRejection};
+///
+/// let config = SafeGlobConfig::new().max_globstars(1);
+/// assert!(config.check("logs/**/*.txt").is_ok());
+/// assert_eq!(
+///     config.check("**/logs/**/*.txt"),
+///     Err(SafePathRejection::TooManyGlobstars { max: 1 })
+/// );
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SafeGlobConfig {
+    length: usize,
+    depth: usize,
+    globstars: usize,
+}
+
+impl Default for SafeGlobConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SafeGlobConfig {
+    /// Creates a config with the default limits
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            length: MAX_LENGTH,
+            depth: MAX_DEPTH,
+            globstars: MAX_GLOBSTARS,
+        }
+    }
+
+    /// Rejects patterns longer than `length` bytes, 1024 by default
+    #[must_use]
+    pub const fn max_length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Rejects patterns having more than `depth` segments, 32 by default
+    #[must_use]
+    pub const fn max_depth(mut self, depth: usize) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Rejects patterns having more than `count` `**` segments, 2 by default
+    #[must_use]
+    pub const fn max_globstars(mut self, count: usize) -> Self {
+        self.globstars = count;
+        self
+    }
+
+    /// Validates the pattern against the config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn validate(&self, pattern: impl Into<String>) -> Result<SafeGlob, SafePathRejection> {
+        let pattern = pattern.into();
+        self.check(&pattern)?;
+        Ok(SafeGlob(pattern))
+    }
+
+    /// Checks the pattern against the config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn check(&self, pattern: &str) -> Result<(), SafePathRejection> {
+        if pattern.is_empty() {
+            return Err(SafePathRejection::Empty);
+        }
+        if pattern.len() > self.length {
+            return Err(SafePathRejection::TooLong { max: self.length });
+        }
+        if pattern.contains('\0') {
+            return Err(SafePathRejection::NulByte);
+        }
+        if pattern.starts_with('/') {
+            return Err(SafePathRejection::TraversalAttack(Traversal::RootDir));
+        }
+        if let [drive, b':', ..] = pattern.as_bytes()
+            && drive.is_ascii_alphabetic()
+        {
+            return Err(SafePathRejection::TraversalAttack(Traversal::Prefix));
+        }
+        if pattern.chars().any(char::is_control) || pattern.contains(UNSUPPORTED_CHARACTERS) {
+            return Err(SafePathRejection::InvalidCharacter);
+        }
+
+        let segments = pattern.split('/').filter(|segment| !segment.is_empty());
+        let mut segment_count = 0;
+        let mut globstars = 0;
+        for (index, segment) in segments.enumerate() {
+            match segment {
+                ".." => {
+                    return Err(SafePathRejection::TraversalAttack(Traversal::ParentDir {
+                        index,
+                    }));
+                }
+                "." => return Err(SafePathRejection::CurrentDir),
+                "**" => globstars += 1,
+                _ if segment.contains("**") => return Err(SafePathRejection::PatternMismatch),
+                _ => {}
+            }
+            segment_count += 1;
+        }
+        if segment_count > self.depth {
+            return Err(SafePathRejection::TooDeep { max: self.depth });
+        }
+        if globstars > self.globstars {
+            return Err(SafePathRejection::TooManyGlobstars {
+                max: self.globstars,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Checks if the path components match the pattern
+pub fn matches<'a>(pattern: &str, components: impl IntoIterator<Item = &'a str>) -> bool {
+    let pattern: Vec<&str> = pattern
+        .split('/')
+        .filter(|segment| !segment.is_empty() && *segment != ".")
+        .collect();
+    let components: Vec<&str> = components.into_iter().collect();
+    match_segments(&pattern, &components)
+}
+
+/// Matches pattern segments against path components
+fn match_segments(pattern: &[&str], components: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => components.is_empty(),
+        Some((&"**", rest)) => {
+            let mut tail = components;
+            loop {
+                if match_segments(rest, tail) {
+                    return true;
+                }
+                match tail.split_first() {
+                    Some((_, next)) => tail = next,
+                    None => return false,
+                }
+            }
+        }
+        Some((segment, rest)) => components
+            .split_first()
+            .is_some_and(|(name, tail)| match_name(segment, name) && match_segments(rest, tail)),
+    }
+}
+
+/// Matches a single component against a pattern segment with `*` and `?`
+fn match_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the name position it was
+    // tried at, for backtracking
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match (pattern.get(p), name.get(n)) {
+            (Some('*'), _) => {
+                star = Some((p, n));
+                p += 1;
+            }
+            (Some(&pc), Some(&nc)) if pc == '?' || pc == nc => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern
+        .get(p..)
+        .is_some_and(|rest| rest.iter().all(|&c| c == '*'))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn check(pattern: &str, path: &str) -> bool {
+        matches(pattern, path.split('/').filter(|s| !s.is_empty()))
+    }
+
+    #[test]
+    fn names() {
+        assert!(match_name("*", ""));
+        assert!(match_name("*", "foo"));
+        assert!(match_name("*.css", "main.css"));
+        assert!(match_name("*.css", ".css"));
+        assert!(match_name("m?in.*", "main.css"));
+        assert!(match_name("*a*b*", "xaxxbx"));
+        assert!(!match_name("*.css", "main.js"));
+        assert!(!match_name("?", ""));
+        assert!(!match_name("foo", "foobar"));
+        assert!(!match_name("*a*b", "xaxxbx"));
+    }
+
+    #[test]
+    fn paths() {
+        assert!(check("docs/*.md", "docs/index.md"));
+        assert!(!check("docs/*.md", "docs/api/index.md"));
+        assert!(!check("docs/*.md", "docs"));
+
+        assert!(check("assets/**/*.css", "assets/main.css"));
+        assert!(check("assets/**/*.css", "assets/css/vendor/main.css"));
+        assert!(!check("assets/**/*.css", "assets/css/main.js"));
+        assert!(!check("assets/**/*.css", "static/main.css"));
+
+        assert!(check("**", ""));
+        assert!(check("**", "a/b/c"));
+        assert!(check("./a//b", "a/b"));
+    }
+
+    #[test]
+    fn safe_glob() {
+        let pattern: SafeGlob = "logs/**/*.txt".parse().unwrap();
+        assert!(pattern.matches("logs/app.txt"));
+        assert!(pattern.matches("./logs/2024/01/app.txt"));
+        assert!(!pattern.matches("logs/app.log"));
+        assert!(!pattern.matches("logs/../secrets/app.txt"));
+        assert!(!pattern.matches("/logs/app.txt"));
+
+        assert!("*.md".parse::<SafeGlob>().is_ok());
+        assert!("a/?/b*".parse::<SafeGlob>().is_ok());
+    }
+
+    #[test]
+    fn safe_glob_rejections() {
+        for (pattern, rejection) in [
+            ("", SafePathRejection::Empty),
+            ("a/\0", SafePathRejection::NulByte),
+            (
+                "/etc/*",
+                SafePathRejection::TraversalAttack(Traversal::RootDir),
+            ),
+            (
+                "C:/*",
+                SafePathRejection::TraversalAttack(Traversal::Prefix),
+            ),
+            (
+                "logs/../*",
+                SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 1 }),
+            ),
+            ("./*", SafePathRejection::CurrentDir),
+            ("a\\b", SafePathRejection::InvalidCharacter),
+            ("{..,a}/*", SafePathRejection::InvalidCharacter),
+            ("[.]/*", SafePathRejection::InvalidCharacter),
+            ("a\tb", SafePathRejection::InvalidCharacter),
+            ("a**/b", SafePathRejection::PatternMismatch),
+            ("**/**/**", SafePathRejection::TooManyGlobstars { max: 2 }),
+        ] {
+            assert_eq!(pattern.parse::<SafeGlob>(), Err(rejection), "{pattern:?}");
+        }
+
+        let config = SafeGlobConfig::new().max_length(4).max_depth(2);
+        assert!(config.check("a/b").is_ok());
+        assert_eq!(
+            config.check("a/b/c"),
+            Err(SafePathRejection::TooLong { max: 4 })
+        );
+        assert_eq!(
+            config.max_length(8).check("a/b/c"),
+            Err(SafePathRejection::TooDeep { max: 2 })
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn safe_glob_deserialize() {
+        let pattern: SafeGlob = serde_json::from_str(r#""docs/*.md""#).unwrap();
+        assert_eq!(pattern.as_str(), "docs/*.md");
+        assert!(serde_json::from_str::<SafeGlob>(r#""../*.md""#).is_err());
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn glob_crate() {
+        let pattern: SafeGlob = "logs/**/*.txt".parse().unwrap();
+        let compiled = pattern.to_pattern().unwrap();
+        let options = SafeGlob::match_options();
+        assert!(compiled.matches_with("logs/2024/app.txt", options));
+        assert!(!compiled.matches_with("logs/2024/app.log", options));
+
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("logs/2024")).unwrap();
+        std::fs::write(root.path().join("logs/2024/app.txt"), "").unwrap();
+        std::fs::write(root.path().join("logs/app.log"), "").unwrap();
+        std::fs::write(root.path().join("app.txt"), "").unwrap();
+        let found: Vec<_> = pattern
+            .walk(root.path())
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .collect();
+        assert_eq!(found, [root.path().join("logs/2024/app.txt")]);
+    }
+
+    #[cfg(feature = "globset")]
+    #[test]
+    fn globset_crate() {
+        let pattern: SafeGlob = "logs/**/*.txt".parse().unwrap();
+        let matcher = pattern.to_glob().unwrap().compile_matcher();
+        for path in [
+            "logs/app.txt",
+            "logs/2024/01/app.txt",
+            "logs/a.txt/b",
+            "x/logs/a.txt",
+        ] {
+            assert_eq!(matcher.is_match(path), pattern.matches(path), "{path:?}");
+        }
+        assert!(!matcher.is_match("logs/a/b.log"));
+    }
+}
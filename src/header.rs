@@ -0,0 +1,242 @@
+//! Paths transported in request headers.
+
+use std::{marker::PhantomData, path::PathBuf};
+
+#[cfg(feature = "axum08")]
+use axum::extract::OptionalFromRequestParts;
+use axum::{
+    extract::FromRequestParts,
+    http::{HeaderName, Uri, request::Parts},
+};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection, raw};
+
+/// A request header carrying a path, see [`SafeHeaderPath`]
+pub trait PathHeader: Send + Sync + 'static {
+    /// The header name
+    const NAME: HeaderName;
+
+    /// Extracts the path from the header value, the value itself by default
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection if the value can't be turned into a path.
+    fn path(value: &str) -> Result<PathBuf, SafePathRejection> {
+        Ok(PathBuf::from(value))
+    }
+}
+
+/// The `X-File-Path` header holding a plain relative path
+#[derive(Debug, Clone, Copy)]
+pub struct XFilePath;
+
+impl PathHeader for XFilePath {
+    const NAME: HeaderName = HeaderName::from_static("x-file-path");
+}
+
+/// The `Destination` header of `WebDAV` `COPY` and `MOVE` requests.
+///
+/// The value is an absolute URI or an absolute path, the path part of which is
+/// percent-decoded and used without the leading slash.
+#[derive(Debug, Clone, Copy)]
+pub struct Destination;
+
+impl PathHeader for Destination {
+    const NAME: HeaderName = HeaderName::from_static("destination");
+
+    fn path(value: &str) -> Result<PathBuf, SafePathRejection> {
+        let uri: Uri = value
+            .parse()
+            .map_err(|_| SafePathRejection::InvalidCharacter)?;
+        if raw::has_encoded_separator(uri.path()) {
+            return Err(SafePathRejection::EncodedSeparator);
+        }
+        let decoded = String::from_utf8(raw::percent_decode(uri.path()))
+            .map_err(|_| SafePathRejection::InvalidUtf8)?;
+        Ok(PathBuf::from(decoded.trim_start_matches('/')))
+    }
+}
+
+/// A path taken from a request header and validated with the current
+/// [`SafePathConfig`].
+///
+/// Some upload and copy protocols transport paths in headers rather than the
+/// URI. The header is chosen with a [`PathHeader`] type, like the built-in
+/// [`XFilePath`] and [`Destination`]. A missing header is rejected, use
+/// `Option<SafeHeaderPath<_>>` if it's optional:
+///
+/// ```
+/// use axum::{Router, http::HeaderName, routing::put};
+/// use axum_safe_path::{PathHeader, SafeHeaderPath, XFilePath};
+///
+/// async fn upload(header: SafeHeaderPath<XFilePath>) -> String {
+///     header.path.display().to_string()
+/// }
+///
+/// struct UploadPath;
+///
+/// impl PathHeader for UploadPath {
+///     const NAME: HeaderName = HeaderName::from_static("upload-path");
+/// }
+///
+/// async fn custom(header: Option<SafeHeaderPath<UploadPath>>) -> String {
+///     header.map(|h| h.path.display().to_string()).unwrap_or_default()
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/upload", put(upload))
+///     .route("/custom", put(custom));
+/// ```
+///
+/// With axum 0.7, the optional form is `None` for invalid paths too, as it
+/// can't tell them from missing headers.
+#[derive(Debug, Clone)]
+pub struct SafeHeaderPath<H> {
+    /// The validated path
+    pub path: PathBuf,
+    header: PhantomData<H>,
+}
+
+impl<H> SafeHeaderPath<H> {
+    /// Returns the validated path
+    #[must_use]
+    pub fn into_inner(self) -> PathBuf {
+        self.path
+    }
+}
+
+impl<H> AsRef<std::path::Path> for SafeHeaderPath<H> {
+    fn as_ref(&self) -> &std::path::Path {
+        self.path.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<H, S> FromRequestParts<S> for SafeHeaderPath<H>
+where
+    H: PathHeader,
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::extract(parts)?.ok_or(SafePathRejection::MissingHeader { name: H::NAME })
+    }
+}
+
+#[cfg(feature = "axum08")]
+impl<H, S> OptionalFromRequestParts<S> for SafeHeaderPath<H>
+where
+    H: PathHeader,
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Self::extract(parts)
+    }
+}
+
+impl<H: PathHeader> SafeHeaderPath<H> {
+    /// Validates the path of the header if it's present
+    fn extract(parts: &mut Parts) -> Result<Option<Self>, SafePathRejection> {
+        let Some(value) = parts.headers.get(H::NAME) else {
+            return Ok(None);
+        };
+        let value = value
+            .to_str()
+            .map_err(|_| SafePathRejection::InvalidCharacter)?;
+        let path = H::path(value)?;
+        let SafePath(path) =
+            SafePathConfig::validate_current(&mut parts.extensions, value, |config| {
+                config.validate(path)
+            })?;
+        Ok(Some(Self {
+            path,
+            header: PhantomData,
+        }))
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::put};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    #[test]
+    fn destination() {
+        for (value, expected) in [
+            ("http://example.com/dav/a%20b.txt", "dav/a b.txt"),
+            ("/dav/a.txt", "dav/a.txt"),
+            ("/", ""),
+        ] {
+            assert_eq!(
+                Destination::path(value).unwrap(),
+                PathBuf::from(expected),
+                "{value}"
+            );
+        }
+        assert!(matches!(
+            Destination::path("/dav/a%2F..%2F..%2Fetc"),
+            Err(SafePathRejection::EncodedSeparator)
+        ));
+        assert!(matches!(
+            Destination::path("/dav/%FF"),
+            Err(SafePathRejection::InvalidUtf8)
+        ));
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        async fn handler(header: SafeHeaderPath<XFilePath>) -> String {
+            header.path.display().to_string()
+        }
+
+        async fn optional(header: Option<SafeHeaderPath<Destination>>) -> String {
+            header.map_or_else(|| "-".into(), |h| h.path.display().to_string())
+        }
+
+        let app = Router::new()
+            .route("/", put(handler))
+            .route("/optional", put(optional))
+            .layer(Extension(SafePathConfig::new().deny_hidden(true)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server
+            .put("/")
+            .add_header("x-file-path", "docs/a.txt")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "docs/a.txt");
+
+        for value in ["../etc/passwd", "/etc/passwd", "docs/.env"] {
+            let res = server.put("/").add_header("x-file-path", value).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{value}");
+        }
+
+        let res = server.put("/").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Missing `x-file-path` header");
+
+        let res = server.put("/optional").await;
+        assert_eq!(res.text(), "-");
+
+        let res = server
+            .put("/optional")
+            .add_header("destination", "http://localhost/b/c.txt")
+            .await;
+        assert_eq!(res.text(), "b/c.txt");
+
+        let res = server
+            .put("/optional")
+            .add_header("destination", "http://localhost/b/%2e%2e/%2e%2e/x")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
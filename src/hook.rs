@@ -0,0 +1,146 @@
+//! Callbacks fired on rejections.
+
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex},
+};
+
+use axum::http::Uri;
+
+use crate::SafePathRejection;
+
+/// A rejection reported to the
+/// [`on_rejection`](crate::SafePathPolicyLayer::on_rejection) callback
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RejectionEvent {
+    /// The reason of the rejection
+    pub rejection: Arc<SafePathRejection>,
+    /// The route matched by the router, like `/files/{*path}`
+    ///
+    /// It's only known if the layer runs after the routing, e.g. when it's
+    /// added with [`route_layer`](axum::Router::route_layer).
+    pub route: Option<String>,
+    /// The request URI as sent by the client, before percent-decoding
+    pub uri: Uri,
+    /// The rejected value as sent by the client, e.g. the raw path capture
+    /// or the header value
+    ///
+    /// It's `None` if the rejection happened before validation, e.g. when
+    /// the path couldn't be extracted.
+    pub value: Option<OffendingValue>,
+}
+
+/// The raw value of a rejected input, for logs and callbacks only
+///
+/// The value is attacker-controlled, so it's kept out of responses: the type
+/// implements neither `IntoResponse` nor `Serialize`, and rejection messages
+/// never include it. `Debug` quotes the value and escapes control characters,
+/// which keeps line-based logs intact.
+#[derive(Clone, PartialEq, Eq)]
+pub struct OffendingValue(String);
+
+impl OffendingValue {
+    /// The value as sent by the client
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for OffendingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// A request extension receiving the offending value during validation
+///
+/// The layer inserts it only if the value is needed, so requests outside of
+/// such layers don't pay for copying it.
+#[derive(Debug, Clone)]
+pub struct OffendingValueSlot {
+    value: Arc<Mutex<Option<OffendingValue>>>,
+    /// Whether to add the value to the rejection warning
+    pub log: bool,
+}
+
+impl OffendingValueSlot {
+    /// Creates an empty slot
+    pub fn new(log: bool) -> Self {
+        Self {
+            value: Arc::default(),
+            log,
+        }
+    }
+
+    /// Stores the value, replacing the one of a previous rejection
+    pub fn set(&self, value: &str) -> OffendingValue {
+        let value = OffendingValue(value.to_owned());
+        if let Ok(mut slot) = self.value.lock() {
+            *slot = Some(value.clone());
+        }
+        value
+    }
+
+    /// Takes the stored value
+    pub fn take(&self) -> Option<OffendingValue> {
+        self.value.lock().ok().and_then(|mut slot| slot.take())
+    }
+}
+
+/// A boxed future returned by the callback
+type HookFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The function called on rejections
+type HookFn = dyn Fn(RejectionEvent) -> HookFuture + Send + Sync;
+
+/// The callback fired on rejections
+#[derive(Clone)]
+pub struct OnRejection(Arc<HookFn>);
+
+impl OnRejection {
+    /// Wraps the async function
+    pub fn new<F, Fut>(hook: F) -> Self
+    where
+        F: Fn(RejectionEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self(Arc::new(move |event| Box::pin(hook(event))))
+    }
+
+    /// Calls the function with the event
+    pub fn call(&self, event: RejectionEvent) -> HookFuture {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for OnRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnRejection")
+    }
+}
+
+/// The request details kept until the response is known
+#[derive(Debug)]
+pub struct PendingEvent {
+    /// See [`RejectionEvent::route`]
+    pub route: Option<String>,
+    /// See [`RejectionEvent::uri`]
+    pub uri: Uri,
+    /// Receives [`RejectionEvent::value`]
+    pub slot: OffendingValueSlot,
+}
+
+impl PendingEvent {
+    /// Completes the event with the rejection
+    pub fn into_event(self, rejection: Arc<SafePathRejection>) -> RejectionEvent {
+        RejectionEvent {
+            rejection,
+            route: self.route,
+            uri: self.uri,
+            value: self.slot.take(),
+        }
+    }
+}
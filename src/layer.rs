@@ -0,0 +1,804 @@
+//! Tower layer setting the config for a part of the router.
+
+#[cfg(feature = "tarpit")]
+use std::{
+    hash::{BuildHasher, RandomState},
+    ops::Range,
+};
+use std::{
+    mem,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+#[cfg(feature = "i18n")]
+use crate::response::Translator;
+use crate::{
+    SafePathConfig,
+    hook::{OffendingValueSlot, OnRejection, PendingEvent, RejectionEvent},
+    policy::Policy,
+    response::{NotFound, Rejected, RejectionFormat, Responder},
+};
+
+/// A layer storing a [`SafePathConfig`] in request extensions.
+///
+/// Unlike [`axum::Extension`], it also removes a [`ReloadableConfig`] set by
+/// outer layers, so the innermost layer always wins. This allows different
+/// router subtrees to apply different rules with the same [`SafePath`]
+/// extractor:
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::{SafePath, SafePathConfig, SafePathPolicyLayer};
+///
+/// async fn handler(SafePath(path): SafePath) -> String {
+///     path.display().to_string()
+/// }
+///
+/// let assets = Router::new()
+///     .route("/{*path}", get(handler))
+///     .layer(SafePathPolicyLayer::new(SafePathConfig::web_assets()));
+/// let admin = Router::new()
+///     .route("/{*path}", get(handler))
+///     .layer(SafePathPolicyLayer::new(SafePathConfig::new().max_depth(8)));
+/// let app: Router = Router::new()
+///     .nest("/assets", assets)
+///     .nest("/admin/files", admin);
+/// ```
+///
+/// Paths are rejected with `400 Bad Request` by default. The status can be
+/// changed for the subtree with [`rejection_status`](Self::rejection_status),
+/// e.g. to answer probing requests with `404 Not Found` and not confirm the
+/// endpoint exists:
+///
+/// ```
+/// use axum::{Router, http::StatusCode, routing::get};
+/// use axum_safe_path::{SafePath, SafePathConfig, SafePathPolicyLayer};
+///
+/// async fn handler(SafePath(path): SafePath) -> String {
+///     path.display().to_string()
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(handler)).layer(
+///     SafePathPolicyLayer::new(SafePathConfig::new()).rejection_status(StatusCode::NOT_FOUND),
+/// );
+/// ```
+///
+/// The body is a plain-text message unless another
+/// [`rejection_format`](Self::rejection_format) is chosen.
+///
+/// [`SafePath`]: crate::SafePath
+/// [`ReloadableConfig`]: crate::ReloadableConfig
+#[derive(Debug, Clone)]
+pub struct SafePathPolicyLayer {
+    config: SafePathConfig,
+    responder: Responder,
+    on_rejection: Option<OnRejection>,
+    log_offending_values: bool,
+    #[cfg(feature = "tarpit")]
+    rejection_delay: Option<Range<Duration>>,
+}
+
+impl SafePathPolicyLayer {
+    /// Creates a layer applying the config
+    #[must_use]
+    pub const fn new(config: SafePathConfig) -> Self {
+        Self {
+            config,
+            responder: Responder::new(),
+            on_rejection: None,
+            log_offending_values: false,
+            #[cfg(feature = "tarpit")]
+            rejection_delay: None,
+        }
+    }
+
+    /// Sets the status of responses to paths rejected by the config, like
+    /// `403`, `404` or `422`
+    ///
+    /// Only the validation failures are affected, the errors of the
+    /// underlying extractors keep their statuses. As with the config, the
+    /// innermost layer wins.
+    #[must_use]
+    pub const fn rejection_status(mut self, status: StatusCode) -> Self {
+        self.responder.status = Some(status);
+        self
+    }
+
+    /// Sets the format of responses to paths rejected by the config
+    ///
+    /// ```
+    /// # #[cfg(feature = "json")] {
+    /// use axum_safe_path::{RejectionFormat, SafePathConfig, SafePathPolicyLayer};
+    ///
+    /// let layer = SafePathPolicyLayer::new(SafePathConfig::new())
+    ///     .rejection_format(RejectionFormat::Json);
+    /// # }
+    /// ```
+    #[must_use]
+    pub const fn rejection_format(mut self, format: RejectionFormat) -> Self {
+        self.responder.format = format;
+        self
+    }
+
+    /// Replaces the messages of responses to paths rejected by the config
+    ///
+    /// Useful to show product-specific wording instead of the technical
+    /// default ones. The machine-readable codes of the JSON formats are kept.
+    ///
+    /// Unlike [`SafePathConfig::rejection_message`], it also replaces the
+    /// messages of rejections made with the configs of inner layers.
+    ///
+    /// ```
+    /// use axum_safe_path::{SafePathConfig, SafePathPolicyLayer};
+    ///
+    /// let layer = SafePathPolicyLayer::new(SafePathConfig::new())
+    ///     .rejection_message("Sorry, we couldn't find this file");
+    /// ```
+    #[must_use]
+    pub fn rejection_message(mut self, message: impl Into<Arc<str>>) -> Self {
+        self.responder.message = Some(message.into());
+        self
+    }
+
+    /// Answers paths rejected by the config the same way as the default
+    /// router fallback, with `404 Not Found` and an empty body
+    ///
+    /// Then probing requests can't tell routes validating paths from missing
+    /// ones. It takes precedence over the status, format and messages of
+    /// rejections.
+    #[must_use]
+    pub fn mask_as_not_found(mut self) -> Self {
+        self.responder.not_found = Some(NotFound::default());
+        self
+    }
+
+    /// Answers paths rejected by the config with the response of a custom
+    /// router fallback, see [`mask_as_not_found`](Self::mask_as_not_found)
+    ///
+    /// ```
+    /// use axum::{Router, http::StatusCode, routing::get};
+    /// use axum_safe_path::{SafePath, SafePathConfig, SafePathPolicyLayer};
+    ///
+    /// fn not_found() -> (StatusCode, &'static str) {
+    ///     (StatusCode::NOT_FOUND, "Nothing here")
+    /// }
+    ///
+    /// async fn handler(SafePath(path): SafePath) -> String {
+    ///     path.display().to_string()
+    /// }
+    ///
+    /// let app: Router = Router::new()
+    ///     .route("/files/{*path}", get(handler))
+    ///     .layer(SafePathPolicyLayer::new(SafePathConfig::new()).mask_as_not_found_with(not_found))
+    ///     .fallback(async || not_found());
+    /// ```
+    #[must_use]
+    pub fn mask_as_not_found_with<F, R>(mut self, fallback: F) -> Self
+    where
+        F: Fn() -> R + Send + Sync + 'static,
+        R: IntoResponse,
+    {
+        self.responder.not_found =
+            Some(NotFound(Some(Arc::new(move || fallback().into_response()))));
+        self
+    }
+
+    /// Sets a lookup of translated rejection messages
+    ///
+    /// It's called with the languages of the `Accept-Language` header, from
+    /// the most preferred one, and the
+    /// [`code`](crate::SafePathRejection::code) of the rejection until it
+    /// returns a message. Without a translation the message set by
+    /// [`rejection_message`](Self::rejection_message) or the default one is
+    /// used.
+    ///
+    /// ```
+    /// use axum_safe_path::{SafePathConfig, SafePathPolicyLayer};
+    ///
+    /// let layer = SafePathPolicyLayer::new(SafePathConfig::new()).rejection_translator(
+    ///     |language, _code| match language {
+    ///         "de" | "de-DE" => Some("Ungültiger Pfad".to_owned()),
+    ///         "uk" => Some("Неприпустимий шлях".to_owned()),
+    ///         _ => None,
+    ///     },
+    /// );
+    /// ```
+    #[cfg(feature = "i18n")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+    #[must_use]
+    pub fn rejection_translator<F>(mut self, translate: F) -> Self
+    where
+        F: Fn(&str, &str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.responder.translator = Some(Translator(Arc::new(translate)));
+        self
+    }
+
+    /// Sets an async callback fired on every path rejected by the config
+    ///
+    /// It gets the reason, the matched route and the raw request URI, e.g. to
+    /// report probing to an alerting system. The response is sent after the
+    /// returned future completes, so long-running work is better spawned:
+    ///
+    /// ```
+    /// use axum::{Router, routing::get};
+    /// use axum_safe_path::{SafePath, SafePathConfig, SafePathPolicyLayer};
+    ///
+    /// async fn handler(SafePath(path): SafePath) -> String {
+    ///     path.display().to_string()
+    /// }
+    ///
+    /// let app: Router = Router::new().route("/files/{*path}", get(handler)).route_layer(
+    ///     SafePathPolicyLayer::new(SafePathConfig::new()).on_rejection(|event| async move {
+    ///         eprintln!("{:?} at {:?}: {}", event.rejection, event.route, event.uri);
+    ///     }),
+    /// );
+    /// ```
+    ///
+    /// The offending value is only passed to the callback and never appears in
+    /// the response, see [`RejectionEvent::value`].
+    #[must_use]
+    pub fn on_rejection<F, Fut>(mut self, hook: F) -> Self
+    where
+        F: Fn(RejectionEvent) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_rejection = Some(OnRejection::new(hook));
+        self
+    }
+
+    /// Adds the raw rejected values to the warnings logged with the `tracing`
+    /// feature
+    ///
+    /// Off by default, as the values are attacker-controlled and may contain
+    /// personal data. They're logged with `Debug`, so control characters are
+    /// escaped, and never appear in responses.
+    #[must_use]
+    pub const fn log_offending_values(mut self, log: bool) -> Self {
+        self.log_offending_values = log;
+        self
+    }
+
+    /// Delays responses to paths rejected by the config by a random duration
+    /// in the range
+    ///
+    /// Slows down automated scanners probing for traversals, at the cost of
+    /// keeping their connections open longer. Off by default.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use axum_safe_path::{SafePathConfig, SafePathPolicyLayer};
+    ///
+    /// let layer = SafePathPolicyLayer::new(SafePathConfig::new())
+    ///     .rejection_delay(Duration::from_secs(1)..Duration::from_secs(3));
+    /// ```
+    #[cfg(feature = "tarpit")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tarpit")))]
+    #[must_use]
+    pub const fn rejection_delay(mut self, delay: Range<Duration>) -> Self {
+        self.rejection_delay = Some(delay);
+        self
+    }
+
+    /// Creates a layer applying the default config with the policy on top of
+    /// it
+    #[must_use]
+    pub fn from_policy<P: Policy>(policy: P) -> Self {
+        Self::new(SafePathConfig::new().policy(policy))
+    }
+}
+
+impl<S> Layer<S> for SafePathPolicyLayer {
+    type Service = SafePathPolicy<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SafePathPolicy {
+            inner,
+            config: self.config.clone(),
+            responder: self.responder.clone(),
+            on_rejection: self.on_rejection.clone(),
+            log_offending_values: self.log_offending_values,
+            #[cfg(feature = "tarpit")]
+            rejection_delay: self.rejection_delay.clone(),
+        }
+    }
+}
+
+/// The middleware created by [`SafePathPolicyLayer`]
+#[derive(Debug, Clone)]
+pub struct SafePathPolicy<S> {
+    inner: S,
+    config: SafePathConfig,
+    responder: Responder,
+    on_rejection: Option<OnRejection>,
+    log_offending_values: bool,
+    #[cfg(feature = "tarpit")]
+    rejection_delay: Option<Range<Duration>>,
+}
+
+impl<S, B> Service<Request<B>> for SafePathPolicy<S>
+where
+    S: Service<Request<B>, Response = Response>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let extensions = req.extensions_mut();
+        #[cfg(feature = "arc-swap")]
+        extensions.remove::<crate::ReloadableConfig>();
+        extensions.insert(self.config.clone());
+        // Replaces the slot of outer layers, as the innermost one handles
+        // rejections
+        let slot = if self.on_rejection.is_some() || self.log_offending_values {
+            let slot = OffendingValueSlot::new(self.log_offending_values);
+            extensions.insert(slot.clone());
+            Some(slot)
+        } else {
+            extensions.remove::<OffendingValueSlot>();
+            None
+        };
+        let hook = self.on_rejection.clone().zip(slot).map(|(hook, slot)| {
+            let pending = PendingEvent {
+                route: req
+                    .extensions()
+                    .get::<MatchedPath>()
+                    .map(|route| route.as_str().to_owned()),
+                uri: req.uri().clone(),
+                slot,
+            };
+            (hook, pending)
+        });
+        ResponseFuture {
+            responder: self.responder.clone().for_request(req.headers()),
+            hook,
+            #[cfg(feature = "tarpit")]
+            delay: self.rejection_delay.as_ref().map(random_delay),
+            #[cfg(not(feature = "tarpit"))]
+            delay: None,
+            notifying: None,
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+pin_project! {
+    /// The response future of [`SafePathPolicy`]
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        responder: Responder,
+        hook: Option<(OnRejection, PendingEvent)>,
+        delay: Option<Duration>,
+        notifying: Option<Notifying>,
+    }
+}
+
+/// Picks a delay from the range
+#[cfg(feature = "tarpit")]
+fn random_delay(range: &Range<Duration>) -> Duration {
+    let span = range.end.saturating_sub(range.start).as_nanos();
+    let span = u64::try_from(span).unwrap_or(u64::MAX);
+    if span == 0 {
+        return range.start;
+    }
+    // Hashers are randomly seeded, which is enough for jitter
+    let random = RandomState::new().hash_one(span);
+    range.start + Duration::from_nanos(random % span)
+}
+
+/// The rejection callback and delay in progress
+struct Notifying {
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    response: Response,
+}
+
+impl<F, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response, E>>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if this.notifying.is_none() {
+            let res = ready!(this.inner.poll(cx))?;
+            let Some(rejection) = Rejected::unhandled(&res).cloned() else {
+                return Poll::Ready(Ok(this.responder.respond(res)));
+            };
+            let hook = this
+                .hook
+                .take()
+                .map(|(hook, pending)| hook.call(pending.into_event(rejection)));
+            let delay = this.delay.take();
+            if hook.is_none() && delay.is_none() {
+                return Poll::Ready(Ok(this.responder.respond(res)));
+            }
+            *this.notifying = Some(Notifying {
+                future: Box::pin(async move {
+                    if let Some(hook) = hook {
+                        hook.await;
+                    }
+                    #[cfg(feature = "tarpit")]
+                    if let Some(delay) = delay {
+                        tokio::time::sleep(delay).await;
+                    }
+                }),
+                response: res,
+            });
+        }
+
+        let Some(notifying) = this.notifying else {
+            return Poll::Pending;
+        };
+        ready!(notifying.future.as_mut().poll(cx));
+        let res = mem::take(&mut notifying.response);
+        Poll::Ready(Ok(this.responder.respond(res)))
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{
+        Extension, Router,
+        http::{StatusCode, header},
+        routing::get,
+    };
+    use axum_test::TestServer;
+
+    use super::*;
+    use crate::SafePath;
+
+    async fn handler(SafePath(path): SafePath) -> String {
+        path.display().to_string()
+    }
+
+    #[tokio::test]
+    async fn per_router_config() {
+        let public = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(SafePathPolicyLayer::new(
+                SafePathConfig::new().deny_hidden(true),
+            ));
+        let admin = Router::new().route("/{*path}", get(handler));
+        let app = Router::new()
+            .nest("/public", public)
+            .nest("/admin", admin)
+            .layer(SafePathPolicyLayer::new(SafePathConfig::new().max_depth(2)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/public/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+
+        let res = server.get("/public/a/b/c").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server.get("/admin/.env").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server.get("/admin/a/b/c").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn rejection_status() {
+        let inner = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(SafePathPolicyLayer::new(SafePathConfig::new()));
+        let forbidden = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                .rejection_status(StatusCode::FORBIDDEN),
+        );
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .nest("/inner", inner)
+            .nest("/forbidden", forbidden)
+            .layer(
+                SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                    .rejection_status(StatusCode::NOT_FOUND),
+            );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/a/.env").await;
+        assert_eq!(res.status_code(), StatusCode::NOT_FOUND);
+
+        let res = server.get("/forbidden/.env").await;
+        assert_eq!(res.status_code(), StatusCode::FORBIDDEN);
+
+        let res = server.get("/inner/..%2Fb").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+
+        let res = server.get("/a/b").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn json_format() {
+        let app = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                .rejection_format(RejectionFormat::Json)
+                .rejection_status(StatusCode::UNPROCESSABLE_ENTITY),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/..%2Fetc").await;
+        assert_eq!(res.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(res.maybe_header("vary").is_none());
+        assert_eq!(
+            res.json::<serde_json::Value>(),
+            serde_json::json!({
+                "error": "path_traversal",
+                "detail": "Invalid path: possible traversal attack detected",
+            })
+        );
+
+        let res = server.get("/.env").await;
+        assert_eq!(
+            res.json::<serde_json::Value>(),
+            serde_json::json!({
+                "error": "hidden",
+                "detail": "Invalid path: hidden files are not allowed",
+            })
+        );
+
+        let res = server.get("/a").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "problem")]
+    #[tokio::test]
+    async fn problem_format() {
+        let app = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new())
+                .rejection_format(RejectionFormat::Problem)
+                .rejection_status(StatusCode::FORBIDDEN),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/..%2Fetc").await;
+        assert_eq!(res.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(res.header("content-type"), "application/problem+json");
+        assert_eq!(
+            res.json::<serde_json::Value>(),
+            serde_json::json!({
+                "type": "about:blank",
+                "title": "Forbidden",
+                "status": 403,
+                "detail": "Invalid path: possible traversal attack detected",
+                "code": "path_traversal",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn negotiated_format() {
+        let app = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new())
+                .rejection_format(RejectionFormat::Negotiate),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server
+            .get("/..%2Fetc")
+            .add_header("accept", "text/html,*/*;q=0.8")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.header("content-type"), "text/html; charset=utf-8");
+        assert!(res.text().contains("<h1>400 Bad Request</h1>"));
+        assert_eq!(res.header("vary"), "accept");
+
+        let res = server.get("/..%2Fetc").await;
+        assert_eq!(res.header("content-type"), "text/plain; charset=utf-8");
+        assert_eq!(res.header("vary"), "accept");
+        assert_eq!(
+            res.text(),
+            "Invalid path: possible traversal attack detected"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejection_message() {
+        let app = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                .rejection_message("Nothing here"),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        for path in ["/..%2Fetc", "/.env"] {
+            let res = server.get(path).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+            assert_eq!(res.text(), "Nothing here");
+        }
+    }
+
+    #[tokio::test]
+    async fn rejection_message_of_config() {
+        let config = SafePathConfig::new()
+            .deny_hidden(true)
+            .rejection_message("Not here");
+        let bare = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(Extension(config.clone()));
+        let server = TestServer::new(bare.clone()).unwrap();
+        let res = server.get("/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Not here");
+
+        // The layer replaces the message whatever set the config
+        let app = bare.layer(
+            SafePathPolicyLayer::new(SafePathConfig::new()).rejection_message("Nothing here"),
+        );
+        let server = TestServer::new(app).unwrap();
+        let res = server.get("/.env").await;
+        assert_eq!(res.text(), "Nothing here");
+    }
+
+    #[cfg(feature = "i18n")]
+    #[tokio::test]
+    async fn translated_message() {
+        let app = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                .rejection_message("Invalid path")
+                .rejection_translator(|language, code| match (language, code) {
+                    ("de", "hidden") => Some("Versteckte Dateien sind nicht erlaubt".to_owned()),
+                    ("de", _) => Some("Ungültiger Pfad".to_owned()),
+                    _ => None,
+                }),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server
+            .get("/.env")
+            .add_header("accept-language", "fr, de;q=0.9")
+            .await;
+        assert_eq!(res.text(), "Versteckte Dateien sind nicht erlaubt");
+        assert_eq!(res.header("vary"), "accept-language");
+
+        let res = server
+            .get("/..%2Fetc")
+            .add_header("accept-language", "de")
+            .await;
+        assert_eq!(res.text(), "Ungültiger Pfad");
+
+        let res = server
+            .get("/.env")
+            .add_header("accept-language", "fr")
+            .await;
+        assert_eq!(res.text(), "Invalid path");
+        assert_eq!(res.header("vary"), "accept-language");
+    }
+
+    #[tokio::test]
+    async fn on_rejection() {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let app = Router::new()
+            .route("/files/{*path}", get(handler))
+            .route_layer(
+                SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true)).on_rejection(
+                    move |event| {
+                        let tx = tx.clone();
+                        async move {
+                            tx.send(event).unwrap();
+                        }
+                    },
+                ),
+            );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/files/a/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        let event = rx.try_recv().unwrap();
+        assert!(matches!(*event.rejection, crate::SafePathRejection::Hidden));
+        assert_eq!(event.route.as_deref(), Some("/files/{*path}"));
+        assert_eq!(event.uri.path(), "/files/a/.env");
+        assert_eq!(event.value.unwrap().as_str(), "a/.env");
+        assert!(!res.text().contains("a/.env"));
+
+        let res = server.get("/files/a").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn masked_as_not_found() {
+        fn not_found() -> (StatusCode, &'static str) {
+            (StatusCode::NOT_FOUND, "Nothing here")
+        }
+
+        let default = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                .rejection_format(RejectionFormat::Html)
+                .mask_as_not_found(),
+        );
+        let custom = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(
+                SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                    .mask_as_not_found_with(not_found),
+            )
+            .fallback(async || not_found());
+        let app = Router::new()
+            .nest("/default", default)
+            .nest("/custom", custom);
+        let server = TestServer::new(app).unwrap();
+        assert_eq!(server.get("/custom").await.text(), "Nothing here");
+
+        for prefix in ["/default", "/custom"] {
+            let missing = server.get(prefix).await;
+            assert_eq!(missing.status_code(), StatusCode::NOT_FOUND);
+            for path in ["/.env", "/..%2Fetc"] {
+                let res = server.get(&format!("{prefix}{path}")).await;
+                assert_eq!(res.status_code(), StatusCode::NOT_FOUND, "{prefix}{path}");
+                assert_eq!(res.text(), missing.text(), "{prefix}{path}");
+                assert_eq!(
+                    res.headers().get(header::CONTENT_TYPE),
+                    missing.headers().get(header::CONTENT_TYPE),
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "tarpit")]
+    #[tokio::test]
+    async fn rejection_delay() {
+        let delay = Duration::from_millis(50)..Duration::from_millis(80);
+        let app = Router::new().route("/{*path}", get(handler)).layer(
+            SafePathPolicyLayer::new(SafePathConfig::new().deny_hidden(true))
+                .rejection_delay(delay.clone()),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let started = std::time::Instant::now();
+        let res = server.get("/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert!(started.elapsed() >= delay.start);
+
+        let res = server.get("/a").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "tarpit")]
+    #[test]
+    fn random_delays() {
+        let range = Duration::from_secs(1)..Duration::from_secs(3);
+        for _ in 0..100 {
+            assert!(range.contains(&random_delay(&range)));
+        }
+        let second = Duration::from_secs(1);
+        assert_eq!(random_delay(&(second..second)), second);
+    }
+
+    #[tokio::test]
+    async fn from_policy() {
+        let app =
+            Router::new()
+                .route("/{*path}", get(handler))
+                .layer(SafePathPolicyLayer::from_policy(
+                    crate::policy::DenyDotfiles,
+                ));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/a/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -1,96 +1,661 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 #![doc = include_str!("../README.md")]
 #![allow(forbidden_lint_groups)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+#[cfg(all(feature = "std", not(any(feature = "axum07", feature = "axum08"))))]
+compile_error!("the `std` feature needs an axum version: enable `axum08` or `axum07`");
+
+// The rest of the crate refers to the enabled version as `axum`. If both are
+// enabled, e.g. with `--all-features`, the current one wins.
+#[cfg(all(feature = "axum07", not(feature = "axum08")))]
+extern crate axum07 as axum;
+
+#[cfg(feature = "std")]
 use std::{
     error::Error,
-    fmt,
+    fmt, io, mem,
     path::{self, Component, PathBuf},
     str::FromStr,
+    sync::Arc,
 };
 
+#[cfg(feature = "std")]
 use axum::{
-    extract::{FromRequestParts, Path, rejection::PathRejection},
-    http::{StatusCode, request::Parts},
+    extract::{FromRequestParts, Path, path::ErrorKind, rejection::PathRejection},
+    http::{HeaderName, StatusCode, request::Parts},
     response::{IntoResponse, Response},
 };
 
+#[cfg(feature = "arc-swap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arc-swap")))]
+pub use crate::config::ReloadableConfig;
+#[cfg(feature = "unicode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "unicode")))]
+pub use crate::config::UnicodeForm;
+#[cfg(any(feature = "zip", feature = "tar"))]
+#[cfg_attr(docsrs, doc(cfg(any(feature = "zip", feature = "tar"))))]
+pub use crate::extract::{Archive, ExtractError, ExtractLimits, extract_into};
+#[cfg(feature = "fs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+pub use crate::fs::{
+    CanonicalizeError, canonicalize_within, copy_in, remove_in, rename_in, secure_open,
+    temp_file_in,
+};
+#[cfg(all(feature = "fs", feature = "json"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "fs", feature = "json"))))]
+pub use crate::listing::{Listing, ListingEntry, list_dir};
+#[cfg(feature = "multipart")]
+#[cfg_attr(docsrs, doc(cfg(feature = "multipart")))]
+pub use crate::multipart::{SafeField, SafeMultipart, SafeMultipartError};
+#[cfg(feature = "fs")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+pub use crate::serve::{SafeRouterExt, SafeServeDir, serve_file};
+#[cfg(feature = "std")]
+pub use crate::{
+    archive::{
+        TarEntry, TarEntryKind, TarEntryReport, TarFinding, validate_archive_entry,
+        validate_tar_entry,
+    },
+    canonical::{CanonicalPath, CanonicalPathLayer},
+    config::{SafePathConfig, TrailingSlash},
+    custom::SafePathWith,
+    disposition::{Disposition, content_disposition},
+    glob::{SafeGlob, SafeGlobConfig},
+    header::{Destination, PathHeader, SafeHeaderPath, XFilePath},
+    hook::{OffendingValue, RejectionEvent},
+    layer::{SafePathPolicy, SafePathPolicyLayer},
+    mount::{MountTable, MountedPath},
+    normalized::NormalizedSafePath,
+    object_key::{ObjectKeyConfig, SafeObjectKey},
+    outcome::SafePathOutcome,
+    params::{SafePathLayer, SafePathParams},
+    parts::{SafeDirPath, SafePathParts},
+    query::{QueryGuard, QueryGuardLayer},
+    raw::RawSafePath,
+    redirect::SafeRedirect,
+    response::RejectionFormat,
+    rooted::{PathRoot, RootedPath, ensure_within, join_rooted},
+    sanitize::{SanitizedPath, sanitize},
+    tenant::{TenantId, TenantSafePath, TenantSafePathRejection},
+    uri::{NestedSafePath, RequireSafePath, SafeUri},
+};
+
+#[cfg(feature = "std")]
+mod archive;
+#[cfg(feature = "std")]
+mod canonical;
+#[cfg(feature = "cap-std")]
+mod cap;
+pub mod component;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(feature = "std")]
+mod custom;
+#[cfg(feature = "std")]
+mod disposition;
+#[cfg(any(feature = "zip", feature = "tar"))]
+mod extract;
+#[cfg(feature = "fs")]
+mod fs;
+#[cfg(feature = "std")]
+mod glob;
+#[cfg(feature = "std")]
+mod header;
+#[cfg(feature = "std")]
+mod hook;
+#[cfg(feature = "std")]
+mod layer;
+#[cfg(all(feature = "fs", feature = "json"))]
+mod listing;
+#[cfg(feature = "std")]
+mod mount;
+#[cfg(feature = "multipart")]
+mod multipart;
+#[cfg(feature = "std")]
+mod normalized;
+#[cfg(feature = "std")]
+mod object_key;
+#[cfg(all(feature = "openat2", target_os = "linux"))]
+mod openat2;
+#[cfg(feature = "std")]
+mod outcome;
+#[cfg(feature = "std")]
+mod params;
+#[cfg(feature = "std")]
+mod parts;
+#[cfg(feature = "std")]
+pub mod policy;
+#[cfg(feature = "std")]
+mod query;
+#[cfg(feature = "std")]
+mod raw;
+#[cfg(feature = "std")]
+mod redirect;
+#[cfg(feature = "std")]
+mod response;
+#[cfg(feature = "std")]
+mod rooted;
+#[cfg(feature = "std")]
+mod sanitize;
+#[cfg(feature = "fs")]
+mod save;
+#[cfg(feature = "std")]
+mod scan;
+#[cfg(feature = "fs")]
+mod serve;
+#[cfg(feature = "std")]
+mod tenant;
+#[cfg(feature = "std")]
+mod trace;
+#[cfg(feature = "std")]
+mod uri;
+
+#[cfg(feature = "std")]
 const REJECTION_MESSAGE: &str = "Invalid path: possible traversal attack detected";
 
 /// A traversal-safe path extractor for Axum.
 ///
 /// This extractor wraps `axum::extract::Path` and rejects requests
-/// containing path components like `..`, `/`, or `C:`, preventing
-/// directory traversal attacks.
+/// containing path components like `..`, `/`, or `C:`, or NUL bytes,
+/// preventing directory traversal attacks. Additional checks can be enabled
+/// with [`SafePathConfig`].
+///
+/// Percent-encoding lets clients send bytes that aren't valid UTF-8, like
+/// `%FF`. Such paths are rejected with [`SafePathRejection::InvalidUtf8`]
+/// rather than kept as raw bytes, so the path is the same text on every
+/// platform and can be logged or stored as a string.
+#[cfg(feature = "std")]
 #[derive(Debug, Default, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SafePath(pub PathBuf);
 
+#[cfg(feature = "std")]
+impl SafePath {
+    /// Checks if the path ends with a slash, which usually means a directory
+    /// was requested
+    #[must_use]
+    pub fn has_trailing_slash(&self) -> bool {
+        config::has_trailing_slash(&self.0)
+    }
+}
+
+#[cfg(feature = "std")]
 impl AsRef<path::Path> for SafePath {
     fn as_ref(&self) -> &path::Path {
         self.0.as_ref()
     }
 }
 
+#[cfg(feature = "std")]
 impl FromStr for SafePath {
     type Err = SafePathRejection;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if is_traversal_attack(s) {
-            Err(SafePathRejection::TraversalAttack)
-        } else {
-            Ok(Self(PathBuf::from(s)))
-        }
+        SafePathConfig::default().validate(s)
     }
 }
 
 /// Rejection type for [`SafePath`].
-#[derive(Debug)]
+///
+/// It's cheap to clone and can be compared, e.g. in tests:
+///
+/// ```
+/// use axum_safe_path::{SafePathConfig, SafePathRejection};
+///
+/// assert_eq!(
+///     SafePathConfig::new().max_depth(2).check("a/b/c"),
+///     Err(SafePathRejection::TooDeep { max: 2 })
+/// );
+/// ```
+///
+/// To map it into the error type of the app, implement [`From`] for it and
+/// use `WithRejection` from `axum-extra`:
+///
+/// ```
+/// use axum::{
+///     Router,
+///     http::StatusCode,
+///     response::{IntoResponse, Response},
+///     routing::get,
+/// };
+/// use axum_extra::extract::WithRejection;
+/// use axum_safe_path::{SafePath, SafePathRejection};
+///
+/// enum AppError {
+///     BadPath(SafePathRejection),
+/// }
+///
+/// impl From<SafePathRejection> for AppError {
+///     fn from(rejection: SafePathRejection) -> Self {
+///         Self::BadPath(rejection)
+///     }
+/// }
+///
+/// impl IntoResponse for AppError {
+///     fn into_response(self) -> Response {
+///         match self {
+///             Self::BadPath(rejection) => (StatusCode::NOT_FOUND, rejection.body_text()),
+///         }
+///         .into_response()
+///     }
+/// }
+///
+/// async fn handler(WithRejection(SafePath(path), _): WithRejection<SafePath, AppError>) -> String {
+///     path.display().to_string()
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(handler));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum SafePathRejection {
-    /// Possible traversal attack detected
-    TraversalAttack,
+    /// Possible traversal attack detected, see [`Traversal`] for the details
+    TraversalAttack(Traversal),
+    /// The path contains a NUL byte
+    NulByte,
+    /// The path has more components than allowed
+    TooDeep {
+        /// The maximum allowed number of components
+        max: usize,
+    },
+    /// The path is longer than allowed
+    TooLong {
+        /// The maximum allowed length in bytes
+        max: usize,
+    },
+    /// A path component is longer than allowed
+    ComponentTooLong {
+        /// The maximum allowed component length in bytes
+        max: usize,
+    },
+    /// The glob pattern has more `**` wildcards than allowed, see [`SafeGlob`]
+    TooManyGlobstars {
+        /// The maximum allowed number of `**` wildcards
+        max: usize,
+    },
+    /// The path has no normal components
+    Empty,
+    /// The path has a `.` component
+    CurrentDir,
+    /// The path has a trailing slash while it's forbidden
+    TrailingSlash,
+    /// The path has no trailing slash while it's required
+    MissingTrailingSlash,
+    /// The path has a hidden component
+    Hidden,
+    /// The path has a component named after a sensitive file
+    Sensitive,
+    /// The path has a component with a denied name
+    DeniedName,
+    /// A path component has characters outside of the portable set
+    NonPortable,
+    /// A path component has a forbidden character
+    InvalidCharacter,
+    /// A path component consists of characters looking like dots
+    LookalikeDots,
+    /// A path component ends with a dot or a space
+    TrailingDotOrSpace,
+    /// A path component is a name reserved on Windows
+    ReservedName,
+    /// The path starts with a Windows UNC prefix like `\\server\share`
+    UncPath,
+    /// The path starts with a Windows verbatim prefix like `\\?\C:\`
+    VerbatimPath,
+    /// The path starts with the Windows device namespace prefix `\\.\`
+    DevicePath,
+    /// A path component refers to an NTFS alternate data stream
+    AlternateDataStream,
+    /// The path has percent-escapes left after decoding
+    PercentEncoded,
+    /// The raw path parameter has an encoded slash or backslash
+    EncodedSeparator,
+    /// The raw path parameter has an overlong UTF-8 sequence
+    OverlongUtf8,
+    /// The decoded path isn't valid UTF-8
+    InvalidUtf8,
+    /// The request path isn't in the canonical form, see [`CanonicalPathLayer`]
+    NotCanonical,
+    /// The path doesn't point to a file with an extension
+    MissingExtension,
+    /// The file extension isn't in the allowlist
+    ExtensionNotAllowed,
+    /// The file extension is in the denylist
+    ExtensionDenied,
+    /// The path doesn't match the configured patterns
+    PatternMismatch,
+    /// The path is denied by a [`policy`]
+    Denied,
+    /// The redirect target isn't a same-origin path, see [`SafeRedirect`]
+    UnsafeRedirect,
+    /// The first path segment isn't in the [`MountTable`]
+    UnknownMount,
+    /// The header carrying the path is missing, see [`SafeHeaderPath`]
+    MissingHeader {
+        /// The name of the header
+        name: HeaderName,
+    },
     /// The underlying [`Path`] extractor failed
-    PathExtraction(PathRejection),
+    ///
+    /// Compared by the status and the message, as [`PathRejection`] isn't
+    /// comparable itself.
+    PathExtraction(Arc<PathRejection>),
+    /// A rejection answered with the message set by
+    /// [`SafePathConfig::rejection_message`]
+    ///
+    /// The code and the status are the ones of the actual rejection, see
+    /// [`reason`](Self::reason).
+    WithMessage {
+        /// The actual rejection
+        rejection: Arc<Self>,
+        /// The message of the response
+        message: Arc<str>,
+    },
+}
+
+#[cfg(feature = "std")]
+impl PartialEq for SafePathRejection {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::TraversalAttack(a), Self::TraversalAttack(b)) => a == b,
+            (Self::TooDeep { max: a }, Self::TooDeep { max: b })
+            | (Self::TooLong { max: a }, Self::TooLong { max: b })
+            | (Self::ComponentTooLong { max: a }, Self::ComponentTooLong { max: b })
+            | (Self::TooManyGlobstars { max: a }, Self::TooManyGlobstars { max: b }) => a == b,
+            (Self::MissingHeader { name: a }, Self::MissingHeader { name: b }) => a == b,
+            (Self::PathExtraction(a), Self::PathExtraction(b)) => {
+                a.status() == b.status() && a.body_text() == b.body_text()
+            }
+            (
+                Self::WithMessage {
+                    rejection: a,
+                    message: a_message,
+                },
+                Self::WithMessage {
+                    rejection: b,
+                    message: b_message,
+                },
+            ) => a == b && a_message == b_message,
+            _ => mem::discriminant(self) == mem::discriminant(other),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Eq for SafePathRejection {}
+
+/// Invalid UTF-8 in the path parameter becomes
+/// [`InvalidUtf8`](Self::InvalidUtf8), the other errors
+/// [`PathExtraction`](Self::PathExtraction)
+#[cfg(feature = "std")]
+impl From<PathRejection> for SafePathRejection {
+    fn from(rejection: PathRejection) -> Self {
+        if let PathRejection::FailedToDeserializePathParams(err) = &rejection
+            && matches!(err.kind(), ErrorKind::InvalidUtf8InPathParam { .. })
+        {
+            return Self::InvalidUtf8;
+        }
+        Self::PathExtraction(Arc::new(rejection))
+    }
+}
+
+/// Allows `WithRejection<SafePath, Response>` and `?` in handlers returning
+/// `Result<_, Response>`
+#[cfg(feature = "std")]
+impl From<SafePathRejection> for Response {
+    fn from(rejection: SafePathRejection) -> Self {
+        rejection.into_response()
+    }
 }
 
+/// An [`InvalidInput`](io::ErrorKind::InvalidInput) error wrapping the
+/// rejection, for code serving files with `io::Result`
+#[cfg(feature = "std")]
+impl From<SafePathRejection> for io::Error {
+    fn from(rejection: SafePathRejection) -> Self {
+        Self::new(io::ErrorKind::InvalidInput, rejection)
+    }
+}
+
+#[cfg(feature = "std")]
 impl fmt::Display for SafePathRejection {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::TraversalAttack => f.write_str(REJECTION_MESSAGE),
+            Self::TraversalAttack(_)
+            | Self::LookalikeDots
+            | Self::UncPath
+            | Self::VerbatimPath
+            | Self::DevicePath => f.write_str(REJECTION_MESSAGE),
+            Self::NulByte => f.write_str("Invalid path: NUL bytes are not allowed"),
+            Self::TrailingDotOrSpace => {
+                f.write_str("Invalid path: names can't end with a dot or a space")
+            }
+            Self::TooDeep { max } => write!(f, "Invalid path: too many components (max {max})"),
+            Self::TooLong { max } => write!(f, "Invalid path: too long (max {max} bytes)"),
+            Self::Empty => f.write_str("Invalid path: empty"),
+            Self::CurrentDir => f.write_str("Invalid path: `.` components are not allowed"),
+            Self::TrailingSlash => f.write_str("Invalid path: trailing slash is not allowed"),
+            Self::MissingTrailingSlash => f.write_str("Invalid path: trailing slash is required"),
+            Self::Hidden => f.write_str("Invalid path: hidden files are not allowed"),
+            Self::Sensitive => f.write_str("Invalid path: sensitive files are not allowed"),
+            Self::DeniedName | Self::ReservedName => f.write_str("Invalid path: forbidden name"),
+            Self::NonPortable | Self::InvalidCharacter => {
+                f.write_str("Invalid path: unsupported characters")
+            }
+            Self::AlternateDataStream => {
+                f.write_str("Invalid path: alternate data streams are not allowed")
+            }
+            Self::PercentEncoded => f.write_str("Invalid path: percent-encoding is not allowed"),
+            Self::EncodedSeparator => {
+                f.write_str("Invalid path: encoded separators are not allowed")
+            }
+            Self::OverlongUtf8 => f.write_str("Invalid path: overlong UTF-8 sequence"),
+            Self::InvalidUtf8 => f.write_str("Invalid path: not valid UTF-8"),
+            Self::NotCanonical => f.write_str("Invalid path: not in canonical form"),
+            Self::PatternMismatch => f.write_str("Invalid path: unexpected format"),
+            Self::Denied => f.write_str("Invalid path: access denied"),
+            Self::UnsafeRedirect => f.write_str("Invalid redirect target"),
+            Self::UnknownMount => f.write_str("Invalid path: unknown mount"),
+            Self::MissingHeader { name } => write!(f, "Missing `{name}` header"),
+            Self::MissingExtension => f.write_str("Invalid path: file extension is required"),
+            Self::ExtensionNotAllowed | Self::ExtensionDenied => {
+                f.write_str("Invalid path: file type is not allowed")
+            }
+            Self::ComponentTooLong { max } => {
+                write!(f, "Invalid path: component too long (max {max} bytes)")
+            }
+            Self::TooManyGlobstars { max } => {
+                write!(f, "Invalid pattern: too many `**` wildcards (max {max})")
+            }
             Self::PathExtraction(err) => write!(f, "{err}"),
+            Self::WithMessage { message, .. } => f.write_str(message),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl SafePathRejection {
+    /// A machine-readable code of the rejection, like `path_traversal`
+    ///
+    /// It's as detailed as the message, so the rejections the message doesn't
+    /// tell apart share the code.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TraversalAttack(_)
+            | Self::LookalikeDots
+            | Self::UncPath
+            | Self::VerbatimPath
+            | Self::DevicePath => "path_traversal",
+            Self::NulByte => "nul_byte",
+            Self::TrailingDotOrSpace => "trailing_dot_or_space",
+            Self::TooDeep { .. } => "too_deep",
+            Self::TooLong { .. } => "too_long",
+            Self::ComponentTooLong { .. } => "component_too_long",
+            Self::TooManyGlobstars { .. } => "too_many_globstars",
+            Self::Empty => "empty",
+            Self::CurrentDir => "current_dir",
+            Self::TrailingSlash => "trailing_slash",
+            Self::MissingTrailingSlash => "missing_trailing_slash",
+            Self::Hidden => "hidden",
+            Self::Sensitive => "sensitive",
+            Self::DeniedName | Self::ReservedName => "forbidden_name",
+            Self::NonPortable | Self::InvalidCharacter => "unsupported_characters",
+            Self::AlternateDataStream => "alternate_data_stream",
+            Self::PercentEncoded => "percent_encoded",
+            Self::EncodedSeparator => "encoded_separator",
+            Self::OverlongUtf8 => "overlong_utf8",
+            Self::InvalidUtf8 => "invalid_utf8",
+            Self::NotCanonical => "not_canonical",
+            Self::PatternMismatch => "pattern_mismatch",
+            Self::Denied => "denied",
+            Self::UnsafeRedirect => "unsafe_redirect",
+            Self::UnknownMount => "unknown_mount",
+            Self::MissingHeader { .. } => "missing_header",
+            Self::MissingExtension => "missing_extension",
+            Self::ExtensionNotAllowed | Self::ExtensionDenied => "file_type_not_allowed",
+            Self::PathExtraction(_) => "path_extraction",
+            Self::WithMessage { rejection, .. } => rejection.code(),
+        }
+    }
+
+    /// The actual rejection, unwrapping [`WithMessage`](Self::WithMessage)
+    ///
+    /// ```
+    /// use axum_safe_path::SafePathRejection;
+    ///
+    /// let rejection = SafePathRejection::WithMessage {
+    ///     rejection: SafePathRejection::Hidden.into(),
+    ///     message: "Not found".into(),
+    /// };
+    /// assert_eq!(rejection.reason(), &SafePathRejection::Hidden);
+    /// assert_eq!(rejection.body_text(), "Not found");
+    /// ```
+    #[must_use]
+    pub fn reason(&self) -> &Self {
+        match self {
+            Self::WithMessage { rejection, .. } => rejection.reason(),
+            rejection => rejection,
+        }
+    }
+
+    /// The status of the response, like [`PathRejection::status`]
+    ///
+    /// It's `400 Bad Request` unless the path couldn't be extracted. A
+    /// [`SafePathPolicyLayer`] may still change it in the response.
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::PathExtraction(inner) => inner.status(),
+            Self::WithMessage { rejection, .. } => rejection.status(),
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// The body of the response, like [`PathRejection::body_text`]
+    ///
+    /// ```
+    /// use axum::http::StatusCode;
+    /// use axum_safe_path::SafePathRejection;
+    ///
+    /// let rejection = SafePathRejection::Hidden;
+    /// assert_eq!(rejection.status(), StatusCode::BAD_REQUEST);
+    /// assert_eq!(rejection.body_text(), "Invalid path: hidden files are not allowed");
+    /// ```
+    #[must_use]
+    pub fn body_text(&self) -> String {
+        match self {
+            Self::PathExtraction(inner) => inner.body_text(),
+            rejection => rejection.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 impl Error for SafePathRejection {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
-            Self::TraversalAttack => None,
-            Self::PathExtraction(err) => Some(err),
+            Self::PathExtraction(err) => Some(err.as_ref()),
+            Self::WithMessage { rejection, .. } => Some(rejection.as_ref()),
+            _ => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl IntoResponse for SafePathRejection {
     fn into_response(self) -> Response {
         match self {
-            Self::TraversalAttack => (StatusCode::BAD_REQUEST, REJECTION_MESSAGE).into_response(),
-            Self::PathExtraction(inner) => inner.into_response(),
+            Self::PathExtraction(inner) => Arc::try_unwrap(inner).map_or_else(
+                |inner| (inner.status(), inner.body_text()).into_response(),
+                IntoResponse::into_response,
+            ),
+            rejection => {
+                let mut res = (rejection.status(), rejection.body_text()).into_response();
+                res.extensions_mut()
+                    .insert(response::Rejected::new(rejection));
+                res
+            }
         }
     }
 }
 
+/// What made a path a traversal attack, see
+/// [`SafePathRejection::TraversalAttack`]
+///
+/// It's meant for logs and tests, the response doesn't reveal it:
+///
+/// ```
+/// use axum_safe_path::{SafePathConfig, SafePathRejection, Traversal};
+///
+/// assert!(matches!(
+///     SafePathConfig::new().check("docs/../../etc/passwd"),
+///     Err(SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 1 }))
+/// ));
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Traversal {
+    /// A `..` component, at the index among the components of the path
+    ParentDir {
+        /// The index of the component, starting from zero
+        index: usize,
+    },
+    /// The path is absolute
+    RootDir,
+    /// The path starts with a Windows prefix, like `C:`
+    Prefix,
+    /// A name expected to be a single component has a separator
+    Separator,
+    /// The path resolves to a location outside of its base directory
+    EscapesBase,
+}
+
 /// Checks if a path contains traversal-related components such as `..`, a root
 /// directory, or a drive prefix.
+#[cfg(all(test, feature = "std"))]
 fn is_traversal_attack(path: impl AsRef<path::Path>) -> bool {
-    path.as_ref().components().any(|component| {
-        matches!(
-            component,
-            Component::ParentDir | Component::Prefix(_) | Component::RootDir
-        )
-    })
+    find_traversal(path).is_some()
 }
 
+/// Finds the first traversal-related component of the path
+#[cfg(feature = "std")]
+fn find_traversal(path: impl AsRef<path::Path>) -> Option<Traversal> {
+    path.as_ref()
+        .components()
+        .enumerate()
+        .find_map(|(index, component)| match component {
+            Component::ParentDir => Some(Traversal::ParentDir { index }),
+            Component::RootDir => Some(Traversal::RootDir),
+            Component::Prefix(_) => Some(Traversal::Prefix),
+            Component::CurDir | Component::Normal(_) => None,
+        })
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
 impl<S> FromRequestParts<S> for SafePath
 where
     S: Send + Sync,
@@ -98,13 +663,30 @@ where
     type Rejection = SafePathRejection;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let Path(path) = Path::from_request_parts(parts, state)
+        if let Some(result) = outcome::Cached::get(&parts.extensions) {
+            return result;
+        }
+        let result = Self::extract(parts, state).await;
+        outcome::Cached::insert(&mut parts.extensions, result.clone());
+        result
+    }
+}
+
+#[cfg(feature = "std")]
+impl SafePath {
+    /// Parses and validates the path parameter
+    async fn extract<S: Send + Sync>(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, SafePathRejection> {
+        let Path(path) = Path::<PathBuf>::from_request_parts(parts, state)
             .await
-            .map_err(SafePathRejection::PathExtraction)?;
+            .map_err(SafePathRejection::from)?;
+        let raw = raw::capture(&parts.uri, &path);
 
-        (!is_traversal_attack(&path))
-            .then_some(Self(path))
-            .ok_or(SafePathRejection::TraversalAttack)
+        SafePathConfig::validate_current(&mut parts.extensions, raw, |config| {
+            config.validate_encoded(path, Some(raw))
+        })
     }
 }
 
@@ -116,15 +698,13 @@ impl<'de> serde::Deserialize<'de> for SafePath {
     {
         let path = PathBuf::deserialize(deserializer)?;
 
-        if is_traversal_attack(&path) {
-            Err(serde::de::Error::custom(REJECTION_MESSAGE))
-        } else {
-            Ok(Self(path))
-        }
+        SafePathConfig::default()
+            .validate(path)
+            .map_err(serde::de::Error::custom)
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod validation_tests {
     use super::*;
 
@@ -147,6 +727,36 @@ mod validation_tests {
         assert!(is_traversal_attack("foo/bar/.."));
     }
 
+    #[test]
+    fn traversal_reasons() {
+        assert_eq!(
+            find_traversal("a/b/../c"),
+            Some(Traversal::ParentDir { index: 2 })
+        );
+        assert_eq!(
+            find_traversal("../a"),
+            Some(Traversal::ParentDir { index: 0 })
+        );
+        assert_eq!(find_traversal("/etc/passwd"), Some(Traversal::RootDir));
+        assert_eq!(find_traversal("a/b"), None);
+    }
+
+    #[test]
+    fn rejection_equality() {
+        let traversal = SafePathRejection::TraversalAttack(Traversal::RootDir);
+        assert_eq!(traversal.clone(), traversal);
+        assert_ne!(
+            traversal,
+            SafePathRejection::TraversalAttack(Traversal::Prefix)
+        );
+        assert_ne!(
+            SafePathRejection::TooDeep { max: 2 },
+            SafePathRejection::TooLong { max: 2 }
+        );
+        assert_eq!(SafePathRejection::Hidden, SafePathRejection::Hidden);
+        assert_ne!(SafePathRejection::Hidden, SafePathRejection::Sensitive);
+    }
+
     #[test]
     fn invalid_absolute_paths() {
         assert!(is_traversal_attack("/etc/passwd"));
@@ -184,7 +794,7 @@ mod serde_tests {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "axum08"))]
 #[allow(clippy::unwrap_used)]
 mod path_integration_tests {
     use axum::{Router, routing::get};
@@ -215,9 +825,182 @@ mod path_integration_tests {
         assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
         assert_eq!(res.text(), REJECTION_MESSAGE);
     }
+
+    #[tokio::test]
+    async fn with_rejection() {
+        use axum_extra::extract::WithRejection;
+
+        struct AppError(SafePathRejection);
+
+        impl From<SafePathRejection> for AppError {
+            fn from(rejection: SafePathRejection) -> Self {
+                Self(rejection)
+            }
+        }
+
+        impl IntoResponse for AppError {
+            fn into_response(self) -> Response {
+                (StatusCode::UNPROCESSABLE_ENTITY, self.0.code()).into_response()
+            }
+        }
+
+        async fn app_handler(
+            WithRejection(SafePath(path), _): WithRejection<SafePath, AppError>,
+        ) -> String {
+            path.display().to_string()
+        }
+
+        async fn response_handler(
+            WithRejection(SafePath(path), _): WithRejection<SafePath, Response>,
+        ) -> String {
+            path.display().to_string()
+        }
+
+        let app = Router::new()
+            .route("/app/{*path}", get(app_handler))
+            .route("/response/{*path}", get(response_handler));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/app/a/..%2F..%2Fb").await;
+        assert_eq!(res.status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(res.text(), "path_traversal");
+
+        let res = server.get("/response/a/..%2F..%2Fb").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), REJECTION_MESSAGE);
+
+        let res = server.get("/app/a/b").await;
+        assert_eq!(res.text(), "a/b");
+    }
+
+    #[test]
+    fn into_io_error() {
+        let err = io::Error::from(SafePathRejection::Hidden);
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(err.to_string(), SafePathRejection::Hidden.to_string());
+    }
+
+    #[tokio::test]
+    async fn rejected_nul_byte() {
+        let app = Router::new().route("/path/{*path}", get(handler));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/path/safe.txt%00.jpg").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Invalid path: NUL bytes are not allowed");
+    }
+
+    #[tokio::test]
+    async fn rejected_invalid_utf8() {
+        let app = Router::new()
+            .route("/path/{*path}", get(handler))
+            .route("/uri/{*path}", get(|uri: SafeUri| async move { uri.0 }));
+        let server = TestServer::new(app).unwrap();
+
+        for uri in ["/path/a%FFb.txt", "/path/%C3", "/uri/a%FFb.txt"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+            assert_eq!(res.text(), "Invalid path: not valid UTF-8", "{uri}");
+        }
+
+        let res = server.get("/path/%C3%A9t%C3%A9.txt").await;
+        assert_eq!(res.text(), "Path: été.txt");
+    }
+
+    #[tokio::test]
+    async fn rejected_double_encoding() {
+        let app = Router::new()
+            .route("/path/{*path}", get(handler))
+            .layer(axum::Extension(
+                SafePathConfig::new().deny_percent_encoded(true),
+            ));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/path/%252e%252e/secret").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Invalid path: percent-encoding is not allowed");
+    }
+
+    #[tokio::test]
+    async fn rejected_encoded_separator() {
+        let app = Router::new()
+            .route("/path/{*path}", get(handler))
+            .layer(axum::Extension(
+                SafePathConfig::new().deny_encoded_separators(true),
+            ));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/path/foo/bar.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server.get("/path/foo%2Fbar.txt").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            res.text(),
+            "Invalid path: encoded separators are not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn config_from_extension() {
+        let app = Router::new()
+            .route("/path/{*path}", get(handler))
+            .layer(axum::Extension(SafePathConfig::new().max_depth(1)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/path/foo.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server.get("/path/foo/bar.txt").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Invalid path: too many components (max 1)");
+    }
+
+    #[tokio::test]
+    async fn cached_extraction() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use axum::{
+            extract::Request,
+            middleware::{self, Next},
+        };
+
+        async fn log_path(req: Request, next: Next) -> Response {
+            let (mut parts, body) = req.into_parts();
+            let logged = SafePath::from_request_parts(&mut parts, &()).await.is_ok();
+            let mut res = next.run(Request::from_parts(parts, body)).await;
+            res.headers_mut()
+                .insert("x-logged", logged.to_string().parse().unwrap());
+            res
+        }
+
+        let checks = Arc::new(AtomicUsize::new(0));
+        let counter = checks.clone();
+        let config = SafePathConfig::new()
+            .deny_hidden(true)
+            .policy(move |_: &path::Path| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            });
+        let app = Router::new()
+            .route("/path/{*path}", get(handler))
+            .route_layer(middleware::from_fn(log_path))
+            .layer(axum::Extension(config));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/path/foo.txt").await;
+        assert_eq!(res.text(), "Path: foo.txt");
+        assert_eq!(res.header("x-logged"), "true");
+        assert_eq!(checks.load(Ordering::Relaxed), 1);
+
+        let res = server.get("/path/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.header("x-logged"), "false");
+        assert_eq!(checks.load(Ordering::Relaxed), 1);
+    }
 }
 
-#[cfg(all(test, feature = "json"))]
+#[cfg(all(test, feature = "axum08", feature = "json"))]
 #[allow(clippy::unwrap_used, forbidden_lint_groups)]
 mod json_integration_tests {
     use axum::{Json, Router, routing::post};
@@ -264,7 +1047,7 @@ mod json_integration_tests {
     }
 }
 
-#[cfg(all(test, feature = "form"))]
+#[cfg(all(test, feature = "axum08", feature = "form"))]
 #[allow(clippy::unwrap_used, forbidden_lint_groups)]
 mod form_integration_tests {
     use axum::{Form, Router, routing::post};
@@ -318,3 +1101,46 @@ mod form_integration_tests {
         assert!(res.text().contains(REJECTION_MESSAGE));
     }
 }
+
+#[cfg(all(test, feature = "axum07", not(feature = "axum08")))]
+#[allow(clippy::unwrap_used)]
+mod axum07_tests {
+    use axum::{Router, routing::get};
+    use axum_test07::TestServer;
+
+    use super::*;
+
+    async fn handler(SafePath(path): SafePath) -> String {
+        format!("Path: {}", path.display())
+    }
+
+    #[tokio::test]
+    async fn safe_path() {
+        let app = Router::new().route("/path/*path", get(handler));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/path/foo/bar.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "Path: foo/bar.txt");
+
+        let res = server.get("/path//etc/passwd").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), REJECTION_MESSAGE);
+    }
+
+    #[tokio::test]
+    async fn policy_layer() {
+        let app = Router::new()
+            .route("/*path", get(handler))
+            .layer(SafePathPolicyLayer::new(
+                SafePathConfig::new().deny_hidden(true),
+            ));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/foo/bar.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server.get("/foo/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,204 @@
+//! JSON listings of directories.
+
+use std::{io, path::Path};
+
+use axum::{Json, http::StatusCode};
+use serde::Serialize;
+
+use crate::{SafeDirPath, SafePath, secure_open};
+
+/// The directory listing returned by [`list_dir`]
+#[derive(Debug, Clone, Serialize)]
+pub struct Listing {
+    /// The path of the directory relative to the root
+    pub path: String,
+    /// The entries sorted by name
+    pub entries: Vec<ListingEntry>,
+}
+
+/// An entry of [`Listing`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ListingEntry {
+    /// The file name
+    pub name: String,
+    /// The path relative to the root
+    pub path: String,
+    /// Whether it's a directory
+    pub is_dir: bool,
+    /// The size of a file in bytes
+    pub size: Option<u64>,
+}
+
+/// Lists the directory at the path relative to the root.
+///
+/// Paths in the listing are relative to the root with `/` separators, so
+/// server paths never leak. Entries with names failing the config of the
+/// path, like hidden files with
+/// [`deny_hidden`](crate::SafePathConfig::deny_hidden), symlinks and names
+/// which aren't valid UTF-8 are skipped. Only the name rules apply to
+/// entries, so subdirectories are listed even if the config requires
+/// extensions or trailing slashes:
+///
+/// ```
+/// use axum::{Json, Router, http::StatusCode, routing::get};
+/// use axum_safe_path::{Listing, SafeDirPath, list_dir};
+///
+/// async fn browse(dir: SafeDirPath) -> Result<Json<Listing>, StatusCode> {
+///     list_dir("/var/www", &dir).await
+/// }
+///
+/// let app: Router = Router::new().route("/browse/{*path}", get(browse));
+/// ```
+///
+/// # Errors
+///
+/// Returns `404 Not Found` if the path isn't a directory or goes through a
+/// symlink, and `500 Internal Server Error` on other IO errors.
+pub async fn list_dir(
+    root: impl AsRef<Path>,
+    dir: &SafeDirPath,
+) -> Result<Json<Listing>, StatusCode> {
+    read_listing(root.as_ref(), dir)
+        .await
+        .map(Json)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound
+            | io::ErrorKind::NotADirectory
+            | io::ErrorKind::PermissionDenied => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        })
+}
+
+/// Reads the entries of the directory
+async fn read_listing(root: &Path, dir: &SafeDirPath) -> io::Result<Listing> {
+    let safe_path = SafePath(dir.path.clone());
+    // Checks for symlinks on the way
+    if !secure_open(root, &safe_path)
+        .await?
+        .metadata()
+        .await?
+        .is_dir()
+    {
+        return Err(io::ErrorKind::NotADirectory.into());
+    }
+    let path = dir.config.normalized_relative(&dir.path);
+
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(root.join(&path)).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let file_type = entry.file_type().await?;
+        if file_type.is_symlink() || dir.config.check_name(name.as_ref()).is_err() {
+            continue;
+        }
+        let relative = path.join(&name);
+        let size = if file_type.is_file() {
+            Some(entry.metadata().await?.len())
+        } else {
+            None
+        };
+        entries.push(ListingEntry {
+            path: dir
+                .config
+                .normalized(&relative)
+                .to_string_lossy()
+                .into_owned(),
+            name,
+            is_dir: file_type.is_dir(),
+            size,
+        });
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Listing {
+        path: path.to_string_lossy().into_owned(),
+        entries,
+    })
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, routing::get};
+    use axum_test::TestServer;
+    use serde_json::json;
+
+    use super::*;
+    use crate::SafePathConfig;
+
+    #[tokio::test]
+    async fn listing() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        std::fs::create_dir_all(root.join("docs/guide")).unwrap();
+        std::fs::write(root.join("docs/b.md"), "bb").unwrap();
+        std::fs::write(root.join("docs/.secret"), "s").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("/etc", root.join("docs/etc")).unwrap();
+
+        let app = Router::new()
+            .route(
+                "/{*path}",
+                get(move |dir: SafeDirPath| async move { list_dir(&root, &dir).await }),
+            )
+            .layer(Extension(SafePathConfig::new().deny_hidden(true)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/docs/./").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(
+            res.json::<serde_json::Value>(),
+            json!({
+                "path": "docs",
+                "entries": [
+                    { "name": "b.md", "path": "docs/b.md", "is_dir": false, "size": 2 },
+                    { "name": "guide", "path": "docs/guide", "is_dir": true, "size": null },
+                ],
+            })
+        );
+
+        for uri in ["/docs/b.md", "/missing"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::NOT_FOUND, "{uri}");
+        }
+        #[cfg(unix)]
+        assert_eq!(
+            server.get("/docs/etc").await.status_code(),
+            StatusCode::NOT_FOUND
+        );
+        for uri in ["/docs/.secret", "/..%2F.."] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn subdirectories_pass_path_level_checks() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("docs/guide")).unwrap();
+        std::fs::write(tmp.path().join("docs/b.md"), "bb").unwrap();
+        std::fs::write(tmp.path().join("docs/.b.md"), "bb").unwrap();
+
+        for config in [
+            SafePathConfig::new()
+                .deny_hidden(true)
+                .allowed_extensions(["md"]),
+            SafePathConfig::new()
+                .deny_hidden(true)
+                .require_extension(true),
+            SafePathConfig::new()
+                .deny_hidden(true)
+                .trailing_slash(crate::TrailingSlash::Require),
+        ] {
+            let dir = SafeDirPath {
+                path: "docs/".into(),
+                config,
+            };
+            let Json(listing) = list_dir(tmp.path(), &dir).await.unwrap();
+            let names: Vec<_> = listing.entries.iter().map(|e| e.name.as_str()).collect();
+            assert_eq!(names, ["b.md", "guide"], "{:?}", dir.config);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Paths resolved against one of several mounted roots.
+
+use std::{
+    collections::HashMap,
+    path::{self, PathBuf},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection};
+
+/// A mapping of the first path segment to a root directory, see
+/// [`MountedPath`].
+///
+/// It's taken from the app state with [`FromRef`] and is cheap to clone.
+#[derive(Debug, Clone, Default)]
+pub struct MountTable {
+    mounts: Arc<HashMap<String, PathBuf>>,
+}
+
+impl MountTable {
+    /// Creates an empty table
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mounts the root directory at the name, replacing the previous one
+    ///
+    /// Names are matched against a single path segment, so ones with slashes
+    /// never match.
+    #[must_use]
+    pub fn mount(mut self, name: impl Into<String>, root: impl Into<PathBuf>) -> Self {
+        Arc::make_mut(&mut self.mounts).insert(name.into(), root.into());
+        self
+    }
+
+    /// Returns the root directory mounted at the name
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&path::Path> {
+        self.mounts.get(name).map(PathBuf::as_path)
+    }
+
+    /// Splits the validated path into the mount name and the rest, resolving
+    /// the latter against the mounted root
+    fn resolve(&self, path: &path::Path) -> Result<MountedPath, SafePathRejection> {
+        let mut components = path.components();
+        let name = components
+            .next()
+            .and_then(|name| name.as_os_str().to_str())
+            .ok_or(SafePathRejection::UnknownMount)?;
+        let root = self.get(name).ok_or(SafePathRejection::UnknownMount)?;
+        Ok(MountedPath {
+            mount: name.to_owned(),
+            path: root.join(components.as_path()),
+        })
+    }
+}
+
+/// A [`SafePath`] resolved against the root its first segment is mounted at.
+///
+/// The [`MountTable`] is taken from the app state. The path is validated with
+/// the current [`SafePathConfig`] and normalized, then the first segment
+/// selects the root the rest is joined onto. Paths with an unknown first
+/// segment are rejected:
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::{MountTable, MountedPath};
+///
+/// async fn files(path: MountedPath) -> String {
+///     // `uploads` and `/data/uploads/a.png` for `/files/uploads/a.png`
+///     format!("{} {}", path.mount, path.path.display())
+/// }
+///
+/// let mounts = MountTable::new()
+///     .mount("static", "/var/www")
+///     .mount("uploads", "/data/uploads");
+/// let app: Router = Router::new()
+///     .route("/files/{*path}", get(files))
+///     .with_state(mounts);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MountedPath {
+    /// The name of the mount, i.e. the first path segment
+    pub mount: String,
+    /// The rest of the path joined onto the mounted root
+    pub path: PathBuf,
+}
+
+impl AsRef<path::Path> for MountedPath {
+    fn as_ref(&self) -> &path::Path {
+        self.path.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for MountedPath
+where
+    MountTable: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let SafePath(path) = SafePath::from_request_parts(parts, state).await?;
+        let normalized = SafePathConfig::with_current(&parts.extensions, |config| {
+            config.normalized_relative(&path)
+        });
+        MountTable::from_ref(state).resolve(&normalized)
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    fn mounts() -> MountTable {
+        MountTable::new()
+            .mount("static", "/var/www")
+            .mount("uploads", "/data/uploads")
+    }
+
+    #[test]
+    fn resolve() {
+        let mounted = mounts()
+            .resolve(path::Path::new("static/css/a.css"))
+            .unwrap();
+        assert_eq!(mounted.mount, "static");
+        assert_eq!(mounted.path, PathBuf::from("/var/www/css/a.css"));
+
+        let mounted = mounts().resolve(path::Path::new("uploads")).unwrap();
+        assert_eq!(mounted.path, PathBuf::from("/data/uploads/"));
+
+        for path in ["", "other/a", "stat"] {
+            assert!(
+                matches!(
+                    mounts().resolve(path::Path::new(path)),
+                    Err(SafePathRejection::UnknownMount)
+                ),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        async fn handler(path: MountedPath) -> String {
+            format!("{} {}", path.mount, path.path.display())
+        }
+
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .with_state(mounts());
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/uploads/./a.png").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "uploads /data/uploads/a.png");
+
+        for uri in ["/private/key.pem", "/static%2F..%2Fprivate"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+}
@@ -0,0 +1,332 @@
+//! Multipart uploads with validated file names.
+
+use std::{error::Error, fmt, path::PathBuf};
+
+use axum::{
+    body::Bytes,
+    extract::{
+        FromRequest, Request,
+        multipart::{Field, Multipart, MultipartError, MultipartRejection},
+    },
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    SafePath, SafePathConfig, SafePathRejection, Traversal, config::depth,
+    sanitize::sanitize_component,
+};
+
+/// The name used when nothing is left of the original one
+const FALLBACK_NAME: &str = "upload";
+
+/// A wrapper around [`Multipart`] validating the file name of each part.
+///
+/// File names come straight from the client and can be anything, like
+/// `../../etc/cron.d/x` or `C:\fakepath\photo.jpg`. Each name must be a
+/// single path component passing the current [`SafePathConfig`], otherwise
+/// [`next_field`](Self::next_field) fails. With [`rename`](Self::rename)
+/// dangerous names are fixed using the rules of [`sanitize`](crate::sanitize)
+/// instead, keeping only the last component:
+///
+/// ```
+/// use axum::{Router, routing::post};
+/// use axum_safe_path::{SafeMultipart, SafeMultipartError};
+///
+/// async fn upload(multipart: SafeMultipart) -> Result<String, SafeMultipartError> {
+///     let mut multipart = multipart.rename();
+///     let mut names = Vec::new();
+///     while let Some(field) = multipart.next_field().await? {
+///         if let Some(name) = field.file_name() {
+///             names.push(name.to_owned());
+///         }
+///     }
+///     Ok(names.join(", "))
+/// }
+///
+/// let app: Router = Router::new().route("/upload", post(upload));
+/// ```
+#[derive(Debug)]
+pub struct SafeMultipart {
+    inner: Multipart,
+    config: SafePathConfig,
+    rename: bool,
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequest<S> for SafeMultipart
+where
+    S: Send + Sync,
+{
+    type Rejection = MultipartRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let config = SafePathConfig::with_current(req.extensions(), Clone::clone);
+        let inner = Multipart::from_request(req, state).await?;
+        Ok(Self {
+            inner,
+            config,
+            rename: false,
+        })
+    }
+}
+
+impl SafeMultipart {
+    /// Renames dangerous file names instead of failing
+    #[must_use]
+    pub const fn rename(mut self) -> Self {
+        self.rename = true;
+        self
+    }
+
+    /// Yields the next field if available
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be parsed or the file name of the field is
+    /// invalid.
+    pub async fn next_field(&mut self) -> Result<Option<SafeField<'_>>, SafeMultipartError> {
+        let Some(field) = self.inner.next_field().await? else {
+            return Ok(None);
+        };
+        let file_name = match field.file_name() {
+            // Browsers send an empty name for file inputs left blank
+            None | Some("") => None,
+            Some(name) if self.rename => Some(rename(&self.config, name)?),
+            Some(name) => Some(check(&self.config, name)?),
+        };
+        Ok(Some(SafeField {
+            inner: field,
+            file_name,
+        }))
+    }
+}
+
+/// A field of [`SafeMultipart`] with a validated file name
+#[derive(Debug)]
+pub struct SafeField<'a> {
+    inner: Field<'a>,
+    file_name: Option<String>,
+}
+
+impl<'a> SafeField<'a> {
+    /// The field name found in the `Content-Disposition` header
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        self.inner.name()
+    }
+
+    /// The validated or renamed file name, `None` if the field isn't a file
+    #[must_use]
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// The content type of the field
+    #[must_use]
+    pub fn content_type(&self) -> Option<&str> {
+        self.inner.content_type()
+    }
+
+    /// The headers of the field
+    #[must_use]
+    pub fn headers(&self) -> &HeaderMap {
+        self.inner.headers()
+    }
+
+    /// Gets the full content of the field as bytes
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be read.
+    pub async fn bytes(self) -> Result<Bytes, MultipartError> {
+        self.inner.bytes().await
+    }
+
+    /// Gets the full content of the field as text
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be read or isn't valid UTF-8.
+    pub async fn text(self) -> Result<String, MultipartError> {
+        self.inner.text().await
+    }
+
+    /// Gets the next chunk of the field content, `None` if it's exhausted
+    ///
+    /// # Errors
+    ///
+    /// Fails if the body can't be read.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        self.inner.chunk().await
+    }
+
+    /// Returns the inner field, which still has the original file name
+    #[must_use]
+    pub fn into_inner(self) -> Field<'a> {
+        self.inner
+    }
+}
+
+/// Errors of [`SafeMultipart::next_field`]
+#[derive(Debug)]
+pub enum SafeMultipartError {
+    /// The body can't be parsed
+    Multipart(MultipartError),
+    /// The file name of a field is invalid
+    FileName(SafePathRejection),
+}
+
+impl fmt::Display for SafeMultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Multipart(err) => write!(f, "{err}"),
+            Self::FileName(err) => write!(f, "Invalid file name: {err}"),
+        }
+    }
+}
+
+impl Error for SafeMultipartError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Multipart(err) => Some(err),
+            Self::FileName(err) => Some(err),
+        }
+    }
+}
+
+impl IntoResponse for SafeMultipartError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Multipart(inner) => inner.into_response(),
+            Self::FileName(inner) => inner.into_response(),
+        }
+    }
+}
+
+impl From<MultipartError> for SafeMultipartError {
+    fn from(err: MultipartError) -> Self {
+        Self::Multipart(err)
+    }
+}
+
+impl From<SafePathRejection> for SafeMultipartError {
+    fn from(rejection: SafePathRejection) -> Self {
+        Self::FileName(rejection)
+    }
+}
+
+/// Validates the file name as a single path component
+fn check(config: &SafePathConfig, name: &str) -> Result<String, SafePathRejection> {
+    // Backslashes are separators for the clients sending them
+    if name.contains(['/', '\\']) {
+        return Err(SafePathRejection::TraversalAttack(Traversal::Separator));
+    }
+    let SafePath(path) = config.validate(PathBuf::from(name))?;
+    if depth(&path) != 1 {
+        return Err(SafePathRejection::Empty);
+    }
+    Ok(name.to_owned())
+}
+
+/// Sanitizes the last component of the file name and validates the result
+fn rename(config: &SafePathConfig, name: &str) -> Result<String, SafePathRejection> {
+    let name = name
+        .rsplit(['/', '\\'])
+        .find_map(sanitize_component)
+        .unwrap_or_else(|| FALLBACK_NAME.to_owned());
+    check(config, &name)
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::post};
+    use axum_test::{
+        TestServer,
+        multipart::{MultipartForm, Part},
+    };
+
+    use super::*;
+
+    async fn handler(multipart: SafeMultipart) -> Result<String, SafeMultipartError> {
+        collect(multipart).await
+    }
+
+    async fn renaming_handler(multipart: SafeMultipart) -> Result<String, SafeMultipartError> {
+        collect(multipart.rename()).await
+    }
+
+    async fn collect(mut multipart: SafeMultipart) -> Result<String, SafeMultipartError> {
+        let mut names = Vec::new();
+        while let Some(field) = multipart.next_field().await? {
+            names.push(field.file_name().unwrap_or("-").to_owned());
+            field.bytes().await?;
+        }
+        Ok(names.join(","))
+    }
+
+    fn form(file_name: &str) -> MultipartForm {
+        MultipartForm::new().add_text("title", "report").add_part(
+            "file",
+            Part::bytes(b"content".as_slice()).file_name(file_name),
+        )
+    }
+
+    #[test]
+    fn names() {
+        let config = SafePathConfig::new().deny_hidden(true);
+        assert_eq!(check(&config, "photo.jpg").unwrap(), "photo.jpg");
+        for name in [
+            "..",
+            "../x",
+            "a/b",
+            "/etc/passwd",
+            "C:\\fakepath\\x",
+            ".env",
+            "a\0b",
+        ] {
+            assert!(check(&config, name).is_err(), "{name:?}");
+        }
+
+        for (name, expected) in [
+            ("photo.jpg", "photo.jpg"),
+            ("../../etc/cron.d/x", "x"),
+            ("C:\\fakepath\\photo.jpg", "photo.jpg"),
+            (".env", "env"),
+            ("a/..", "a"),
+            ("..", "upload"),
+            ("con.txt", "_con.txt"),
+        ] {
+            assert_eq!(rename(&config, name).unwrap(), expected, "{name:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let app = Router::new()
+            .route("/", post(handler))
+            .route("/rename", post(renaming_handler))
+            .layer(Extension(SafePathConfig::new().allowed_extensions(["pdf"])));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.post("/").multipart(form("report.pdf")).await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "-,report.pdf");
+
+        for name in ["../report.pdf", "script.php"] {
+            let res = server.post("/").multipart(form(name)).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{name}");
+        }
+
+        let res = server
+            .post("/rename")
+            .multipart(form("..\\..\\report.pdf"))
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "-,report.pdf");
+
+        let res = server.post("/rename").multipart(form("script.php")).await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,77 @@
+//! Extractor returning a lexically normalized path.
+
+use std::path::{self, PathBuf};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection};
+
+/// A [`SafePath`] normalized with [`SafePathConfig::normalize`].
+///
+/// `.` and empty components are removed, so `./a//b/` becomes `a/b`, and the
+/// value can be used directly as a cache or storage key:
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::NormalizedSafePath;
+///
+/// async fn handler(NormalizedSafePath(key): NormalizedSafePath) -> String {
+///     key.display().to_string()
+/// }
+///
+/// let app: Router = Router::new().route("/objects/{*key}", get(handler));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NormalizedSafePath(pub PathBuf);
+
+impl AsRef<path::Path> for NormalizedSafePath {
+    fn as_ref(&self) -> &path::Path {
+        self.0.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for NormalizedSafePath
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let SafePath(path) = SafePath::from_request_parts(parts, state).await?;
+        Ok(Self(SafePathConfig::with_current(
+            &parts.extensions,
+            |config| config.normalized(&path),
+        )))
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(NormalizedSafePath(path): NormalizedSafePath) -> String {
+        path.display().to_string()
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let app = Router::new().route("/{*path}", get(handler));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/a//b%2F%2F%2Fc/").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a/b/c");
+
+        let res = server.get("/a%5Cb").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a/b");
+
+        let res = server.get("/a/%2E%2E%2F%2E%2E%2Fb").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,446 @@
+//! Validation of object storage keys.
+
+use std::{path, str::FromStr, sync::Arc};
+
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+
+use crate::{SafePathOutcome, SafePathRejection, Traversal, raw, trace};
+
+/// The maximum key length of S3 and Google Cloud Storage in bytes, and of
+/// Azure Blob Storage in characters
+const MAX_LENGTH: usize = 1024;
+
+/// Characters S3 documents as safe to use in keys, besides alphanumerics
+const S3_SAFE_CHARACTERS: &str = "/!-_.*'()";
+
+/// A key of an object in S3-like storage.
+///
+/// Object storage has no directories, so there's nothing to traverse, but
+/// keys often come from request paths and end up in consoles, sync tools and
+/// local mirrors, which do treat them as paths. The key is checked with the
+/// [`ObjectKeyConfig`] from request extensions or the default one, which
+/// rejects:
+///
+/// - empty keys and keys longer than 1024 bytes
+/// - a leading `/`, `.` and `..` segments
+/// - control characters
+///
+/// ```
+/// use axum::{Extension, Router, routing::get};
+/// use axum_safe_path::{ObjectKeyConfig, SafeObjectKey};
+///
+/// async fn download(SafeObjectKey(key): SafeObjectKey) -> String {
+///     format!("s3://bucket/{key}")
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/objects/{*key}", get(download))
+///     .layer(Extension(ObjectKeyConfig::new().safe_characters_only(true)));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SafeObjectKey(pub String);
+
+impl AsRef<str> for SafeObjectKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for SafeObjectKey {
+    type Err = SafePathRejection;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ObjectKeyConfig::default().validate(s)
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for SafeObjectKey
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(key) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(SafePathRejection::from)?;
+        let raw = raw::capture(&parts.uri, path::Path::new(&key));
+        let result = trace::validation(&parts.extensions, raw, || {
+            match parts.extensions.get::<ObjectKeyConfig>() {
+                Some(config) => config.validate(key),
+                None => ObjectKeyConfig::default().validate(key),
+            }
+        });
+        SafePathOutcome::record(&mut parts.extensions, &result);
+        result
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SafeObjectKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::de::Deserializer<'de>,
+    {
+        let key = String::deserialize(deserializer)?;
+
+        ObjectKeyConfig::default()
+            .validate(key)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// Rules for [`SafeObjectKey`], inserted into request extensions to
+/// override the default ones
+///
+/// The default rules follow S3, while [`gcs`](Self::gcs) and
+/// [`azure_blob`](Self::azure_blob) encode the restrictions of other
+/// backends, so a gateway can pick the config per bucket:
+///
+/// ```
+/// use axum_safe_path::ObjectKeyConfig;
+///
+/// assert!(ObjectKeyConfig::s3().check("reports/q1#draft.pdf").is_ok());
+/// assert!(ObjectKeyConfig::gcs().check("reports/q1#draft.pdf").is_err());
+/// assert!(ObjectKeyConfig::azure_blob().check("reports/q1.").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ObjectKeyConfig {
+    max_length: usize,
+    count_characters: bool,
+    max_segments: Option<usize>,
+    safe_characters_only: bool,
+    deny_trailing_dots: bool,
+    denied_characters: Option<Arc<str>>,
+    denied_prefixes: Option<Arc<[String]>>,
+}
+
+impl Default for ObjectKeyConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ObjectKeyConfig {
+    /// Creates a config with the S3 limits
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            max_length: MAX_LENGTH,
+            count_characters: false,
+            max_segments: None,
+            safe_characters_only: false,
+            deny_trailing_dots: false,
+            denied_characters: None,
+            denied_prefixes: None,
+        }
+    }
+
+    /// A preset for Amazon S3, the same as [`new`](Self::new)
+    #[must_use]
+    pub const fn s3() -> Self {
+        Self::new()
+    }
+
+    /// A preset for Google Cloud Storage
+    ///
+    /// Rejects `#[]*?`, which break gsutil wildcards and XML API URLs, and
+    /// the `.well-known/acme-challenge/` prefix reserved for domain
+    /// verification.
+    #[must_use]
+    pub fn gcs() -> Self {
+        Self::new()
+            .denied_characters("#[]*?")
+            .denied_prefixes([".well-known/acme-challenge/"])
+    }
+
+    /// A preset for Azure Blob Storage
+    ///
+    /// Limits the name to 1024 characters and 254 segments, and rejects
+    /// names ending with a dot or a slash, which the service can't address,
+    /// and backslashes, which it silently turns into slashes.
+    #[must_use]
+    pub fn azure_blob() -> Self {
+        Self::new()
+            .count_characters(true)
+            .max_segments(254)
+            .deny_trailing_dots(true)
+            .denied_characters("\\")
+    }
+
+    /// Rejects keys longer than `length` bytes, 1024 by default
+    #[must_use]
+    pub const fn max_length(mut self, length: usize) -> Self {
+        self.max_length = length;
+        self
+    }
+
+    /// Counts the [`max_length`](Self::max_length) in characters rather
+    /// than bytes
+    #[must_use]
+    pub const fn count_characters(mut self, enabled: bool) -> Self {
+        self.count_characters = enabled;
+        self
+    }
+
+    /// Rejects keys having more than `segments` `/`-separated segments
+    #[must_use]
+    pub const fn max_segments(mut self, segments: usize) -> Self {
+        self.max_segments = Some(segments);
+        self
+    }
+
+    /// Rejects keys ending with a dot or a slash
+    #[must_use]
+    pub const fn deny_trailing_dots(mut self, deny: bool) -> Self {
+        self.deny_trailing_dots = deny;
+        self
+    }
+
+    /// Allows only ASCII alphanumerics and `/!-_.*'()`, the characters S3
+    /// guarantees to work with every tool
+    #[must_use]
+    pub const fn safe_characters_only(mut self, enabled: bool) -> Self {
+        self.safe_characters_only = enabled;
+        self
+    }
+
+    /// Rejects keys containing any of the characters, like `\{}^%` S3
+    /// advises to avoid
+    #[must_use]
+    pub fn denied_characters(mut self, characters: impl Into<Arc<str>>) -> Self {
+        self.denied_characters = Some(characters.into());
+        self
+    }
+
+    /// Rejects keys starting with any of the prefixes
+    #[must_use]
+    pub fn denied_prefixes<I>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.denied_prefixes = Some(prefixes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Validates the key against the config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn validate(&self, key: impl Into<String>) -> Result<SafeObjectKey, SafePathRejection> {
+        let key = key.into();
+        self.check(&key)?;
+        Ok(SafeObjectKey(key))
+    }
+
+    /// Checks the key against the config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn check(&self, key: &str) -> Result<(), SafePathRejection> {
+        if key.is_empty() {
+            return Err(SafePathRejection::Empty);
+        }
+        let length = if self.count_characters {
+            key.chars().count()
+        } else {
+            key.len()
+        };
+        if length > self.max_length {
+            return Err(SafePathRejection::TooLong {
+                max: self.max_length,
+            });
+        }
+        if let Some(max) = self.max_segments
+            && key.trim_end_matches('/').split('/').count() > max
+        {
+            return Err(SafePathRejection::TooDeep { max });
+        }
+        if key.contains('\0') {
+            return Err(SafePathRejection::NulByte);
+        }
+        if key.starts_with('/') {
+            return Err(SafePathRejection::TraversalAttack(Traversal::RootDir));
+        }
+        if let Some(index) = key.split('/').position(|segment| segment == "..") {
+            return Err(SafePathRejection::TraversalAttack(Traversal::ParentDir {
+                index,
+            }));
+        }
+        if key.split('/').any(|segment| segment == ".") {
+            return Err(SafePathRejection::CurrentDir);
+        }
+        if key.chars().any(char::is_control) {
+            return Err(SafePathRejection::InvalidCharacter);
+        }
+        if self.deny_trailing_dots {
+            if key.ends_with('/') {
+                return Err(SafePathRejection::TrailingSlash);
+            }
+            if key.ends_with('.') {
+                return Err(SafePathRejection::TrailingDotOrSpace);
+            }
+        }
+        if let Some(prefixes) = &self.denied_prefixes
+            && prefixes
+                .iter()
+                .any(|prefix| key.starts_with(prefix.as_str()))
+        {
+            return Err(SafePathRejection::DeniedName);
+        }
+        if let Some(denied) = &self.denied_characters
+            && key.chars().any(|c| denied.contains(c))
+        {
+            return Err(SafePathRejection::InvalidCharacter);
+        }
+        if self.safe_characters_only
+            && !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || S3_SAFE_CHARACTERS.contains(c))
+        {
+            return Err(SafePathRejection::NonPortable);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    #[test]
+    fn check() {
+        let config = ObjectKeyConfig::new();
+        for key in [
+            "a",
+            "photos/2024/cat.jpg",
+            "folder/",
+            "a b/ü.txt",
+            "a..b/.c",
+        ] {
+            assert!(config.check(key).is_ok(), "{key:?}");
+        }
+        for (key, expected) in [
+            ("", SafePathRejection::Empty),
+            ("/a", SafePathRejection::TraversalAttack(Traversal::RootDir)),
+            (
+                "a/../b",
+                SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 1 }),
+            ),
+            (
+                "..",
+                SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 0 }),
+            ),
+            ("a/./b", SafePathRejection::CurrentDir),
+            ("a\0b", SafePathRejection::NulByte),
+            ("a\nb", SafePathRejection::InvalidCharacter),
+        ] {
+            assert_eq!(
+                config.check(key).as_ref().map_err(std::mem::discriminant),
+                Err(std::mem::discriminant(&expected)),
+                "{key:?}"
+            );
+        }
+        assert!(matches!(
+            config.check(&"a".repeat(1025)),
+            Err(SafePathRejection::TooLong { max: 1024 })
+        ));
+    }
+
+    #[test]
+    fn characters() {
+        let config = ObjectKeyConfig::new().denied_characters("\\{}^%");
+        assert!(config.check("a b.txt").is_ok());
+        assert!(config.check("a\\b").is_err());
+        assert!(config.check("100%.txt").is_err());
+
+        let config = ObjectKeyConfig::new().safe_characters_only(true);
+        assert!(config.check("Photos/cat-1_(copy)'.jpg").is_ok());
+        for key in ["a b", "ü.txt", "a+b", "a&b"] {
+            assert!(
+                matches!(config.check(key), Err(SafePathRejection::NonPortable)),
+                "{key:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn presets() {
+        let gcs = ObjectKeyConfig::gcs();
+        assert!(gcs.check("a b/ü.txt").is_ok());
+        for key in ["a#b", "a[1]", "*.txt", "what?"] {
+            assert!(
+                matches!(gcs.check(key), Err(SafePathRejection::InvalidCharacter)),
+                "{key:?}"
+            );
+        }
+        assert!(matches!(
+            gcs.check(".well-known/acme-challenge/token"),
+            Err(SafePathRejection::DeniedName)
+        ));
+        assert!(gcs.check(".well-known/other").is_ok());
+
+        let azure = ObjectKeyConfig::azure_blob();
+        assert!(azure.check("a/b.txt").is_ok());
+        assert!(matches!(
+            azure.check("a/b."),
+            Err(SafePathRejection::TrailingDotOrSpace)
+        ));
+        assert!(matches!(
+            azure.check("folder/"),
+            Err(SafePathRejection::TrailingSlash)
+        ));
+        assert!(azure.check("a\\b").is_err());
+        // 1024 characters, but more bytes
+        assert!(azure.check(&"ü".repeat(1024)).is_ok());
+        assert!(ObjectKeyConfig::s3().check(&"ü".repeat(1024)).is_err());
+        assert!(matches!(
+            azure.check(&format!("{}b", "a/".repeat(254))),
+            Err(SafePathRejection::TooDeep { max: 254 })
+        ));
+        assert!(azure.check(&format!("{}b", "a/".repeat(253))).is_ok());
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        async fn handler(SafeObjectKey(key): SafeObjectKey) -> String {
+            key
+        }
+
+        let app = Router::new().route("/default/{*key}", get(handler)).merge(
+            Router::new()
+                .route("/safe/{*key}", get(handler))
+                .layer(Extension(ObjectKeyConfig::new().safe_characters_only(true))),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/default/a%20b/c.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a b/c.txt");
+
+        for uri in ["/default/a/..%2Fb", "/default//a", "/safe/a%20b"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let key: SafeObjectKey = serde_json::from_str(r#""a/b.txt""#).unwrap();
+        assert_eq!(serde_json::to_string(&key).unwrap(), r#""a/b.txt""#);
+        assert!(serde_json::from_str::<SafeObjectKey>(r#""../b.txt""#).is_err());
+    }
+}
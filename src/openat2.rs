@@ -0,0 +1,82 @@
+//! Kernel-enforced containment with `openat2` on Linux.
+
+use std::{fs::File, io, path::Path};
+
+use rustix::fs::{Mode, OFlags, ResolveFlags, open, openat2};
+
+use crate::SafePath;
+
+#[cfg_attr(docsrs, doc(cfg(all(feature = "openat2", target_os = "linux"))))]
+impl SafePath {
+    /// Opens the file in read-only mode beneath the root directory
+    ///
+    /// The path is resolved by the kernel with `RESOLVE_BENEATH` and
+    /// `RESOLVE_NO_MAGICLINKS`, so neither `..`, absolute symlinks, symlinks
+    /// pointing outside of the root nor `/proc` magic links can escape it,
+    /// even if the filesystem changes concurrently:
+    ///
+    /// ```
+    /// use axum_safe_path::SafePath;
+    ///
+    /// let path: SafePath = "missing/file.txt".parse().unwrap();
+    /// assert!(path.open_beneath(std::env::temp_dir()).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file can't be opened or is outside of the root. Kernels
+    /// older than 5.6 don't have `openat2` and fail with `ENOSYS`.
+    pub fn open_beneath(&self, root: impl AsRef<Path>) -> io::Result<File> {
+        let root = open(
+            root.as_ref(),
+            OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC,
+            Mode::empty(),
+        )?;
+        let fd = openat2(
+            &root,
+            &self.0,
+            OFlags::RDONLY | OFlags::CLOEXEC,
+            Mode::empty(),
+            ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+        )?;
+        Ok(File::from(fd))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn beneath() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(tmp.path().join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink("../secret.txt", root.join("relative")).unwrap();
+        std::os::unix::fs::symlink(tmp.path().join("secret.txt"), root.join("absolute")).unwrap();
+        std::os::unix::fs::symlink("a.txt", root.join("inside")).unwrap();
+
+        let mut content = String::new();
+        let path: SafePath = "inside".parse().unwrap();
+        path.open_beneath(&root)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "a");
+
+        for name in ["relative", "absolute", "missing"] {
+            let path: SafePath = name.parse().unwrap();
+            assert!(path.open_beneath(&root).is_err(), "{name}");
+        }
+        assert!(
+            SafePath("../secret.txt".into())
+                .open_beneath(&root)
+                .is_err()
+        );
+    }
+}
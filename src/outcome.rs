@@ -0,0 +1,144 @@
+//! The outcome of validation stored in request extensions.
+
+use std::path::{Path, PathBuf};
+
+use axum::http::Extensions;
+
+use crate::{SafeObjectKey, SafePath, SafePathConfig, SafePathRejection, SafeUri};
+
+/// The outcome of the last path validation of the request.
+///
+/// The extractors store it in request extensions, so the extractors and
+/// middleware running after them can see what happened without validating
+/// the path again:
+///
+/// ```
+/// use axum::{Extension, Router, routing::get};
+/// use axum_safe_path::{SafePath, SafePathOutcome, SafePathRejection};
+///
+/// async fn handler(
+///     path: Result<SafePath, SafePathRejection>,
+///     Extension(outcome): Extension<SafePathOutcome>,
+/// ) -> String {
+///     format!("{outcome:?}")
+/// }
+///
+/// let app: Router = Router::new().route("/{*path}", get(handler));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SafePathOutcome {
+    /// The path passed the validation
+    Accepted(PathBuf),
+    /// The path was rejected, with the [`code`](SafePathRejection::code) of
+    /// the rejection
+    Rejected(&'static str),
+}
+
+impl SafePathOutcome {
+    /// Stores the outcome of the validation in the extensions
+    pub(crate) fn record<T: Validated>(
+        extensions: &mut Extensions,
+        result: &Result<T, SafePathRejection>,
+    ) {
+        let outcome = match result {
+            Ok(validated) => Self::Accepted(validated.path().to_owned()),
+            Err(rejection) => Self::Rejected(rejection.code()),
+        };
+        extensions.insert(outcome);
+    }
+}
+
+/// The result of an extractor kept in request extensions, so extracting it
+/// again for the same request, e.g. in a middleware and the handler, doesn't
+/// repeat the work
+///
+/// It's keyed by the config the result was validated with, so an inner layer
+/// setting another config, e.g. with [`Extension`](axum::Extension), makes
+/// the extractor validate the path again.
+#[derive(Debug, Clone)]
+pub struct Cached<T> {
+    config: SafePathConfig,
+    result: Result<T, SafePathRejection>,
+}
+
+impl<T: Clone + Send + Sync + 'static> Cached<T> {
+    /// Returns the cached result if it was validated with the current config
+    pub fn get(extensions: &Extensions) -> Option<Result<T, SafePathRejection>> {
+        let cached = extensions.get::<Self>()?;
+        SafePathConfig::with_current(extensions, |config| config.same_as(&cached.config))
+            .then(|| cached.result.clone())
+    }
+
+    /// Caches the result validated with the current config
+    pub fn insert(extensions: &mut Extensions, result: Result<T, SafePathRejection>) {
+        let config = SafePathConfig::with_current(extensions, Clone::clone);
+        extensions.insert(Self { config, result });
+    }
+}
+
+/// A value accepted by the validation
+pub trait Validated {
+    /// The accepted path
+    fn path(&self) -> &Path;
+}
+
+impl Validated for SafePath {
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Validated for SafeUri {
+    fn path(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl Validated for SafeObjectKey {
+    fn path(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(
+        _: Result<SafePath, SafePathRejection>,
+        Extension(outcome): Extension<SafePathOutcome>,
+    ) -> String {
+        format!("{outcome:?}")
+    }
+
+    #[tokio::test]
+    async fn stored_in_extensions() {
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(Extension(SafePathConfig::new().deny_hidden(true)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/a/b").await;
+        assert_eq!(res.text(), r#"Accepted("a/b")"#);
+
+        let res = server.get("/a/.env").await;
+        assert_eq!(res.text(), r#"Rejected("hidden")"#);
+    }
+
+    #[tokio::test]
+    async fn cache_is_keyed_by_config() {
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(Extension(SafePathConfig::new().deny_hidden(true)))
+            .layer(axum::middleware::from_extractor::<SafePath>());
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/a/.env").await;
+        assert_eq!(res.text(), r#"Rejected("hidden")"#);
+    }
+}
@@ -0,0 +1,178 @@
+//! Tower layer validating all path parameters of matched routes.
+
+use std::{
+    future::Future,
+    path::{self, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::{FromRequestParts, RawPathParams, Request, rejection::RawPathParamsRejection},
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{SafePathConfig, raw};
+
+/// A layer validating every captured path parameter with a
+/// [`SafePathConfig`].
+///
+/// Requests with an invalid parameter are rejected before reaching the
+/// handler, which gives blanket protection to handlers still using plain
+/// [`Path`](axum::extract::Path) extractors:
+///
+/// ```
+/// use axum::{Router, extract::Path, routing::get};
+/// use axum_safe_path::SafePathLayer;
+///
+/// async fn legacy(Path(name): Path<String>) -> String {
+///     name
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/files/{*name}", get(legacy))
+///     .layer(SafePathLayer::default());
+/// ```
+///
+/// Parameters are only known after routing, so the layer has to be added
+/// with [`Router::layer`](axum::Router::layer) or
+/// [`Router::route_layer`](axum::Router::route_layer), which wrap each route.
+/// All parameters are checked as paths, including ids and slugs, so the config
+/// should suit all of them.
+#[derive(Debug, Clone, Default)]
+pub struct SafePathLayer {
+    config: SafePathConfig,
+}
+
+impl SafePathLayer {
+    /// Creates a layer validating parameters with the config
+    #[must_use]
+    pub const fn new(config: SafePathConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl<S> Layer<S> for SafePathLayer {
+    type Service = SafePathParams<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SafePathParams {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// The middleware created by [`SafePathLayer`]
+#[derive(Debug, Clone)]
+pub struct SafePathParams<S> {
+    inner: S,
+    config: SafePathConfig,
+}
+
+impl<S, B> Service<Request<B>> for SafePathParams<S>
+where
+    S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        // The clone may not be ready, so the ready one is taken
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            if let Err(response) = validate_params(&config, &mut parts).await {
+                return Ok(response);
+            }
+            inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
+
+/// Validates all path parameters of the request
+async fn validate_params(config: &SafePathConfig, parts: &mut Parts) -> Result<(), Response> {
+    let params = match RawPathParams::from_request_parts(parts, &()).await {
+        Ok(params) => params,
+        Err(RawPathParamsRejection::MissingPathParams(_)) => return Ok(()),
+        Err(rejection) => return Err(rejection.into_response()),
+    };
+    for (_, value) in &params {
+        let raw = raw::capture(&parts.uri, path::Path::new(value));
+        config
+            .validate_encoded(PathBuf::from(value), Some(raw))
+            .map_err(IntoResponse::into_response)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Router, extract::Path, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(Path(params): Path<Vec<String>>) -> String {
+        params.join(" ")
+    }
+
+    fn server(layer: SafePathLayer) -> TestServer {
+        let app = Router::new()
+            .route("/files/{*path}", get(handler))
+            .route("/users/{user}/files/{file}", get(handler))
+            .route("/health", get(|| async { "ok" }))
+            .layer(layer);
+        TestServer::new(app).unwrap()
+    }
+
+    #[tokio::test]
+    async fn valid_params() {
+        let server = server(SafePathLayer::default());
+
+        let res = server.get("/files/a/b.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a/b.txt");
+
+        let res = server.get("/users/42/files/a.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "42 a.txt");
+
+        let res = server.get("/health").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn invalid_params() {
+        let server = server(SafePathLayer::default());
+        for uri in [
+            "/files//etc/passwd",
+            "/users/..%2Fadmin/files/a.txt",
+            "/users/42/files/a%00b",
+        ] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_config() {
+        let server = server(SafePathLayer::new(SafePathConfig::new().deny_hidden(true)));
+        let res = server.get("/files/a/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Invalid path: hidden files are not allowed");
+    }
+}
@@ -0,0 +1,183 @@
+//! Extractors splitting validated paths into parts.
+
+use std::path::{self, PathBuf};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection, Traversal};
+
+/// A [`SafePath`] of a directory, e.g. to list with
+/// [`list_dir`](crate::list_dir).
+///
+/// It keeps the current [`SafePathConfig`], so the listing only has entries
+/// which would pass it as well.
+#[derive(Debug, Clone)]
+pub struct SafeDirPath {
+    /// The validated path of the directory
+    pub path: PathBuf,
+    pub(crate) config: SafePathConfig,
+}
+
+impl AsRef<path::Path> for SafeDirPath {
+    fn as_ref(&self) -> &path::Path {
+        self.path.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for SafeDirPath
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let SafePath(path) = SafePath::from_request_parts(parts, state).await?;
+        let config = SafePathConfig::with_current(&parts.extensions, Clone::clone);
+        Ok(Self { path, config })
+    }
+}
+
+/// A [`SafePath`] split into the directory, the file stem and the extension.
+///
+/// The split follows [`Path::file_stem`](path::Path::file_stem), so
+/// `archive.tar.gz` has the `archive.tar` stem and the `gz` extension, and
+/// hidden files like `.env` have no extension. Names which would leave a
+/// misleading stem are rejected:
+///
+/// - `..x` and `...`, having the `.` and `..` stems
+/// - names ending with a dot, having an empty extension
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::SafePathParts;
+///
+/// async fn thumbnail(SafePathParts { dir, stem, ext }: SafePathParts) -> String {
+///     let ext = ext.as_deref().unwrap_or("bin");
+///     dir.path.join(format!("{stem}.thumb.{ext}")).display().to_string()
+/// }
+///
+/// let app: Router = Router::new().route("/thumbnails/{*path}", get(thumbnail));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SafePathParts {
+    /// The directory of the file, empty for files in the root
+    pub dir: SafeDirPath,
+    /// The file name without the extension
+    pub stem: String,
+    /// The extension without the dot
+    pub ext: Option<String>,
+}
+
+impl SafePathParts {
+    /// Splits the validated path with the config it was validated with
+    fn split(path: &path::Path, config: SafePathConfig) -> Result<Self, SafePathRejection> {
+        let name = path.file_name().ok_or(SafePathRejection::Empty)?;
+        let name = name.to_str().ok_or(SafePathRejection::InvalidUtf8)?;
+        if name.ends_with('.') {
+            return Err(SafePathRejection::TrailingDotOrSpace);
+        }
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+            _ => (name, None),
+        };
+        match stem {
+            "." => return Err(SafePathRejection::CurrentDir),
+            ".." => {
+                let index = path.components().count() - 1;
+                return Err(SafePathRejection::TraversalAttack(Traversal::ParentDir {
+                    index,
+                }));
+            }
+            _ => {}
+        }
+        let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+        Ok(Self {
+            dir: SafeDirPath { path: dir, config },
+            stem: stem.to_owned(),
+            ext: ext.map(str::to_owned),
+        })
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for SafePathParts
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let SafeDirPath { path, config } = SafeDirPath::from_request_parts(parts, state).await?;
+        Self::split(&path, config)
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    fn split(path: &str) -> Result<(String, String, Option<String>), SafePathRejection> {
+        let parts = SafePathParts::split(path::Path::new(path), SafePathConfig::new())?;
+        let dir = parts.dir.path.display().to_string();
+        Ok((dir, parts.stem, parts.ext))
+    }
+
+    #[test]
+    fn splits() {
+        for (path, dir, stem, ext) in [
+            ("a.txt", "", "a", Some("txt")),
+            ("docs/readme", "docs", "readme", None),
+            ("docs/archive.tar.gz", "docs", "archive.tar", Some("gz")),
+            ("docs/.env", "docs", ".env", None),
+            ("docs/.config.toml", "docs", ".config", Some("toml")),
+            ("a/b/....txt", "a/b", "...", Some("txt")),
+        ] {
+            let expected = (dir.to_owned(), stem.to_owned(), ext.map(str::to_owned));
+            assert_eq!(split(path).unwrap(), expected, "{path:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_misleading_names() {
+        for (path, rejection) in [
+            ("", SafePathRejection::Empty),
+            ("docs/file.", SafePathRejection::TrailingDotOrSpace),
+            ("docs/...", SafePathRejection::TrailingDotOrSpace),
+            ("docs/..txt", SafePathRejection::CurrentDir),
+            (
+                "docs/...txt",
+                SafePathRejection::TraversalAttack(Traversal::ParentDir { index: 1 }),
+            ),
+        ] {
+            assert_eq!(split(path), Err(rejection), "{path:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let app = Router::new().route(
+            "/{*path}",
+            get(
+                |SafePathParts { dir, stem, ext }: SafePathParts| async move {
+                    format!("{}|{stem}|{ext:?}", dir.path.display())
+                },
+            ),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/photos/2024/cat.jpeg").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), r#"photos/2024|cat|Some("jpeg")"#);
+
+        let res = server.get("/photos/...jpeg").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+
+        let res = server.get("/photos/..%2Fcat.jpeg").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,227 @@
+//! Composable path policies.
+
+use std::{fmt, path::Path, sync::Arc};
+
+use crate::{
+    SafePathConfig, SafePathRejection,
+    config::{depth, file_name, has_extension, is_hidden, normal_components},
+};
+
+/// A check applied to a path on top of the built-in ones.
+///
+/// Policies can be combined using [`PolicyExt`] and added to a config with
+/// [`SafePathConfig::policy`]:
+///
+/// ```
+/// use axum_safe_path::{
+///     SafePathConfig,
+///     policy::{DenyDotfiles, FileExtension, MaxDepth, PolicyExt},
+/// };
+///
+/// let config = SafePathConfig::new()
+///     .policy(DenyDotfiles.and(MaxDepth::<5>).and_not(FileExtension("php")));
+/// assert!(config.check("blog/index.html").is_ok());
+/// assert!(config.check("blog/index.php").is_err());
+/// assert!(config.check("blog/.env").is_err());
+/// ```
+pub trait Policy: Send + Sync + 'static {
+    /// Checks the path
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection if the path violates the policy.
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection>;
+}
+
+impl fmt::Debug for dyn Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Policy")
+    }
+}
+
+impl<F> Policy for F
+where
+    F: Fn(&Path) -> Result<(), SafePathRejection> + Send + Sync + 'static,
+{
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        self(path)
+    }
+}
+
+impl<P: Policy + ?Sized> Policy for Arc<P> {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        (**self).check(path)
+    }
+}
+
+impl Policy for SafePathConfig {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        Self::check(self, path)
+    }
+}
+
+/// Combinators for [`Policy`]
+pub trait PolicyExt: Policy + Sized {
+    /// Passes if both policies pass
+    fn and<P: Policy>(self, other: P) -> And<Self, P> {
+        And(self, other)
+    }
+
+    /// Passes if either of the policies passes
+    fn or<P: Policy>(self, other: P) -> Or<Self, P> {
+        Or(self, other)
+    }
+
+    /// Passes if the policy fails
+    fn not(self) -> Not<Self> {
+        Not(self)
+    }
+
+    /// Passes if this policy passes and the other one fails
+    fn and_not<P: Policy>(self, other: P) -> And<Self, Not<P>> {
+        And(self, Not(other))
+    }
+}
+
+impl<T: Policy> PolicyExt for T {}
+
+/// Passes if both policies pass, see [`PolicyExt::and`]
+#[derive(Debug, Clone, Copy)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: Policy, B: Policy> Policy for And<A, B> {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        self.0.check(path)?;
+        self.1.check(path)
+    }
+}
+
+/// Passes if either of the policies passes, see [`PolicyExt::or`]
+///
+/// If both fail, the rejection of the second one is returned.
+#[derive(Debug, Clone, Copy)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: Policy, B: Policy> Policy for Or<A, B> {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        self.0.check(path).or_else(|_| self.1.check(path))
+    }
+}
+
+/// Passes if the policy fails, see [`PolicyExt::not`]
+#[derive(Debug, Clone, Copy)]
+pub struct Not<P>(pub P);
+
+impl<P: Policy> Policy for Not<P> {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        match self.0.check(path) {
+            Ok(()) => Err(SafePathRejection::Denied),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Rejects paths having a hidden component, i.e. one starting with a dot
+#[derive(Debug, Clone, Copy)]
+pub struct DenyDotfiles;
+
+impl Policy for DenyDotfiles {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        if normal_components(path).any(is_hidden) {
+            Err(SafePathRejection::Hidden)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Rejects paths having more than `N` components
+#[derive(Debug, Clone, Copy)]
+pub struct MaxDepth<const N: usize>;
+
+impl<const N: usize> Policy for MaxDepth<N> {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        if depth(path) > N {
+            Err(SafePathRejection::TooDeep { max: N })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Passes only if the last path component has the extension, compared
+/// case-insensitively
+#[derive(Debug, Clone, Copy)]
+pub struct FileExtension(pub &'static str);
+
+impl Policy for FileExtension {
+    fn check(&self, path: &Path) -> Result<(), SafePathRejection> {
+        let ext = self.0.trim_start_matches('.').to_lowercase();
+        if file_name(path).is_some_and(|name| has_extension(&name, &ext)) {
+            Ok(())
+        } else {
+            Err(SafePathRejection::ExtensionNotAllowed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn and() {
+        let policy = DenyDotfiles.and(MaxDepth::<2>);
+        assert!(policy.check(Path::new("a/b")).is_ok());
+        assert!(matches!(
+            policy.check(Path::new(".a/b")),
+            Err(SafePathRejection::Hidden)
+        ));
+        assert!(matches!(
+            policy.check(Path::new("a/b/c")),
+            Err(SafePathRejection::TooDeep { max: 2 })
+        ));
+    }
+
+    #[test]
+    fn or() {
+        let policy = FileExtension("png").or(FileExtension("jpg"));
+        assert!(policy.check(Path::new("a.png")).is_ok());
+        assert!(policy.check(Path::new("a.JPG")).is_ok());
+        assert!(policy.check(Path::new("a.gif")).is_err());
+    }
+
+    #[test]
+    fn not() {
+        let policy = FileExtension("php").not();
+        assert!(policy.check(Path::new("index.html")).is_ok());
+        assert!(matches!(
+            policy.check(Path::new("index.php")),
+            Err(SafePathRejection::Denied)
+        ));
+    }
+
+    #[test]
+    fn closure() {
+        let policy = MaxDepth::<3>.and(|path: &Path| {
+            if path.starts_with("private") {
+                Err(SafePathRejection::Denied)
+            } else {
+                Ok(())
+            }
+        });
+        assert!(policy.check(Path::new("public/a")).is_ok());
+        assert!(policy.check(Path::new("private/a")).is_err());
+    }
+
+    #[test]
+    fn config_policy() {
+        let config = SafePathConfig::new()
+            .policy(MaxDepth::<1>)
+            .policy(DenyDotfiles);
+        assert!(config.check("a").is_ok());
+        assert!(config.check("a/b").is_err());
+        assert!(config.check(".a").is_err());
+        assert!(config.check("../a").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Tower layer checking paths passed in query parameters.
+
+use std::{
+    future::Future,
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::Request,
+    response::{IntoResponse, Response},
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{SafePathConfig, SafePathRejection, raw};
+
+/// Query parameters commonly carrying file paths
+const DEFAULT_NAMES: &[&str] = &[
+    "dir", "download", "file", "filename", "include", "page", "path", "template",
+];
+
+/// A layer validating selected query parameters as paths.
+///
+/// It catches the classic `?file=../../etc/passwd` outside of path captures,
+/// rejecting the request before it reaches the handler:
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::{QueryGuardLayer, SafePathConfig};
+///
+/// let app: Router = Router::new()
+///     .route("/download", get(|| async { "file" }))
+///     .layer(
+///         QueryGuardLayer::new(["file", "name"])
+///             .config(SafePathConfig::new().deny_hidden(true)),
+///     );
+/// ```
+///
+/// By default it checks `dir`, `download`, `file`, `filename`, `include`,
+/// `page`, `path` and `template` with the default [`SafePathConfig`].
+#[derive(Debug, Clone)]
+pub struct QueryGuardLayer {
+    names: Arc<[String]>,
+    config: SafePathConfig,
+}
+
+impl Default for QueryGuardLayer {
+    fn default() -> Self {
+        Self::new(DEFAULT_NAMES)
+    }
+}
+
+impl QueryGuardLayer {
+    /// Creates a layer checking the query parameters with the names
+    #[must_use]
+    pub fn new<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        Self {
+            names: names
+                .into_iter()
+                .map(|name| name.as_ref().to_owned())
+                .collect(),
+            config: SafePathConfig::new(),
+        }
+    }
+
+    /// Sets the config the parameters are checked with
+    #[must_use]
+    pub fn config(mut self, config: SafePathConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Checks the selected parameters of the raw query string
+    fn check(&self, query: &str) -> Result<(), SafePathRejection> {
+        for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+            if self.names.contains(&decode(name)) {
+                self.config
+                    .validate_encoded(PathBuf::from(decode(value)), Some(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> Layer<S> for QueryGuardLayer {
+    type Service = QueryGuard<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        QueryGuard {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// The middleware created by [`QueryGuardLayer`]
+#[derive(Debug, Clone)]
+pub struct QueryGuard<S> {
+    inner: S,
+    layer: QueryGuardLayer,
+}
+
+impl<S, B> Service<Request<B>> for QueryGuard<S>
+where
+    S: Service<Request<B>, Response = Response>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let query = req.uri().query().unwrap_or_default();
+        if let Err(rejection) = self.layer.check(query) {
+            let response = rejection.into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+        Box::pin(self.inner.call(req))
+    }
+}
+
+/// Decodes a form-urlencoded query component
+fn decode(raw: &str) -> String {
+    String::from_utf8_lossy(&raw::percent_decode(&raw.replace('+', " "))).into_owned()
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    #[test]
+    fn check() {
+        let layer = QueryGuardLayer::default();
+        for query in [
+            "",
+            "file=a/b.txt",
+            "q=../x",
+            "file",
+            "page=2&path=docs/a+b.md",
+        ] {
+            assert!(layer.check(query).is_ok(), "{query:?}");
+        }
+        for query in [
+            "file=../../etc/passwd",
+            "x=1&path=%2Fetc%2Fpasswd",
+            "template=..%2F..%2Fsecret",
+            "%66ile=/etc/passwd",
+            "file=a.txt&file=../b",
+        ] {
+            assert!(layer.check(query).is_err(), "{query:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware() {
+        let app = Router::new()
+            .route("/download", get(|| async { "ok" }))
+            .layer(QueryGuardLayer::new(["name"]).config(SafePathConfig::new().deny_hidden(true)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server
+            .get("/download")
+            .add_query_param("name", "a.txt")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server
+            .get("/download")
+            .add_query_param("name", ".env")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Invalid path: hidden files are not allowed");
+
+        let res = server
+            .get("/download")
+            .add_query_param("file", ".env")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+    }
+}
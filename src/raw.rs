@@ -0,0 +1,317 @@
+//! Validation of path parameters before percent-decoding.
+
+use std::path::{self, PathBuf};
+
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::{Uri, request::Parts},
+};
+
+use crate::{SafePathConfig, SafePathRejection};
+
+/// A [`SafePath`](crate::SafePath) that also inspects the raw,
+/// percent-encoded text of the parameter.
+///
+/// Some attacks are only visible before decoding, so on top of the checks of
+/// the current [`SafePathConfig`] it rejects:
+///
+/// - encoded separators, `%2F` and `%5C`, which change the structure of the
+///   path after routing
+/// - double encoding, like `%252e`, which turns into `.` when decoded again
+/// - overlong UTF-8 sequences, like `%C0%AE`, which some decoders turn into `.`
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::RawSafePath;
+///
+/// async fn handler(path: RawSafePath) -> String {
+///     format!("{} from {}", path.path.display(), path.raw)
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(handler));
+/// ```
+///
+/// Axum's `RawPathParams` yields already decoded values, so the raw text is
+/// taken from the request URI.
+#[derive(Debug, Clone)]
+pub struct RawSafePath {
+    /// The decoded and validated path
+    pub path: PathBuf,
+    /// The parameter as it appears in the request URI
+    pub raw: String,
+}
+
+impl AsRef<path::Path> for RawSafePath {
+    fn as_ref(&self) -> &path::Path {
+        self.path.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for RawSafePath
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let path = match Path::<PathBuf>::from_request_parts(parts, state).await {
+            Ok(Path(path)) => path,
+            // Overlong sequences aren't valid UTF-8, so decoding fails
+            Err(_) if has_overlong_utf8(parts.uri.path()) => {
+                return Err(SafePathRejection::OverlongUtf8);
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let raw = capture(&parts.uri, &path);
+        check(raw)?;
+
+        let path = SafePathConfig::validate_current(&mut parts.extensions, raw, |config| {
+            config.validate_encoded(path, Some(raw))
+        })?;
+        Ok(Self {
+            path: path.0,
+            raw: raw.to_owned(),
+        })
+    }
+}
+
+/// Performs the checks [`RawSafePath`] applies regardless of the config
+fn check(raw: &str) -> Result<(), SafePathRejection> {
+    if has_encoded_separator(raw) {
+        return Err(SafePathRejection::EncodedSeparator);
+    }
+    if has_double_encoding(raw) {
+        return Err(SafePathRejection::PercentEncoded);
+    }
+    if has_overlong_utf8(raw) {
+        return Err(SafePathRejection::OverlongUtf8);
+    }
+    Ok(())
+}
+
+/// Returns the raw text of the captured path parameter
+///
+/// Falls back to the whole request path if the capture can't be located, so
+/// checks of the raw text err on the safe side.
+pub fn capture<'a>(uri: &'a Uri, decoded: &path::Path) -> &'a str {
+    let raw_path = uri.path();
+    decoded
+        .to_str()
+        .and_then(|decoded| find_capture(raw_path, decoded))
+        .unwrap_or(raw_path)
+}
+
+/// Finds the raw segments of the request path the captured value was decoded
+/// from, trying segment ends from the right, as a wildcard capture is always
+/// at the end
+fn find_capture<'a>(raw_path: &'a str, decoded: &str) -> Option<&'a str> {
+    let ends = raw_path
+        .match_indices('/')
+        .map(|(i, _)| i)
+        .chain([raw_path.len()])
+        .rev();
+    for end in ends {
+        if let Some(start) = capture_start(raw_path.get(..end)?, decoded)
+            && (start == 0 || raw_path.as_bytes().get(start - 1) == Some(&b'/'))
+        {
+            return raw_path.get(start..end);
+        }
+    }
+    None
+}
+
+/// Returns the start of the raw suffix that decodes into the value
+///
+/// Escapes can't overlap, so decoding from the end gives the same bytes as
+/// decoding from the start.
+fn capture_start(raw: &str, decoded: &str) -> Option<usize> {
+    let bytes = raw.as_bytes();
+    let mut start = bytes.len();
+    for &expected in decoded.as_bytes().iter().rev() {
+        let (byte, len) = match start.checked_sub(3).and_then(|i| bytes.get(i..start)) {
+            Some(&[b'%', hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                ((hex_value(hi) << 4) | hex_value(lo), 3)
+            }
+            _ => (*bytes.get(start.checked_sub(1)?)?, 1),
+        };
+        if byte != expected {
+            return None;
+        }
+        start -= len;
+    }
+    Some(start)
+}
+
+/// Converts an ASCII hex digit into its value
+const fn hex_value(digit: u8) -> u8 {
+    match digit {
+        b'0'..=b'9' => digit - b'0',
+        b'a'..=b'f' => digit - b'a' + 10,
+        _ => digit - b'A' + 10,
+    }
+}
+
+/// Decodes percent-escapes, leaving malformed ones as is
+pub fn percent_decode(raw: &str) -> Vec<u8> {
+    let bytes = raw.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while let Some(&b) = bytes.get(i) {
+        match bytes.get(i..i + 3) {
+            Some(&[b'%', hi, lo]) if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                decoded.push((hex_value(hi) << 4) | hex_value(lo));
+                i += 3;
+            }
+            _ => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Iterates over bytes encoded by percent-escapes of the raw text
+fn escaped_bytes(raw: &str) -> impl Iterator<Item = (usize, u8)> + '_ {
+    let bytes = raw.as_bytes();
+    memchr::memchr_iter(b'%', bytes).filter_map(|i| match *bytes.get(i..i + 3)? {
+        [_, hi, lo] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+            Some((i, (hex_value(hi) << 4) | hex_value(lo)))
+        }
+        _ => None,
+    })
+}
+
+/// Checks if the raw text has an encoded slash or backslash
+pub fn has_encoded_separator(raw: &str) -> bool {
+    escaped_bytes(raw).any(|(_, b)| matches!(b, b'/' | b'\\'))
+}
+
+/// Checks if the raw text has an encoded percent-escape, like `%252e`
+fn has_double_encoding(raw: &str) -> bool {
+    escaped_bytes(raw).any(|(i, b)| {
+        b == b'%'
+            && raw
+                .as_bytes()
+                .get(i + 3..i + 5)
+                .is_some_and(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+    })
+}
+
+/// Checks if the raw text has an escaped overlong UTF-8 sequence, i.e. one
+/// encoding a character with more bytes than needed
+fn has_overlong_utf8(raw: &str) -> bool {
+    let escaped: Vec<(usize, u8)> = escaped_bytes(raw).collect();
+    escaped.iter().enumerate().any(|(n, &(i, lead))| {
+        // The continuation byte must directly follow the lead one
+        let next = escaped
+            .get(n + 1)
+            .filter(|&&(j, _)| j == i + 3)
+            .map(|&(_, b)| b);
+        match (lead, next) {
+            (0xC0 | 0xC1, _) => true,
+            (0xE0, Some(b)) => (0x80..0xA0).contains(&b),
+            (0xF0, Some(b)) => (0x80..0x90).contains(&b),
+            _ => false,
+        }
+    })
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(path: RawSafePath) -> String {
+        format!("{} {}", path.path.display(), path.raw)
+    }
+
+    fn server(config: SafePathConfig) -> TestServer {
+        let app = Router::new()
+            .route("/files/{*path}", get(handler))
+            .route("/names/{name}/edit", get(handler))
+            .layer(Extension(config));
+        TestServer::new(app).unwrap()
+    }
+
+    #[test]
+    fn captures() {
+        assert_eq!(find_capture("/files/a/b.txt", "a/b.txt"), Some("a/b.txt"));
+        assert_eq!(find_capture("/files/a%2Fb", "a/b"), Some("a%2Fb"));
+        assert_eq!(find_capture("/files/%252e", "%2e"), Some("%252e"));
+        assert_eq!(find_capture("/files/%%41", "%A"), Some("%%41"));
+        assert_eq!(
+            find_capture("/files/caf%C3%A9", "caf\u{e9}"),
+            Some("caf%C3%A9")
+        );
+        assert_eq!(find_capture("/names/a%2Fb/edit", "a/b"), Some("a%2Fb"));
+        assert_eq!(find_capture("/files/xa", "a"), None);
+        assert_eq!(find_capture("/files/a", "b"), None);
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(percent_decode("a%20b%2fc"), b"a b/c");
+        assert_eq!(percent_decode("100%"), b"100%");
+        assert_eq!(percent_decode("%%41%4"), b"%A%4");
+        assert_eq!(percent_decode("%zz"), b"%zz");
+    }
+
+    #[test]
+    fn raw_checks() {
+        assert!(check("a/b%20c.txt").is_ok());
+        assert!(check("100%25").is_ok());
+        assert!(check("%E2%80%A6").is_ok());
+        for (raw, expected) in [
+            ("a%2fb", SafePathRejection::EncodedSeparator),
+            ("a%5Cb", SafePathRejection::EncodedSeparator),
+            ("%252e%252e", SafePathRejection::PercentEncoded),
+            ("%C0%AE%C0%AE", SafePathRejection::OverlongUtf8),
+            ("%E0%80%AE", SafePathRejection::OverlongUtf8),
+            ("%F0%80%80%AE", SafePathRejection::OverlongUtf8),
+        ] {
+            assert_eq!(
+                check(raw).map_err(|err| err.to_string()),
+                Err(expected.to_string()),
+                "{raw:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let server = server(SafePathConfig::new());
+
+        let res = server.get("/files/a/b%20c.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a/b c.txt a/b%20c.txt");
+
+        let res = server.get("/names/a%20b/edit").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "a b a%20b");
+
+        for uri in [
+            "/files/a%2Fb",
+            "/names/a%2Fb/edit",
+            "/files/%252e%252e/a",
+            "/files/%C0%AE%C0%AE/a",
+            "/files//etc/passwd",
+        ] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn config_checks() {
+        let res = server(SafePathConfig::new().deny_hidden(true))
+            .get("/files/.env")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(res.text(), "Invalid path: hidden files are not allowed");
+    }
+}
@@ -0,0 +1,161 @@
+//! Redirects to user-supplied paths without open redirects.
+
+use axum::response::{IntoResponse, Redirect, Response};
+
+use crate::{SafePathRejection, component::is_control_char, raw};
+
+/// A redirect to a same-origin path taken from user input, like a `next` or
+/// `return` query parameter.
+///
+/// The target must be a relative path starting with a single `/`, so a scheme,
+/// a `//host` or a `/\host` can't send the user to another site. Backslashes,
+/// control and whitespace characters, which browsers strip or treat as
+/// slashes, are rejected as well, including percent-encoded ones at the
+/// start of the path:
+///
+/// ```
+/// use axum_safe_path::SafeRedirect;
+///
+/// fn after_login(next: Option<&str>) -> SafeRedirect {
+///     SafeRedirect::to_or(next.unwrap_or("/"), "/")
+/// }
+///
+/// assert!(SafeRedirect::to("/account?tab=keys").is_ok());
+/// assert!(SafeRedirect::to("//evil.com").is_err());
+/// assert!(SafeRedirect::to("https://evil.com").is_err());
+/// ```
+#[derive(Debug)]
+#[must_use = "needs to be returned from a handler or otherwise turned into a Response to be useful"]
+pub struct SafeRedirect(Redirect);
+
+impl SafeRedirect {
+    /// Creates a `303 See Other` redirect to the target
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SafePathRejection::UnsafeRedirect`] if the target isn't a
+    /// same-origin path.
+    pub fn to(target: &str) -> Result<Self, SafePathRejection> {
+        check(target).map(|()| Self(Redirect::to(target)))
+    }
+
+    /// Creates a `307 Temporary Redirect` to the target
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SafePathRejection::UnsafeRedirect`] if the target isn't a
+    /// same-origin path.
+    pub fn temporary(target: &str) -> Result<Self, SafePathRejection> {
+        check(target).map(|()| Self(Redirect::temporary(target)))
+    }
+
+    /// Creates a `308 Permanent Redirect` to the target
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SafePathRejection::UnsafeRedirect`] if the target isn't a
+    /// same-origin path.
+    pub fn permanent(target: &str) -> Result<Self, SafePathRejection> {
+        check(target).map(|()| Self(Redirect::permanent(target)))
+    }
+
+    /// Creates a `303 See Other` redirect to the target, or to the trusted
+    /// fallback if the target isn't a same-origin path
+    pub fn to_or(target: &str, fallback: &str) -> Self {
+        Self::to(target).unwrap_or_else(|_| Self(Redirect::to(fallback)))
+    }
+}
+
+impl IntoResponse for SafeRedirect {
+    fn into_response(self) -> Response {
+        self.0.into_response()
+    }
+}
+
+/// Checks that the target is a same-origin path
+fn check(target: &str) -> Result<(), SafePathRejection> {
+    let decoded = raw::percent_decode(target);
+    let is_safe = is_local_path(target.as_bytes())
+        && is_local_path(&decoded)
+        && !target
+            .chars()
+            .any(|c| c == '\\' || c.is_whitespace() || is_control_char(c))
+        && !String::from_utf8_lossy(&decoded)
+            .chars()
+            .any(|c| c == '\\' || is_control_char(c));
+    if is_safe {
+        Ok(())
+    } else {
+        Err(SafePathRejection::UnsafeRedirect)
+    }
+}
+
+/// Checks that the path starts with a single slash
+const fn is_local_path(path: &[u8]) -> bool {
+    match path {
+        [b'/'] => true,
+        [b'/', second, ..] => *second != b'/' && *second != b'\\',
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{StatusCode, header::LOCATION};
+
+    use super::*;
+
+    #[test]
+    fn safe_targets() {
+        for target in [
+            "/",
+            "/account",
+            "/a/b?c=d#e",
+            "/search?q=a%20b",
+            "/caf\u{e9}",
+            "/a//b",
+        ] {
+            assert!(check(target).is_ok(), "{target:?}");
+        }
+    }
+
+    #[test]
+    fn unsafe_targets() {
+        for target in [
+            "",
+            "account",
+            "https://evil.com",
+            "javascript:alert(1)",
+            "//evil.com",
+            "/\\evil.com",
+            "\\\\evil.com",
+            "/\t/evil.com",
+            "/ /evil.com",
+            "/a\r\nSet-Cookie: x",
+            "/%2F/evil.com",
+            "/%5Cevil.com",
+            "/%09/evil.com",
+            "/a%0d%0ab",
+        ] {
+            assert!(
+                matches!(check(target), Err(SafePathRejection::UnsafeRedirect)),
+                "{target:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn responses() {
+        let res = SafeRedirect::to("/home")
+            .unwrap_or_else(|_| unreachable!())
+            .into_response();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers()[LOCATION], "/home");
+
+        let res = SafeRedirect::to_or("//evil.com", "/").into_response();
+        assert_eq!(res.headers()[LOCATION], "/");
+
+        let res = SafePathRejection::UnsafeRedirect.into_response();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
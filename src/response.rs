@@ -0,0 +1,456 @@
+//! Formats of rejection responses.
+
+use std::{cmp::Reverse, fmt, sync::Arc};
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+
+use crate::SafePathRejection;
+
+/// The format of rejection bodies, see
+/// [`SafePathPolicyLayer::rejection_format`](crate::SafePathPolicyLayer::rejection_format)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RejectionFormat {
+    /// A plain-text message
+    #[default]
+    Text,
+    /// A JSON object with the machine-readable
+    /// [`code`](crate::SafePathRejection::code) and the message, like
+    /// `{"error":"path_traversal","detail":"Invalid path: ..."}`
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    Json,
+    /// An `application/problem+json` document of RFC 9457 (formerly RFC 7807)
+    /// with the `about:blank` type, the status and the message as the
+    /// `detail`, and the [`code`](crate::SafePathRejection::code) as an
+    /// extension member
+    #[cfg(feature = "problem")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "problem")))]
+    Problem,
+    /// A snippet of HTML with the status and the message, for browser
+    /// navigations
+    Html,
+    /// Chosen by the `Accept` header of the request among the other formats,
+    /// falling back to [`Text`](Self::Text)
+    ///
+    /// It allows the same routes to serve both APIs and pages.
+    Negotiate,
+}
+
+/// How a [`SafePathPolicy`] adjusts responses to rejected paths
+///
+/// [`SafePathPolicy`]: crate::SafePathPolicy
+#[derive(Debug, Default, Clone)]
+pub struct Responder {
+    /// The status replacing the default one
+    pub status: Option<StatusCode>,
+    /// The format of the body
+    pub format: RejectionFormat,
+    /// The message replacing the default ones
+    pub message: Option<Arc<str>>,
+    /// Replaces the whole response with a not-found one
+    pub not_found: Option<NotFound>,
+    /// The lookup of translated messages
+    #[cfg(feature = "i18n")]
+    pub translator: Option<Translator>,
+    /// The `Accept-Language` header of the request
+    #[cfg(feature = "i18n")]
+    accept_language: Option<HeaderValue>,
+    /// Whether the format was chosen by the `Accept` header
+    negotiated: bool,
+}
+
+/// The function building the response of the router fallback
+type FallbackFn = dyn Fn() -> Response + Send + Sync;
+
+/// The not-found response masking rejections, see
+/// [`SafePathPolicyLayer::mask_as_not_found`](crate::SafePathPolicyLayer::mask_as_not_found)
+///
+/// Without a function, it's the response of the default axum fallback: `404
+/// Not Found` with an empty body.
+#[derive(Clone, Default)]
+pub struct NotFound(pub Option<Arc<FallbackFn>>);
+
+impl NotFound {
+    /// Builds the response
+    fn response(&self) -> Response {
+        self.0.as_ref().map_or_else(
+            || StatusCode::NOT_FOUND.into_response(),
+            |fallback| fallback(),
+        )
+    }
+}
+
+impl fmt::Debug for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("NotFound")
+    }
+}
+
+/// The function looking up translated messages
+#[cfg(feature = "i18n")]
+type TranslateFn = dyn Fn(&str, &str) -> Option<String> + Send + Sync;
+
+/// The lookup of translated messages, see
+/// [`SafePathPolicyLayer::rejection_translator`](crate::SafePathPolicyLayer::rejection_translator)
+#[cfg(feature = "i18n")]
+#[derive(Clone)]
+pub struct Translator(pub Arc<TranslateFn>);
+
+#[cfg(feature = "i18n")]
+impl fmt::Debug for Translator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Translator")
+    }
+}
+
+impl Responder {
+    /// Creates a responder keeping the default response
+    pub const fn new() -> Self {
+        Self {
+            status: None,
+            format: RejectionFormat::Text,
+            message: None,
+            not_found: None,
+            #[cfg(feature = "i18n")]
+            translator: None,
+            #[cfg(feature = "i18n")]
+            accept_language: None,
+            negotiated: false,
+        }
+    }
+
+    /// Prepares the responder for the request: resolves
+    /// [`RejectionFormat::Negotiate`] and keeps the preferred languages
+    pub fn for_request(mut self, headers: &HeaderMap) -> Self {
+        if self.format == RejectionFormat::Negotiate {
+            self.negotiated = true;
+            self.format = headers
+                .get(header::ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .map_or(RejectionFormat::Text, preferred_format);
+        }
+        #[cfg(feature = "i18n")]
+        if self.translator.is_some() {
+            self.accept_language = headers.get(header::ACCEPT_LANGUAGE).cloned();
+        }
+        self
+    }
+
+    /// Translates the message of the rejection into the most preferred
+    /// language it has a translation for
+    #[cfg(feature = "i18n")]
+    fn translate(&self, code: &str) -> Option<String> {
+        let Translator(translate) = self.translator.as_ref()?;
+        let accept_language = self.accept_language.as_ref()?.to_str().ok()?;
+        preferred_languages(accept_language)
+            .into_iter()
+            .find_map(|language| translate(language, code))
+    }
+
+    /// Adjusts the response if it's a rejection not handled by an inner
+    /// policy yet
+    pub fn respond(&self, mut res: Response) -> Response {
+        let Some(mut rejected) = res.extensions_mut().remove::<Rejected>() else {
+            return res;
+        };
+        if rejected.handled {
+            res.extensions_mut().insert(rejected);
+            return res;
+        }
+        rejected.handled = true;
+
+        if let Some(not_found) = &self.not_found {
+            let mut res = not_found.response();
+            res.extensions_mut().insert(rejected);
+            return res;
+        }
+
+        let status = self.status.unwrap_or_else(|| res.status());
+        let default_detail = rejected.rejection.to_string();
+        #[cfg(feature = "i18n")]
+        let translated = self.translate(rejected.rejection.code());
+        #[cfg(feature = "i18n")]
+        let detail = translated
+            .as_deref()
+            .or(self.message.as_deref())
+            .unwrap_or(&default_detail);
+        #[cfg(not(feature = "i18n"))]
+        let detail = self.message.as_deref().unwrap_or(&default_detail);
+        let mut res = match self.format {
+            RejectionFormat::Text | RejectionFormat::Negotiate => {
+                (status, detail.to_owned()).into_response()
+            }
+            #[cfg(feature = "json")]
+            RejectionFormat::Json => {
+                let body = axum::Json(JsonBody {
+                    error: rejected.rejection.code(),
+                    detail,
+                });
+                (status, body).into_response()
+            }
+            #[cfg(feature = "problem")]
+            RejectionFormat::Problem => {
+                let body = axum::Json(ProblemBody {
+                    r#type: "about:blank",
+                    title: status.canonical_reason().unwrap_or_default(),
+                    status: status.as_u16(),
+                    detail,
+                    code: rejected.rejection.code(),
+                });
+                let content_type = [(header::CONTENT_TYPE, "application/problem+json")];
+                (status, content_type, body).into_response()
+            }
+            RejectionFormat::Html => {
+                let title = format!(
+                    "{} {}",
+                    status.as_u16(),
+                    status.canonical_reason().unwrap_or_default()
+                );
+                let body = format!(
+                    "<!doctype html><title>{title}</title><h1>{title}</h1><p>{}</p>",
+                    escape_html(detail)
+                );
+                (status, Html(body)).into_response()
+            }
+        };
+        // The body depends on these headers, so caches must not share it
+        // between clients sending different ones
+        if self.negotiated {
+            res.headers_mut()
+                .append(header::VARY, HeaderValue::from_static("accept"));
+        }
+        #[cfg(feature = "i18n")]
+        if self.translator.is_some() {
+            res.headers_mut()
+                .append(header::VARY, HeaderValue::from_static("accept-language"));
+        }
+        res.extensions_mut().insert(rejected);
+        res
+    }
+}
+
+/// The body of [`RejectionFormat::Json`]
+#[cfg(feature = "json")]
+#[derive(serde::Serialize)]
+struct JsonBody<'a> {
+    error: &'a str,
+    detail: &'a str,
+}
+
+/// The body of [`RejectionFormat::Problem`], see RFC 9457
+#[cfg(feature = "problem")]
+#[derive(serde::Serialize)]
+struct ProblemBody<'a> {
+    r#type: &'a str,
+    title: &'a str,
+    status: u16,
+    detail: &'a str,
+    /// The machine-readable code of the rejection, an extension member
+    code: &'a str,
+}
+
+/// Marks responses to rejected paths, so the innermost [`SafePathPolicy`] can
+/// adjust them
+///
+/// [`SafePathPolicy`]: crate::SafePathPolicy
+#[derive(Debug, Clone)]
+pub struct Rejected {
+    /// The rejection the response was made of
+    rejection: Arc<SafePathRejection>,
+    /// Whether a policy has already processed the response
+    handled: bool,
+}
+
+impl Rejected {
+    /// Creates a marker of an unhandled rejection
+    pub fn new(rejection: SafePathRejection) -> Self {
+        Self {
+            rejection: Arc::new(rejection),
+            handled: false,
+        }
+    }
+
+    /// Returns the rejection of the response if no policy has processed it
+    /// yet
+    pub fn unhandled(res: &Response) -> Option<&Arc<SafePathRejection>> {
+        res.extensions()
+            .get::<Self>()
+            .filter(|rejected| !rejected.handled)
+            .map(|rejected| &rejected.rejection)
+    }
+}
+
+/// Chooses the format the client prefers according to the `Accept` header
+///
+/// Formats are ranked by the quality, the specificity of the matching media
+/// range and its position in the header. Ties are resolved in favor of
+/// plain text.
+fn preferred_format(accept: &str) -> RejectionFormat {
+    let ranges: Vec<(&str, u16)> = weighted(accept).collect();
+
+    [
+        (RejectionFormat::Text, "text/plain"),
+        (RejectionFormat::Html, "text/html"),
+        #[cfg(feature = "json")]
+        (RejectionFormat::Json, "application/json"),
+        #[cfg(feature = "problem")]
+        (RejectionFormat::Problem, "application/problem+json"),
+    ]
+    .into_iter()
+    .filter_map(|(format, media)| {
+        let (position, specificity, quality) = ranges
+            .iter()
+            .enumerate()
+            .filter_map(|(position, &(range, quality))| {
+                Some((position, specificity(range, media)?, quality))
+            })
+            .max_by_key(|&(_, specificity, _)| specificity)?;
+        (quality > 0).then_some((format, (quality, specificity, Reverse(position))))
+    })
+    // The first of equally ranked formats wins
+    .rev()
+    .max_by_key(|&(_, rank)| rank)
+    .map_or(RejectionFormat::Text, |(format, _)| format)
+}
+
+/// Lists the languages of the `Accept-Language` header from the most
+/// preferred one, skipping the wildcard and unacceptable ones
+#[cfg(feature = "i18n")]
+fn preferred_languages(accept_language: &str) -> Vec<&str> {
+    let mut languages: Vec<(&str, u16)> = weighted(accept_language)
+        .filter(|&(language, quality)| quality > 0 && language != "*")
+        .collect();
+    languages.sort_by_key(|&(_, quality)| Reverse(quality));
+    languages
+        .into_iter()
+        .map(|(language, _)| language)
+        .collect()
+}
+
+/// Iterates over the values of a header like `Accept` with their quality
+/// values in thousandths
+fn weighted(header: &str) -> impl Iterator<Item = (&str, u16)> {
+    header.split(',').filter_map(|item| {
+        let mut params = item.split(';');
+        let value = params.next()?.trim();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(parse_quality)
+            .unwrap_or(1000);
+        (!value.is_empty()).then_some((value, quality))
+    })
+}
+
+/// How specific the media range matching the media type is, if it matches
+fn specificity(range: &str, media: &str) -> Option<u8> {
+    if range.eq_ignore_ascii_case(media) {
+        return Some(2);
+    }
+    match range.strip_suffix("/*") {
+        Some("*") => Some(0),
+        Some(kind) => media
+            .split('/')
+            .next()
+            .is_some_and(|media_kind| media_kind.eq_ignore_ascii_case(kind))
+            .then_some(1),
+        None => None,
+    }
+}
+
+/// Parses a quality value like `0.8` into thousandths
+fn parse_quality(value: &str) -> Option<u16> {
+    let (int, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 3 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let frac = format!("{frac:0<3}").parse::<u16>().ok()?;
+    match int {
+        "0" => Some(frac),
+        "1" if frac == 0 => Some(1000),
+        _ => None,
+    }
+}
+
+/// Escapes the text to be put into HTML
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiation() {
+        for (accept, expected) in [
+            ("", RejectionFormat::Text),
+            ("*/*", RejectionFormat::Text),
+            ("image/png", RejectionFormat::Text),
+            ("text/*", RejectionFormat::Text),
+            (
+                "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+                RejectionFormat::Html,
+            ),
+            ("text/html;q=0, */*", RejectionFormat::Text),
+            #[cfg(feature = "json")]
+            ("application/json", RejectionFormat::Json),
+            #[cfg(feature = "json")]
+            ("application/json, text/plain, */*", RejectionFormat::Json),
+            #[cfg(feature = "json")]
+            ("text/plain;q=0.5, application/*", RejectionFormat::Json),
+            #[cfg(feature = "problem")]
+            (
+                "application/problem+json, application/json;q=0.9",
+                RejectionFormat::Problem,
+            ),
+        ] {
+            assert_eq!(preferred_format(accept), expected, "{accept:?}");
+        }
+    }
+
+    #[cfg(feature = "i18n")]
+    #[test]
+    fn languages() {
+        assert_eq!(
+            preferred_languages("fr-CH, fr;q=0.9, en;q=0.8, de;q=0.7, *;q=0.5"),
+            ["fr-CH", "fr", "en", "de"]
+        );
+        assert_eq!(preferred_languages("en;q=0.5, uk, ru;q=0"), ["uk", "en"]);
+        assert!(preferred_languages("").is_empty());
+    }
+
+    #[test]
+    fn quality() {
+        assert_eq!(parse_quality("1"), Some(1000));
+        assert_eq!(parse_quality("1.000"), Some(1000));
+        assert_eq!(parse_quality("0.8"), Some(800));
+        assert_eq!(parse_quality("0.05"), Some(50));
+        assert_eq!(parse_quality("0"), Some(0));
+        assert_eq!(parse_quality("1.5"), None);
+        assert_eq!(parse_quality("0.1234"), None);
+        assert_eq!(parse_quality("x"), None);
+    }
+
+    #[test]
+    fn html_escaping() {
+        assert_eq!(
+            escape_html("<a href=\"x\">&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
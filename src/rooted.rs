@@ -0,0 +1,263 @@
+//! Paths resolved against root directories.
+
+use std::path::{self, Component, PathBuf};
+
+use axum::{
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection, Traversal};
+
+/// The directory [`RootedPath`] resolves paths against.
+///
+/// It's taken from the app state with [`FromRef`], so it can be the state
+/// itself or one of its fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathRoot(pub PathBuf);
+
+impl<T: Into<PathBuf>> From<T> for PathRoot {
+    fn from(root: T) -> Self {
+        Self(root.into())
+    }
+}
+
+/// A [`SafePath`] joined onto the [`PathRoot`] of the app state.
+///
+/// The path is validated with the current [`SafePathConfig`] and normalized
+/// before joining, so the result is always lexically inside the root and
+/// handlers don't need to call `root.join(path)` themselves. Symlinks inside
+/// the root aren't resolved:
+///
+/// ```
+/// use axum::{Router, extract::FromRef, routing::get};
+/// use axum_safe_path::{PathRoot, RootedPath};
+///
+/// #[derive(Clone)]
+/// struct AppState {
+///     files: PathRoot,
+/// }
+///
+/// impl FromRef<AppState> for PathRoot {
+///     fn from_ref(state: &AppState) -> Self {
+///         state.files.clone()
+///     }
+/// }
+///
+/// async fn download(RootedPath(path): RootedPath) -> String {
+///     // `/var/www/css/site.css` for `/files/css/site.css`
+///     path.display().to_string()
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/files/{*path}", get(download))
+///     .with_state(AppState {
+///         files: PathRoot::from("/var/www"),
+///     });
+/// ```
+#[derive(Debug, Clone)]
+pub struct RootedPath(pub PathBuf);
+
+impl AsRef<path::Path> for RootedPath {
+    fn as_ref(&self) -> &path::Path {
+        self.0.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for RootedPath
+where
+    PathRoot: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let SafePath(path) = SafePath::from_request_parts(parts, state).await?;
+        let relative = SafePathConfig::with_current(&parts.extensions, |config| {
+            config.normalized_relative(&path)
+        });
+        let PathRoot(root) = PathRoot::from_ref(state);
+        Ok(Self(root.join(relative)))
+    }
+}
+
+/// Joins an untrusted path onto the base, failing if the result escapes it.
+///
+/// Both paths are normalized lexically, so `..` segments are resolved without
+/// touching the filesystem and symlinks aren't followed. Meant for places
+/// extractors can't be used, like background jobs or websocket messages:
+///
+/// ```
+/// use std::path::Path;
+///
+/// use axum_safe_path::join_rooted;
+///
+/// let path = join_rooted("/var/www", "css/../img/a.png").unwrap();
+/// assert_eq!(path, Path::new("/var/www/img/a.png"));
+///
+/// assert!(join_rooted("/var/www", "../../etc/passwd").is_err());
+/// assert!(join_rooted("/var/www", "/etc/passwd").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns [`SafePathRejection::TraversalAttack`] if the joined path isn't
+/// inside the base.
+pub fn join_rooted(
+    base: impl AsRef<path::Path>,
+    path: impl AsRef<path::Path>,
+) -> Result<PathBuf, SafePathRejection> {
+    let base = base.as_ref();
+    ensure_within(base, base.join(path))
+}
+
+/// Checks that the path is lexically inside the base, returning it normalized
+///
+/// # Errors
+///
+/// Returns [`SafePathRejection::TraversalAttack`] if the path isn't inside the
+/// base.
+pub fn ensure_within(
+    base: impl AsRef<path::Path>,
+    path: impl AsRef<path::Path>,
+) -> Result<PathBuf, SafePathRejection> {
+    let path = lexically_normalize(path.as_ref());
+    let base = lexically_normalize(base.as_ref());
+    // An empty base, like `.`, is a prefix of any path, including the ones
+    // going up or starting at the root
+    let within = path.strip_prefix(&base).is_ok_and(|rest| {
+        rest.components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    });
+    if within {
+        Ok(path)
+    } else {
+        Err(SafePathRejection::TraversalAttack(Traversal::EscapesBase))
+    }
+}
+
+/// Removes `.` segments and resolves `..` ones without touching the
+/// filesystem
+fn lexically_normalize(path: &path::Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // `..` of the root is the root itself
+                Some(Component::RootDir | Component::Prefix(_)) => (),
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(RootedPath(path): RootedPath) -> String {
+        path.display().to_string()
+    }
+
+    #[test]
+    fn normalize() {
+        for (path, expected) in [
+            ("/a/./b/../c", "/a/c"),
+            ("/..", "/"),
+            ("a/../../b", "../b"),
+            ("./a/", "a"),
+            ("", ""),
+        ] {
+            assert_eq!(
+                lexically_normalize(path::Path::new(path)),
+                PathBuf::from(expected),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn containment() {
+        for (base, path, expected) in [
+            ("/var/www", "a.txt", "/var/www/a.txt"),
+            ("/var/www", "a/../b", "/var/www/b"),
+            ("/var/www/", ".", "/var/www"),
+            ("data", "x/./y", "data/x/y"),
+            ("./data", "x", "data/x"),
+            ("", "a/../b", "b"),
+            (".", "a", "a"),
+        ] {
+            assert_eq!(
+                join_rooted(base, path).unwrap(),
+                PathBuf::from(expected),
+                "{base:?} {path:?}"
+            );
+        }
+        for (base, path) in [
+            ("/var/www", ".."),
+            ("/var/www", "a/../../www2"),
+            ("/var/www", "/etc/passwd"),
+            ("data", "../data/x/../../y"),
+            ("", "../etc"),
+            ("", "/etc/passwd"),
+            (".", "a/../../etc"),
+        ] {
+            assert!(
+                matches!(
+                    join_rooted(base, path),
+                    Err(SafePathRejection::TraversalAttack(_))
+                ),
+                "{base:?} {path:?}"
+            );
+        }
+        assert!(ensure_within("/var/www", "/var/www/../www/a").is_ok());
+        assert!(ensure_within("/var/www", "/var/www2/a").is_err());
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .with_state(PathRoot::from("/var/www"));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/css/./site.css").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(
+            PathBuf::from(res.text()),
+            PathBuf::from("/var/www/css/site.css")
+        );
+
+        for uri in ["/..%2Fsecret", "//etc/passwd", "/a%5C..%5C..%5Cb"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn allowed_roots_stay_rooted() {
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(Extension(
+                SafePathConfig::new().allowed_roots(["/var/data"]),
+            ))
+            .with_state(PathRoot::from("/var/www"));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("//var/data/x").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "/var/www/var/data/x");
+    }
+}
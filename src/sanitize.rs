@@ -0,0 +1,201 @@
+//! Fixing unsafe paths instead of rejecting them.
+
+use std::path::{self, PathBuf};
+
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+
+use crate::{
+    SafePath, SafePathConfig, SafePathRejection,
+    component::{WINDOWS_FORBIDDEN_CHARS, is_control_char, is_windows_reserved},
+    config::DEFAULT_MAX_COMPONENT_LENGTH,
+    raw,
+};
+
+/// Turns arbitrary user input into a safe relative path.
+///
+/// The algorithm:
+///
+/// 1. The input is split on both `/` and `\`.
+/// 2. Empty, `.` and `..` components are dropped, `..` is never resolved.
+/// 3. Control, zero-width and bidirectional formatting characters are removed
+///    from each component, and so are `<>:"|?*`.
+/// 4. Leading dots, trailing dots and spaces are trimmed, so no component is
+///    hidden or ends the way Windows would silently change.
+/// 5. Windows device names, like `CON` or `nul.txt`, get a `_` prefix.
+/// 6. Components are truncated to 255 bytes, keeping whole characters.
+/// 7. Components left empty are dropped, and the rest is joined with `/`.
+///
+/// The result may be empty, e.g. for `../..`.
+///
+/// ```
+/// use std::path::Path;
+///
+/// use axum_safe_path::sanitize;
+///
+/// assert_eq!(sanitize("../../etc/passwd"), Path::new("etc/passwd"));
+/// assert_eq!(sanitize("my\\.hidden/ report?.pdf "), Path::new("my/hidden/ report.pdf"));
+/// assert_eq!(sanitize("con.txt"), Path::new("_con.txt"));
+/// ```
+#[must_use]
+pub fn sanitize(path: &str) -> PathBuf {
+    path.split(['/', '\\'])
+        .filter_map(sanitize_component)
+        .collect::<Vec<_>>()
+        .join("/")
+        .into()
+}
+
+/// Sanitizes a single component, returning `None` if nothing is left of it
+pub fn sanitize_component(name: &str) -> Option<String> {
+    let forbidden = |c: char| {
+        is_control_char(c) || u8::try_from(c).is_ok_and(|b| WINDOWS_FORBIDDEN_CHARS.contains(&b))
+    };
+    let cleaned: String = name.chars().filter(|&c| !forbidden(c)).collect();
+    let trimmed = cleaned.trim_start_matches('.').trim_end_matches(['.', ' ']);
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let mut sanitized = if is_windows_reserved(trimmed.as_bytes()) {
+        format!("_{trimmed}")
+    } else {
+        trimmed.to_owned()
+    };
+    if sanitized.len() > DEFAULT_MAX_COMPONENT_LENGTH {
+        let end = (0..=DEFAULT_MAX_COMPONENT_LENGTH)
+            .rev()
+            .find(|&i| sanitized.is_char_boundary(i))
+            .unwrap_or_default();
+        sanitized.truncate(end);
+        // Truncation may leave a trailing dot or space
+        sanitized.truncate(sanitized.trim_end_matches(['.', ' ']).len());
+    }
+    Some(sanitized)
+}
+
+/// A path parameter fixed with [`sanitize`] instead of being rejected.
+///
+/// Meant for UX-sensitive endpoints, like uploads named by users. The
+/// sanitized path is then validated with the current [`SafePathConfig`], as
+/// some of its checks, like the allowed extensions, can't be fixed:
+///
+/// ```
+/// use axum::{Router, routing::put};
+/// use axum_safe_path::SanitizedPath;
+///
+/// async fn upload(SanitizedPath(path): SanitizedPath) -> String {
+///     // `etc/passwd` for `/uploads/..%2F..%2Fetc%2Fpasswd`
+///     path.display().to_string()
+/// }
+///
+/// let app: Router = Router::new().route("/uploads/{*path}", put(upload));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SanitizedPath(pub PathBuf);
+
+impl AsRef<path::Path> for SanitizedPath {
+    fn as_ref(&self) -> &path::Path {
+        self.0.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for SanitizedPath
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(path) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(SafePathRejection::from)?;
+        let raw = raw::capture(&parts.uri, path::Path::new(&path));
+        let SafePath(path) =
+            SafePathConfig::validate_current(&mut parts.extensions, raw, |config| {
+                config.validate(sanitize(&path))
+            })?;
+        Ok(Self(path))
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    #[test]
+    fn sanitizes() {
+        for (input, expected) in [
+            ("a/b.txt", "a/b.txt"),
+            ("", ""),
+            ("../..", ""),
+            ("/etc/passwd", "etc/passwd"),
+            ("a/../../b", "a/b"),
+            ("..\\..\\windows\\win.ini", "windows/win.ini"),
+            ("C:\\boot.ini", "C/boot.ini"),
+            (".env", "env"),
+            ("...", ""),
+            ("name. . ", "name"),
+            ("a\u{0}b\u{202E}c", "abc"),
+            ("file.txt::$DATA", "file.txt$DATA"),
+            ("NUL", "_NUL"),
+            ("lpt1.log", "_lpt1.log"),
+            ("\\\\server\\share\\x", "server/share/x"),
+        ] {
+            assert_eq!(sanitize(input), PathBuf::from(expected), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn sanitized_is_safe() {
+        let config = SafePathConfig::windows_safe()
+            .deny_hidden(true)
+            .deny_control_chars(true);
+        for input in [
+            "../a",
+            "a/./b",
+            "\u{FEFF}.env",
+            "x:y",
+            "aux",
+            "a /b.",
+            "\\\\?\\C:\\",
+        ] {
+            assert!(config.check(sanitize(input)).is_ok(), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn truncates() {
+        let long = format!("{}.txt", "\u{e9}".repeat(200));
+        let sanitized = sanitize(&long);
+        let name = sanitized.to_str().unwrap();
+        assert!(name.len() <= DEFAULT_MAX_COMPONENT_LENGTH);
+        assert!(name.chars().all(|c| c == '\u{e9}'));
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        async fn handler(SanitizedPath(path): SanitizedPath) -> String {
+            path.display().to_string()
+        }
+
+        let app = Router::new()
+            .route("/{*path}", get(handler))
+            .layer(Extension(SafePathConfig::new().allowed_extensions(["pdf"])));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/..%2F..%2Freports%2F.q1%3F.pdf").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "reports/q1.pdf");
+
+        let res = server.get("/script.php").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,417 @@
+//! Atomic file writes confined to a root directory.
+
+use std::{
+    error::Error,
+    ffi::OsStr,
+    future::poll_fn,
+    io,
+    path::{Component, Path, PathBuf},
+    pin::pin,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::body::Bytes;
+use futures_core::Stream;
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncRead, AsyncWriteExt},
+};
+
+use crate::{
+    SafePath,
+    fs::{checked, symlink_error},
+};
+
+#[cfg_attr(docsrs, doc(cfg(feature = "fs")))]
+impl SafePath {
+    /// Atomically writes the contents to the file at the path relative to the
+    /// root, returning the full path of the file
+    ///
+    /// The contents are written to a temporary file in the same directory,
+    /// which is then renamed into place, so readers never see a partially
+    /// written file. Missing parent directories are created, while symlinks
+    /// on the way are refused, so nothing is written outside of the root:
+    ///
+    /// ```
+    /// use axum::{Router, body::Bytes, http::StatusCode, routing::put};
+    /// use axum_safe_path::SafePath;
+    ///
+    /// async fn upload(path: SafePath, body: Bytes) -> StatusCode {
+    ///     match path.save_in("/var/uploads", body).await {
+    ///         Ok(_) => StatusCode::CREATED,
+    ///         Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    ///     }
+    /// }
+    ///
+    /// let app: Router = Router::new().route("/files/{*path}", put(upload));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path has no file name, goes through a symlink or the file
+    /// can't be written.
+    pub async fn save_in(
+        &self,
+        root: impl AsRef<Path>,
+        contents: impl AsRef<[u8]>,
+    ) -> io::Result<PathBuf> {
+        let (mut file, staged) = Staged::create(root.as_ref(), self).await?;
+        let written = file.write_all(contents.as_ref()).await;
+        staged.finish(file, written).await
+    }
+
+    /// Atomically writes everything read from the reader to the file at the
+    /// path relative to the root, see [`save_in`](Self::save_in)
+    ///
+    /// # Errors
+    ///
+    /// Fails if the path has no file name, goes through a symlink, the reader
+    /// fails or the file can't be written.
+    pub async fn save_reader_in(
+        &self,
+        root: impl AsRef<Path>,
+        mut reader: impl AsyncRead + Unpin,
+    ) -> io::Result<PathBuf> {
+        let (mut file, staged) = Staged::create(root.as_ref(), self).await?;
+        let written = tokio::io::copy(&mut reader, &mut file).await.map(drop);
+        staged.finish(file, written).await
+    }
+
+    /// Atomically writes the stream, like a request body or a multipart field,
+    /// to the file at the path relative to the root, see
+    /// [`save_in`](Self::save_in)
+    ///
+    /// Writing is aborted and the partial file is removed as soon as the
+    /// stream exceeds `max_size` bytes:
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    ///
+    /// use axum::{Router, body::Body, http::StatusCode, routing::put};
+    /// use axum_safe_path::SafePath;
+    ///
+    /// async fn upload(path: SafePath, body: Body) -> StatusCode {
+    ///     let stream = body.into_data_stream();
+    ///     match path.save_stream_in("/var/uploads", stream, 10 << 20).await {
+    ///         Ok(_) => StatusCode::CREATED,
+    ///         Err(err) if err.kind() == ErrorKind::FileTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+    ///         Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    ///     }
+    /// }
+    ///
+    /// let app: Router = Router::new().route("/files/{*path}", put(upload));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`io::ErrorKind::FileTooLarge`] if the stream is too long,
+    /// and for the same reasons as [`save_in`](Self::save_in) or if the
+    /// stream fails.
+    pub async fn save_stream_in<S, E>(
+        &self,
+        root: impl AsRef<Path>,
+        stream: S,
+        max_size: u64,
+    ) -> io::Result<PathBuf>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        let (mut file, staged) = Staged::create(root.as_ref(), self).await?;
+        let written = write_stream(&mut file, stream, max_size).await;
+        staged.finish(file, written).await
+    }
+}
+
+/// Writes the stream to the file, failing if it's longer than the limit
+async fn write_stream<S, E>(file: &mut File, stream: S, max_size: u64) -> io::Result<()>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    let mut stream = pin!(stream);
+    let mut size = 0_u64;
+    while let Some(chunk) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await {
+        let chunk = chunk.map_err(io::Error::other)?;
+        size = size.saturating_add(chunk.len() as u64);
+        if size > max_size {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("the file is larger than {max_size} bytes"),
+            ));
+        }
+        file.write_all(&chunk).await?;
+    }
+    Ok(())
+}
+
+/// A temporary file waiting to be renamed into place
+#[derive(Debug)]
+pub struct Staged {
+    temp: PathBuf,
+    target: PathBuf,
+}
+
+impl Staged {
+    /// Creates the parent directories and the temporary file next to the
+    /// target
+    pub async fn create(root: &Path, path: &SafePath) -> io::Result<(File, Self)> {
+        let target = create_parents(root, path).await?;
+        let dir = target.parent().unwrap_or(root);
+        let name = target
+            .file_name()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let (file, temp) = create_unique(dir, &name.to_string_lossy()).await?;
+        Ok((file, Self { temp, target }))
+    }
+
+    /// Renames the temporary file into place if writing succeeded, removing
+    /// it otherwise
+    pub async fn finish(self, file: File, written: io::Result<()>) -> io::Result<PathBuf> {
+        let result = async {
+            written?;
+            file.sync_all().await?;
+            drop(file);
+            tokio::fs::rename(&self.temp, &self.target).await
+        }
+        .await;
+        if let Err(err) = result {
+            // The original error is more useful than a failed cleanup
+            tokio::fs::remove_file(&self.temp).await.ok();
+            return Err(err);
+        }
+        Ok(self.target)
+    }
+}
+
+/// Creates missing parent directories of the path relative to the root,
+/// refusing to go through symlinks, and returns the full path
+pub async fn create_parents(root: &Path, path: &SafePath) -> io::Result<PathBuf> {
+    let names: Vec<_> = checked(path)?
+        .0
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    let Some((file_name, dirs)) = names.split_last() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the path has no file name",
+        ));
+    };
+
+    let mut current = create_dirs(root, dirs).await?;
+    current.push(file_name);
+    Ok(current)
+}
+
+/// Creates the nested directories under the root, refusing to go through
+/// symlinks, and returns the full path of the last one
+pub async fn create_dirs(root: &Path, dirs: &[&OsStr]) -> io::Result<PathBuf> {
+    let mut current = root.to_path_buf();
+    for dir in dirs {
+        current.push(dir);
+        match tokio::fs::create_dir(&current).await {
+            Err(err) if err.kind() != io::ErrorKind::AlreadyExists => return Err(err),
+            _ => (),
+        }
+        // An existing entry may be a symlink or a file
+        let metadata = tokio::fs::symlink_metadata(&current).await?;
+        if metadata.file_type().is_symlink() {
+            return Err(symlink_error());
+        }
+        if !metadata.is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+    }
+    Ok(current)
+}
+
+/// The longest part of the original name kept in temporary names, leaving
+/// room for the unique suffix within the usual limit of 255 bytes
+const MAX_TEMP_PREFIX: usize = 200;
+
+/// Creates a new file with a unique name based on the given one in the
+/// directory
+pub async fn create_unique(dir: &Path, name: &str) -> io::Result<(File, PathBuf)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let name = truncate(name, MAX_TEMP_PREFIX);
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.subsec_nanos());
+        let unique = format!(
+            ".{name}.{}-{}-{nanos:x}.tmp",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(unique);
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .await
+        {
+            Ok(file) => return Ok((file, path)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Truncates the string to at most `max` bytes at a char boundary
+fn truncate(s: &str, max: usize) -> &str {
+    let end = s
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .take_while(|&end| end <= max)
+        .last()
+        .unwrap_or(0);
+    s.get(..end).unwrap_or_default()
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn save() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path: SafePath = "a/b/c.txt".parse().unwrap();
+
+        let saved = path.save_in(tmp.path(), "first").await.unwrap();
+        assert_eq!(saved, tmp.path().join("a/b/c.txt"));
+        path.save_in(tmp.path(), b"second").await.unwrap();
+        assert_eq!(std::fs::read_to_string(&saved).unwrap(), "second");
+
+        path.save_reader_in(tmp.path(), &b"third"[..])
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&saved).unwrap(), "third");
+        // No temporary files are left
+        assert_eq!(files(&tmp.path().join("a/b")), ["c.txt"]);
+
+        let empty: SafePath = "".parse().unwrap();
+        assert_eq!(
+            empty.save_in(tmp.path(), "x").await.unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        let through_file: SafePath = "a/b/c.txt/d".parse().unwrap();
+        assert!(through_file.save_in(tmp.path(), "x").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn unvalidated_path() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+
+        let path = SafePath("../x".into());
+        let err = path.save_in(&root, "x").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(files(&root).is_empty());
+        assert_eq!(files(tmp.path()), ["root"]);
+    }
+
+    #[tokio::test]
+    async fn long_name() {
+        let tmp = tempfile::tempdir().unwrap();
+        let name = format!("{}.txt", "é".repeat(125));
+        assert_eq!(name.len(), 254);
+
+        let path: SafePath = name.parse().unwrap();
+        path.save_in(tmp.path(), "x").await.unwrap();
+        assert_eq!(files(tmp.path()), [name]);
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate("abc", 5), "abc");
+        assert_eq!(truncate("abc", 2), "ab");
+        assert_eq!(truncate("aé", 2), "a");
+        assert_eq!(truncate("é", 1), "");
+    }
+
+    #[tokio::test]
+    async fn cleanup() {
+        struct Failing;
+
+        impl AsyncRead for Failing {
+            fn poll_read(
+                self: std::pin::Pin<&mut Self>,
+                _cx: &mut std::task::Context<'_>,
+                _buf: &mut tokio::io::ReadBuf<'_>,
+            ) -> std::task::Poll<io::Result<()>> {
+                std::task::Poll::Ready(Err(io::Error::other("broken")))
+            }
+        }
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path: SafePath = "a.txt".parse().unwrap();
+        assert!(path.save_reader_in(tmp.path(), Failing).await.is_err());
+        assert!(files(tmp.path()).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        std::fs::create_dir_all(tmp.path().join("outside")).unwrap();
+        std::fs::create_dir(&root).unwrap();
+        std::os::unix::fs::symlink("../outside", root.join("link")).unwrap();
+
+        let path: SafePath = "link/a.txt".parse().unwrap();
+        let err = path.save_in(&root, "x").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(files(&tmp.path().join("outside")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn stream_limit() {
+        use axum::{Router, body::Body, http::StatusCode, routing::put};
+        use axum_test::TestServer;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().to_path_buf();
+        let app = Router::new().route(
+            "/{*path}",
+            put(move |path: SafePath, body: Body| async move {
+                match path.save_stream_in(&root, body.into_data_stream(), 8).await {
+                    Ok(_) => StatusCode::CREATED,
+                    Err(err) if err.kind() == io::ErrorKind::FileTooLarge => {
+                        StatusCode::PAYLOAD_TOO_LARGE
+                    }
+                    Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                }
+            }),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.put("/docs/a.txt").text("12345678").await;
+        assert_eq!(res.status_code(), StatusCode::CREATED);
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join("docs/a.txt")).unwrap(),
+            "12345678"
+        );
+
+        let res = server.put("/docs/b.txt").text("123456789").await;
+        assert_eq!(res.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(files(&tmp.path().join("docs")), ["a.txt"]);
+    }
+}
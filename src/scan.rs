@@ -0,0 +1,117 @@
+//! Single-pass scanning of path bytes.
+
+use std::iter;
+
+/// What a single scan of the path bytes found
+///
+/// The special bytes and separators are located with the vectorized search of
+/// `memchr`, so the checks going component by component can be skipped when
+/// they can't fail, which matters for catch-all routes receiving long paths.
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Scan {
+    /// The path has a NUL byte
+    pub nul: bool,
+    /// The path has a backslash
+    pub backslash: bool,
+    /// The path has a percent sign
+    pub percent: bool,
+    /// A segment starts with a dot, as `.`, `..` and hidden names do
+    dot_segment: bool,
+    /// The number of non-empty segments
+    segments: usize,
+    /// The length of the longest segment
+    max_segment_len: usize,
+}
+
+impl Scan {
+    /// Scans the bytes of the path
+    pub fn new(bytes: &[u8]) -> Self {
+        let mut scan = Self::default();
+        for i in memchr::memchr3_iter(0, b'\\', b'%', bytes) {
+            match bytes.get(i) {
+                Some(0) => scan.nul = true,
+                Some(b'\\') => scan.backslash = true,
+                _ => scan.percent = true,
+            }
+        }
+
+        let mut start = 0;
+        for end in memchr::memchr_iter(b'/', bytes).chain(iter::once(bytes.len())) {
+            let segment = bytes.get(start..end).unwrap_or_default();
+            if !segment.is_empty() {
+                scan.segments += 1;
+                scan.max_segment_len = scan.max_segment_len.max(segment.len());
+                scan.dot_segment |= segment.first() == Some(&b'.');
+            }
+            start = end + 1;
+        }
+        scan
+    }
+
+    /// Checks if a component may start with a dot
+    ///
+    /// On Windows backslashes separate components too, so it's always true.
+    pub const fn may_have_dot_component(&self) -> bool {
+        cfg!(windows) || self.dot_segment
+    }
+
+    /// Checks if a component may be longer than `max` bytes
+    pub const fn may_have_component_longer_than(&self, max: usize) -> bool {
+        self.max_segment_len > max
+    }
+
+    /// The number of normal components if it's known without parsing them
+    ///
+    /// Without `.` and `..`, every non-empty segment is a normal component.
+    pub const fn depth(&self) -> Option<usize> {
+        if self.may_have_dot_component() {
+            None
+        } else {
+            Some(self.segments)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::config;
+
+    #[test]
+    fn flags() {
+        let scan = Scan::new(b"a/b%20c\\d\0");
+        assert!(scan.nul && scan.backslash && scan.percent);
+        let scan = Scan::new(b"a/b/c.txt");
+        assert!(!scan.nul && !scan.backslash && !scan.percent);
+    }
+
+    #[test]
+    fn components() {
+        for (path, dot) in [
+            ("", false),
+            ("/", false),
+            ("a", false),
+            ("/a//b/", false),
+            ("a/b/c.txt", false),
+            ("a/.env", true),
+            ("a/./b", true),
+            ("../a", true),
+        ] {
+            let scan = Scan::new(path.as_bytes());
+            assert_eq!(
+                scan.may_have_dot_component(),
+                cfg!(windows) || dot,
+                "{path:?}"
+            );
+            if let Some(depth) = scan.depth() {
+                assert_eq!(depth, config::depth(Path::new(path)), "{path:?}");
+            }
+        }
+        let scan = Scan::new(b"ab/abcd/abc");
+        assert!(scan.may_have_component_longer_than(3));
+        assert!(!scan.may_have_component_longer_than(4));
+    }
+}
@@ -0,0 +1,662 @@
+//! Serving static files from a directory.
+
+use std::{
+    convert::Infallible,
+    future::Future,
+    io::{self, SeekFrom},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+use tower_service::Service;
+
+use self::conditional::{Range, Validators};
+use crate::{SafePath, SafePathConfig, SafePathRejection, secure_open, uri};
+
+mod conditional;
+
+/// A service serving files from a directory, like `ServeDir` of `tower-http`,
+/// but validating the request path with [`SafePathConfig`] first.
+///
+/// Invalid paths are rejected with `400 Bad Request` before touching the
+/// filesystem, or with `404 Not Found` if [`not_found_on_invalid`] is set.
+/// Files are opened with [`secure_open`], so symlinks aren't followed.
+/// Directories and missing files give `404 Not Found`, unless [`index`] or
+/// [`fallback`] are set. Conditional and range requests are supported the same
+/// way as with [`serve_file`]:
+///
+/// ```
+/// use axum::Router;
+/// use axum_safe_path::{SafePathConfig, SafeServeDir};
+///
+/// let app: Router = Router::new()
+///     .nest_service(
+///         "/static",
+///         SafeServeDir::new("assets").config(SafePathConfig::new().deny_hidden(true)),
+///     )
+///     // A single-page app with client-side routing
+///     .fallback_service(
+///         SafeServeDir::new("dist")
+///             .index("index.html")
+///             .fallback("index.html"),
+///     );
+/// ```
+///
+/// [`not_found_on_invalid`]: Self::not_found_on_invalid
+/// [`index`]: Self::index
+/// [`fallback`]: Self::fallback
+#[derive(Debug, Clone)]
+pub struct SafeServeDir {
+    root: Arc<Path>,
+    config: SafePathConfig,
+    not_found_on_invalid: bool,
+    index: Option<Arc<Path>>,
+    fallback: Option<Arc<Path>>,
+}
+
+impl SafeServeDir {
+    /// Creates a service serving files from the root directory
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into().into(),
+            config: SafePathConfig::new(),
+            not_found_on_invalid: false,
+            index: None,
+            fallback: None,
+        }
+    }
+
+    /// Sets the config request paths are checked with
+    #[must_use]
+    pub fn config(mut self, config: SafePathConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Responds to invalid paths with `404 Not Found` instead of
+    /// `400 Bad Request`, hiding the reason
+    #[must_use]
+    pub const fn not_found_on_invalid(mut self, enable: bool) -> Self {
+        self.not_found_on_invalid = enable;
+        self
+    }
+
+    /// Serves the file with the name, like `index.html`, for directories
+    #[must_use]
+    pub fn index(mut self, name: impl Into<PathBuf>) -> Self {
+        self.index = Some(name.into().into());
+        self
+    }
+
+    /// Serves the file at the path relative to the root for missing files,
+    /// like `index.html` of a single-page app
+    ///
+    /// Invalid paths are still rejected.
+    #[must_use]
+    pub fn fallback(mut self, path: impl Into<PathBuf>) -> Self {
+        self.fallback = Some(path.into().into());
+        self
+    }
+
+    /// Validates the percent-encoded request path without the leading slash,
+    /// tracing it and storing the outcome like the extractors do
+    fn validate(&self, parts: &mut Parts) -> Result<SafePath, SafePathRejection> {
+        let path = parts.uri.path();
+        self.config
+            .validate_with(&mut parts.extensions, path, |config| {
+                uri::validate_request_path(config, path)
+            })
+    }
+
+    /// Responds to the request
+    async fn serve(self, mut parts: Parts) -> Response {
+        let method = parts.method.clone();
+        if method != Method::GET && method != Method::HEAD {
+            return (
+                StatusCode::METHOD_NOT_ALLOWED,
+                [(header::ALLOW, HeaderValue::from_static("GET, HEAD"))],
+            )
+                .into_response();
+        }
+        let path = match self.validate(&mut parts) {
+            Ok(path) => path,
+            Err(_) if self.not_found_on_invalid => return StatusCode::NOT_FOUND.into_response(),
+            Err(rejection) => return rejection.into_response(),
+        };
+        let opened = match open(&self.root, path, self.index.as_deref()).await {
+            Err(err) if is_not_found(&err) => match &self.fallback {
+                Some(fallback) => open(&self.root, SafePath(fallback.to_path_buf()), None).await,
+                None => Err(err),
+            },
+            opened => opened,
+        };
+        match opened {
+            Ok((file, path)) => {
+                file_response(
+                    file,
+                    content_type(&path.0),
+                    &parts.headers,
+                    method == Method::HEAD,
+                )
+                .await
+            }
+            Err(err) => error_response(&err),
+        }
+    }
+}
+
+impl<B> Service<Request<B>> for SafeServeDir
+where
+    B: Send + 'static,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let this = self.clone();
+        let (parts, _) = req.into_parts();
+        Box::pin(async move { Ok(this.serve(parts).await) })
+    }
+}
+
+/// Extends [`Router`] with mounting of [`SafeServeDir`].
+///
+/// It's the shortest way to serve a static directory safely:
+///
+/// ```
+/// use axum::Router;
+/// use axum_safe_path::{
+///     SafePathConfig, SafeRouterExt,
+///     policy::{FileExtension, PolicyExt},
+/// };
+///
+/// let app: Router = Router::new()
+///     .serve_safe_dir("/static", "assets")
+///     .serve_safe_dir_with(
+///         "/downloads",
+///         "public",
+///         SafePathConfig::new().policy(FileExtension("pdf").or(FileExtension("zip"))),
+///     );
+/// ```
+pub trait SafeRouterExt: Sized {
+    /// Serves the files of the root directory under the path
+    ///
+    /// Hidden and sensitive files are denied, see
+    /// [`deny_hidden`](SafePathConfig::deny_hidden) and
+    /// [`deny_sensitive`](SafePathConfig::deny_sensitive).
+    #[must_use]
+    fn serve_safe_dir(self, path: &str, root: impl Into<PathBuf>) -> Self {
+        let config = SafePathConfig::new().deny_hidden(true).deny_sensitive(true);
+        self.serve_safe_dir_with(path, root, config)
+    }
+
+    /// Serves the files of the root directory under the path, checking
+    /// request paths with the config and its
+    /// [`policies`](SafePathConfig::policy)
+    ///
+    /// Mounting at `/` sets the fallback of the router, as axum doesn't nest
+    /// services at the root.
+    #[must_use]
+    fn serve_safe_dir_with(
+        self,
+        path: &str,
+        root: impl Into<PathBuf>,
+        config: SafePathConfig,
+    ) -> Self;
+}
+
+impl<S> SafeRouterExt for Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn serve_safe_dir_with(
+        self,
+        path: &str,
+        root: impl Into<PathBuf>,
+        config: SafePathConfig,
+    ) -> Self {
+        let service = SafeServeDir::new(root).config(config);
+        match path.trim_end_matches('/') {
+            "" => self.fallback_service(service),
+            path => self.nest_service(path, service),
+        }
+    }
+}
+
+/// Streams the file at the path relative to the root.
+///
+/// It's a one-line download handler: the file is opened with [`secure_open`],
+/// so symlinks aren't followed, and missing files, directories and other
+/// errors give an empty `404 Not Found` or `500 Internal Server Error`
+/// without exposing server paths. The `Content-Type` is guessed from the
+/// extension with the `mime_guess` feature and is `application/octet-stream`
+/// otherwise.
+///
+/// Responses have `ETag` and `Last-Modified` headers. Passing the request
+/// headers enables `304 Not Modified` responses to `If-None-Match` and
+/// `If-Modified-Since`, and `206 Partial Content` ones to a single `Range`,
+/// optionally guarded with `If-Range`:
+///
+/// ```
+/// use axum::{Router, http::HeaderMap, response::Response, routing::get};
+/// use axum_safe_path::{SafePath, serve_file};
+///
+/// async fn download(path: SafePath, headers: HeaderMap) -> Response {
+///     serve_file("/var/www", &path, &headers).await
+/// }
+///
+/// let app: Router = Router::new().route("/files/{*path}", get(download));
+/// ```
+pub async fn serve_file(root: impl AsRef<Path>, path: &SafePath, headers: &HeaderMap) -> Response {
+    serve_path(root.as_ref(), path, headers, false).await
+}
+
+/// Opens and streams the file, without the body for `HEAD` requests
+async fn serve_path(root: &Path, path: &SafePath, headers: &HeaderMap, head: bool) -> Response {
+    match open(root, path.clone(), None).await {
+        Ok((file, path)) => file_response(file, content_type(&path.0), headers, head).await,
+        Err(err) => error_response(&err),
+    }
+}
+
+/// Opens the file, or the index file in it if it's a directory, returning
+/// the path of the opened one
+async fn open(root: &Path, path: SafePath, index: Option<&Path>) -> io::Result<(File, SafePath)> {
+    let file = secure_open(root, &path).await?;
+    if !file.metadata().await?.is_dir() {
+        return Ok((file, path));
+    }
+    let Some(index) = index else {
+        return Err(io::ErrorKind::NotFound.into());
+    };
+    let path = SafePath(path.0.join(index));
+    Ok((secure_open(root, &path).await?, path))
+}
+
+/// Guesses the content type from the file extension
+#[cfg(feature = "mime_guess")]
+fn content_type(path: &Path) -> HeaderValue {
+    mime_guess::from_path(path).first_raw().map_or_else(
+        || HeaderValue::from_static(OCTET_STREAM),
+        HeaderValue::from_static,
+    )
+}
+
+/// Guessing requires the `mime_guess` feature
+#[cfg(not(feature = "mime_guess"))]
+const fn content_type(_path: &Path) -> HeaderValue {
+    HeaderValue::from_static(OCTET_STREAM)
+}
+
+/// The content type of arbitrary binary data
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Streams the requested part of the opened file, responding with
+/// `404 Not Found` if it isn't a regular file
+async fn file_response(
+    mut file: File,
+    content_type: HeaderValue,
+    request_headers: &HeaderMap,
+    head: bool,
+) -> Response {
+    let metadata = match file.metadata().await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => return error_response(&err),
+    };
+    let len = metadata.len();
+    let validators = Validators::new(&metadata);
+    let mut headers = HeaderMap::new();
+    validators.insert_headers(&mut headers);
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if validators.is_not_modified(request_headers) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    let (status, start, length) = match validators.range(request_headers, len) {
+        Range::Full => (StatusCode::OK, 0, len),
+        Range::Partial(start, end) => {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes {start}-{end}/{len}")) {
+                headers.insert(header::CONTENT_RANGE, value);
+            }
+            (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+        }
+        Range::Unsatisfiable => {
+            if let Ok(value) = HeaderValue::from_str(&format!("bytes */{len}")) {
+                headers.insert(header::CONTENT_RANGE, value);
+            }
+            return (StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response();
+        }
+    };
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    headers.insert(header::CONTENT_TYPE, content_type);
+
+    let body = if head {
+        Body::empty()
+    } else {
+        if start > 0
+            && let Err(err) = file.seek(SeekFrom::Start(start)).await
+        {
+            return error_response(&err);
+        }
+        Body::from_stream(ReaderStream::new(file.take(length)))
+    };
+    (status, headers, body).into_response()
+}
+
+/// Converts an error of opening a file into a response without exposing the
+/// details
+fn error_response(err: &io::Error) -> Response {
+    if is_not_found(err) {
+        StatusCode::NOT_FOUND
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+    .into_response()
+}
+
+/// Checks if the file is missing or can't be served, like symlinks
+fn is_not_found(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::NotFound | io::ErrorKind::NotADirectory | io::ErrorKind::PermissionDenied
+    )
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::Router;
+    use axum_test::TestServer;
+
+    use super::*;
+
+    fn root() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("css")).unwrap();
+        std::fs::write(dir.path().join("css/site.css"), "body {}").unwrap();
+        std::fs::write(dir.path().join(".env"), "SECRET=1").unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn router_ext() {
+        let dir = root();
+        std::fs::write(dir.path().join("report.pdf"), "%PDF").unwrap();
+        let config = SafePathConfig::new().policy(crate::policy::FileExtension("pdf"));
+        let app = Router::new()
+            .serve_safe_dir("/static", dir.path())
+            .serve_safe_dir_with("/", dir.path(), config);
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/static/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "body {}");
+        let res = server.get("/static/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        let res = server.get("/static/css/..%2F..%2Fsecret").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+
+        let res = server.get("/report.pdf").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "%PDF");
+        let res = server.get("/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn serves_files() {
+        let dir = root();
+        let app = Router::new().nest_service(
+            "/static",
+            SafeServeDir::new(dir.path()).config(SafePathConfig::new().deny_hidden(true)),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/static/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "body {}");
+        assert_eq!(res.header(header::CONTENT_LENGTH), "7");
+
+        let res = server.method(Method::HEAD, "/static/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.header(header::CONTENT_LENGTH), "7");
+        assert!(res.text().is_empty());
+
+        for uri in [
+            "/static/css",
+            "/static/missing.css",
+            "/static/css/site.css/x",
+        ] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::NOT_FOUND, "{uri}");
+        }
+        for uri in [
+            "/static/.env",
+            "/static/css%2F..%2F.env",
+            "/static/%2Fetc%2Fpasswd",
+        ] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+
+        let res = server.get("/static/css/site.css").await;
+        #[cfg(feature = "mime_guess")]
+        assert_eq!(res.header(header::CONTENT_TYPE), "text/css");
+        #[cfg(not(feature = "mime_guess"))]
+        assert_eq!(res.header(header::CONTENT_TYPE), OCTET_STREAM);
+
+        let res = server.post("/static/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn not_found_on_invalid() {
+        let dir = root();
+        let service = SafeServeDir::new(dir.path())
+            .config(SafePathConfig::new().deny_hidden(true))
+            .not_found_on_invalid(true);
+        let server = TestServer::new(Router::new().fallback_service(service)).unwrap();
+
+        let res = server.get("/.env").await;
+        assert_eq!(res.status_code(), StatusCode::NOT_FOUND);
+        assert!(res.text().is_empty());
+    }
+
+    #[tokio::test]
+    async fn on_rejection() {
+        let dir = root();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let service = SafeServeDir::new(dir.path()).config(SafePathConfig::new().deny_hidden(true));
+        let app = Router::new().fallback_service(service).layer(
+            crate::SafePathPolicyLayer::new(SafePathConfig::new()).on_rejection(move |event| {
+                let tx = tx.clone();
+                async move {
+                    tx.send(event).unwrap();
+                }
+            }),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/css/.env").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+        let event = rx.try_recv().unwrap();
+        assert!(matches!(*event.rejection, SafePathRejection::Hidden));
+        assert_eq!(event.value.unwrap().as_str(), "/css/.env");
+
+        let res = server.get("/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn serve_file_handler() {
+        let dir = root();
+        let root = dir.path().to_path_buf();
+        let app = Router::new().route(
+            "/{*path}",
+            axum::routing::get(move |path: SafePath, headers: HeaderMap| async move {
+                serve_file(&root, &path, &headers).await
+            }),
+        );
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/css/site.css").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "body {}");
+
+        let res = server.get("/css/missing.css").await;
+        assert_eq!(res.status_code(), StatusCode::NOT_FOUND);
+
+        let res = server.get("/%2Fetc%2Fpasswd").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn conditional() {
+        let dir = root();
+        let server =
+            TestServer::new(Router::new().fallback_service(SafeServeDir::new(dir.path()))).unwrap();
+
+        let res = server.get("/css/site.css").await;
+        let etag = res.header(header::ETAG);
+        let modified = res.header(header::LAST_MODIFIED);
+        assert_eq!(res.header(header::ACCEPT_RANGES), "bytes");
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::IF_NONE_MATCH, etag.clone())
+            .await;
+        assert_eq!(res.status_code(), StatusCode::NOT_MODIFIED);
+        assert!(res.text().is_empty());
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::IF_NONE_MATCH, "\"other\", *")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::NOT_MODIFIED);
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::IF_NONE_MATCH, "\"other\"")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::IF_MODIFIED_SINCE, modified.clone())
+            .await;
+        assert_eq!(res.status_code(), StatusCode::NOT_MODIFIED);
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn ranges() {
+        let dir = root();
+        let server =
+            TestServer::new(Router::new().fallback_service(SafeServeDir::new(dir.path()))).unwrap();
+        let etag = server.get("/css/site.css").await.header(header::ETAG);
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::RANGE, "bytes=2-5")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.text(), "dy {");
+        assert_eq!(res.header(header::CONTENT_RANGE), "bytes 2-5/7");
+        assert_eq!(res.header(header::CONTENT_LENGTH), "4");
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::RANGE, "bytes=-2")
+            .await;
+        assert_eq!(res.text(), "{}");
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::RANGE, "bytes=7-")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(res.header(header::CONTENT_RANGE), "bytes */7");
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::RANGE, "bytes=0-0")
+            .add_header(header::IF_RANGE, etag)
+            .await;
+        assert_eq!(res.status_code(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(res.text(), "b");
+
+        let res = server
+            .get("/css/site.css")
+            .add_header(header::RANGE, "bytes=0-0")
+            .add_header(header::IF_RANGE, "\"stale\"")
+            .await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "body {}");
+    }
+
+    #[tokio::test]
+    async fn index_and_fallback() {
+        let dir = root();
+        std::fs::write(dir.path().join("index.html"), "app").unwrap();
+        std::fs::write(dir.path().join("css/index.html"), "css").unwrap();
+
+        let server = TestServer::new(
+            Router::new().fallback_service(SafeServeDir::new(dir.path()).index("index.html")),
+        )
+        .unwrap();
+        for (uri, expected) in [("/", "app"), ("/css", "css"), ("/css/", "css")] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::OK, "{uri}");
+            assert_eq!(res.text(), expected, "{uri}");
+        }
+        let res = server.get("/users/1").await;
+        assert_eq!(res.status_code(), StatusCode::NOT_FOUND);
+
+        let service = SafeServeDir::new(dir.path())
+            .config(SafePathConfig::new().deny_hidden(true))
+            .fallback("index.html");
+        let server = TestServer::new(Router::new().fallback_service(service)).unwrap();
+        for uri in ["/users/1", "/css"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::OK, "{uri}");
+            assert_eq!(res.text(), "app", "{uri}");
+        }
+        #[cfg(feature = "mime_guess")]
+        assert_eq!(
+            server.get("/users/1").await.header(header::CONTENT_TYPE),
+            "text/html"
+        );
+        let res = server.get("/css/site.css").await;
+        assert_eq!(res.text(), "body {}");
+        for uri in ["/.env", "/a%2F..%2F..%2Fsecret"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+}
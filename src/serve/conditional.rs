@@ -0,0 +1,175 @@
+//! Conditional requests and byte ranges of served files.
+
+use std::{fs::Metadata, time::UNIX_EPOCH};
+
+use axum::http::{HeaderMap, HeaderValue, header};
+use httpdate::HttpDate;
+
+/// The validators of a file used to answer conditional requests
+#[derive(Debug)]
+pub struct Validators {
+    etag: Option<String>,
+    modified: Option<HttpDate>,
+}
+
+/// The part of a file to respond with
+#[derive(Debug, PartialEq, Eq)]
+pub enum Range {
+    /// The whole file
+    Full,
+    /// The inclusive byte range
+    Partial(u64, u64),
+    /// The requested range is outside of the file
+    Unsatisfiable,
+}
+
+impl Validators {
+    /// Computes the validators from the size and modification time of the
+    /// file, which are unavailable if the platform doesn't report the latter
+    pub fn new(metadata: &Metadata) -> Self {
+        let modified = metadata.modified().ok();
+        let etag = modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since| format!("\"{:x}-{:x}\"", metadata.len(), since.as_nanos()));
+        Self {
+            etag,
+            modified: modified.map(HttpDate::from),
+        }
+    }
+
+    /// Adds the `ETag` and `Last-Modified` headers
+    pub fn insert_headers(&self, headers: &mut HeaderMap) {
+        if let Some(etag) = self
+            .etag
+            .as_deref()
+            .and_then(|e| HeaderValue::from_str(e).ok())
+        {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(modified) = self
+            .modified
+            .and_then(|date| HeaderValue::from_str(&date.to_string()).ok())
+        {
+            headers.insert(header::LAST_MODIFIED, modified);
+        }
+    }
+
+    /// Checks `If-None-Match`, or `If-Modified-Since` without it, to see if
+    /// the client already has the file
+    pub fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if headers.contains_key(header::IF_NONE_MATCH) {
+            let Some(etag) = &self.etag else {
+                return false;
+            };
+            return headers
+                .get_all(header::IF_NONE_MATCH)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_eq(tag, etag));
+        }
+        match (self.modified, http_date(headers, header::IF_MODIFIED_SINCE)) {
+            (Some(modified), Some(since)) => modified <= since,
+            _ => false,
+        }
+    }
+
+    /// Selects the part of the file requested with `Range`, ignoring it if
+    /// `If-Range` doesn't match
+    ///
+    /// Only a single range is supported, requests for several ones get the
+    /// whole file.
+    pub fn range(&self, headers: &HeaderMap, len: u64) -> Range {
+        let Some(range) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+            return Range::Full;
+        };
+        if !self.if_range_matches(headers) {
+            return Range::Full;
+        }
+        parse_range(range, len)
+    }
+
+    /// Checks that `If-Range` is missing or matches the file exactly
+    fn if_range_matches(&self, headers: &HeaderMap) -> bool {
+        let Some(value) = headers.get(header::IF_RANGE) else {
+            return true;
+        };
+        let Ok(value) = value.to_str() else {
+            return false;
+        };
+        if value.starts_with('"') {
+            self.etag.as_deref() == Some(value)
+        } else {
+            self.modified.is_some() && value.parse::<HttpDate>().ok() == self.modified
+        }
+    }
+}
+
+/// Compares entity tags ignoring the weakness flag
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+/// Parses the date in the header
+fn http_date(headers: &HeaderMap, name: header::HeaderName) -> Option<HttpDate> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+/// Parses a single `bytes` range of the file, ignoring malformed ones
+fn parse_range(range: &str, len: u64) -> Range {
+    let Some((start, end)) = range
+        .strip_prefix("bytes=")
+        .filter(|spec| !spec.contains(','))
+        .and_then(|spec| spec.trim().split_once('-'))
+    else {
+        return Range::Full;
+    };
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => (start, end.min(len.saturating_sub(1))),
+        (Ok(start), Err(_)) if end.is_empty() => (start, len.saturating_sub(1)),
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return Range::Unsatisfiable;
+            }
+            (len.saturating_sub(suffix), len.saturating_sub(1))
+        }
+        _ => return Range::Full,
+    };
+    if start >= len {
+        Range::Unsatisfiable
+    } else {
+        Range::Partial(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges() {
+        for (range, expected) in [
+            ("bytes=0-4", Range::Partial(0, 4)),
+            ("bytes=5-", Range::Partial(5, 9)),
+            ("bytes=-3", Range::Partial(7, 9)),
+            ("bytes=-30", Range::Partial(0, 9)),
+            ("bytes=8-100", Range::Partial(8, 9)),
+            ("bytes=10-", Range::Unsatisfiable),
+            ("bytes=-0", Range::Unsatisfiable),
+            ("bytes=0-1,3-4", Range::Full),
+            ("bytes=4-2", Range::Full),
+            ("bytes=x-", Range::Full),
+            ("lines=1-2", Range::Full),
+        ] {
+            assert_eq!(parse_range(range, 10), expected, "{range}");
+        }
+        assert_eq!(parse_range("bytes=0-", 0), Range::Unsatisfiable);
+    }
+
+    #[test]
+    fn etags() {
+        assert!(weak_eq("W/\"a\"", "\"a\""));
+        assert!(!weak_eq("\"a\"", "\"b\""));
+    }
+}
@@ -0,0 +1,230 @@
+//! Tenant-isolated path extractor.
+
+use std::{
+    error::Error,
+    fmt,
+    path::{self, Component, PathBuf},
+};
+
+use axum::{
+    extract::FromRequestParts,
+    http::{StatusCode, request::Parts},
+    response::{IntoResponse, Response},
+};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection};
+
+/// The tenant the request belongs to.
+///
+/// It's expected to be inserted into request extensions by the
+/// authentication middleware and is used by [`TenantSafePath`] as the name of
+/// the tenant's directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TenantId(pub String);
+
+impl<T: Into<String>> From<T> for TenantId {
+    fn from(id: T) -> Self {
+        Self(id.into())
+    }
+}
+
+/// A [`SafePath`] prefixed with the directory of the current tenant.
+///
+/// The tenant is taken from the [`TenantId`] in request extensions, so a
+/// request can only ever reach files of its own tenant:
+///
+/// ```
+/// use axum::{
+///     Router,
+///     extract::Request,
+///     middleware::{self, Next},
+///     response::Response,
+///     routing::get,
+/// };
+/// use axum_safe_path::{TenantId, TenantSafePath};
+///
+/// async fn auth(mut req: Request, next: Next) -> Response {
+///     // Take it from the session in real code
+///     req.extensions_mut().insert(TenantId::from("acme"));
+///     next.run(req).await
+/// }
+///
+/// async fn handler(TenantSafePath(path): TenantSafePath) -> String {
+///     // `acme/...`
+///     path.display().to_string()
+/// }
+///
+/// let app: Router = Router::new()
+///     .route("/files/{*path}", get(handler))
+///     .layer(middleware::from_fn(auth));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TenantSafePath(pub PathBuf);
+
+impl AsRef<path::Path> for TenantSafePath {
+    fn as_ref(&self) -> &path::Path {
+        self.0.as_ref()
+    }
+}
+
+/// Rejection type for [`TenantSafePath`].
+#[derive(Debug)]
+pub enum TenantSafePathRejection {
+    /// There's no [`TenantId`] in request extensions
+    MissingTenant,
+    /// The [`TenantId`] isn't a single normal path component
+    InvalidTenant,
+    /// The path itself is invalid
+    Path(SafePathRejection),
+}
+
+impl fmt::Display for TenantSafePathRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTenant => f.write_str("Tenant is missing from request extensions"),
+            Self::InvalidTenant => f.write_str("Tenant id is not a valid directory name"),
+            Self::Path(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for TenantSafePathRejection {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Path(err) => Some(err),
+            Self::MissingTenant | Self::InvalidTenant => None,
+        }
+    }
+}
+
+impl IntoResponse for TenantSafePathRejection {
+    fn into_response(self) -> Response {
+        match self {
+            // Both mean the server is misconfigured, so the details aren't
+            // exposed
+            Self::MissingTenant | Self::InvalidTenant => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+            Self::Path(inner) => inner.into_response(),
+        }
+    }
+}
+
+impl From<SafePathRejection> for TenantSafePathRejection {
+    fn from(rejection: SafePathRejection) -> Self {
+        Self::Path(rejection)
+    }
+}
+
+/// Checks that the tenant id can be used as a single directory name
+fn is_valid_tenant(id: &str) -> bool {
+    let mut components = path::Path::new(id).components();
+    matches!(components.next(), Some(Component::Normal(name)) if name == id)
+        && components.next().is_none()
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for TenantSafePath
+where
+    S: Send + Sync,
+{
+    type Rejection = TenantSafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let TenantId(tenant) = parts
+            .extensions
+            .get::<TenantId>()
+            .cloned()
+            .ok_or(TenantSafePathRejection::MissingTenant)?;
+        if !is_valid_tenant(&tenant) {
+            return Err(TenantSafePathRejection::InvalidTenant);
+        }
+
+        let SafePath(path) = SafePath::from_request_parts(parts, state).await?;
+        // Absolute paths allowed by the config would replace the tenant
+        let relative = SafePathConfig::with_current(&parts.extensions, |config| {
+            config.normalized_relative(&path)
+        });
+        Ok(Self(PathBuf::from(tenant).join(relative)))
+    }
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(TenantSafePath(path): TenantSafePath) -> String {
+        path.display().to_string()
+    }
+
+    fn server(tenant: Option<&str>) -> TestServer {
+        let mut app = Router::new().route("/{*path}", get(handler));
+        if let Some(tenant) = tenant {
+            app = app.layer(Extension(TenantId::from(tenant)));
+        }
+        TestServer::new(app).unwrap()
+    }
+
+    #[test]
+    fn tenant_validation() {
+        assert!(is_valid_tenant("acme"));
+        assert!(is_valid_tenant("acme.corp"));
+        for id in ["", ".", "..", "a/b", "/acme", "acme/", "./acme"] {
+            assert!(!is_valid_tenant(id), "{id:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn prefixed_path() {
+        let res = server(Some("acme")).get("/foo/bar.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(
+            res.text(),
+            format!("acme{}foo/bar.txt", path::MAIN_SEPARATOR)
+        );
+    }
+
+    #[tokio::test]
+    async fn traversal_is_rejected() {
+        let res = server(Some("acme")).get("/..%2Fother/secret.txt").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn absolute_paths_stay_within_tenant() {
+        for config in [
+            SafePathConfig::new().allowed_roots(["/srv/tenants"]),
+            SafePathConfig::new().remote(true).allow_absolute(true),
+        ] {
+            let app = Router::new()
+                .route("/{*path}", get(handler))
+                .layer(Extension(TenantId::from("acme")))
+                .layer(Extension(config.clone()));
+            let server = TestServer::new(app).unwrap();
+
+            let res = server.get("//srv/tenants/other/secret").await;
+            assert_eq!(res.status_code(), StatusCode::OK, "{config:?}");
+            assert_eq!(
+                res.text(),
+                format!("acme{}srv/tenants/other/secret", path::MAIN_SEPARATOR),
+                "{config:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_tenant() {
+        let res = server(None).get("/foo").await;
+        assert_eq!(res.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn invalid_tenant() {
+        let res = server(Some("..")).get("/foo").await;
+        assert_eq!(res.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,266 @@
+//! Tracing and metrics of path validation.
+
+#[cfg(feature = "tracing")]
+use std::net::SocketAddr;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+#[cfg(feature = "tracing")]
+use axum::extract::{ConnectInfo, MatchedPath};
+use axum::http::Extensions;
+
+use crate::{SafePathRejection, hook::OffendingValueSlot};
+
+/// Runs the validation of the request input
+///
+/// With the `tracing` feature, it runs in a debug span and a rejection is
+/// reported with a warning. Only the length of the input is logged, not the
+/// input itself, unless the layer opted in with
+/// [`log_offending_values`](crate::SafePathPolicyLayer::log_offending_values).
+///
+/// With the `metrics` feature, rejections are counted by
+/// `axum_safe_path_rejections_total` with the `reason` label set to the
+/// [`code`](SafePathRejection::code), and the duration of validation is
+/// recorded to `axum_safe_path_validation_duration_seconds`.
+///
+/// With the `otel` feature, the outcome (`accepted` or `rejected`) and the
+/// reason are recorded to the `safe_path.outcome` and `safe_path.reason`
+/// fields of the current span, e.g. the request span of an OpenTelemetry
+/// setup, if it declares them. They're also sent with a debug event, which
+/// `tracing-opentelemetry` attaches to the span either way.
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+pub fn validation<T>(
+    extensions: &Extensions,
+    input: &str,
+    validate: impl FnOnce() -> Result<T, SafePathRejection>,
+) -> Result<T, SafePathRejection> {
+    let input_len = input.len();
+    #[cfg(feature = "metrics")]
+    let started = Instant::now();
+    #[cfg(feature = "tracing")]
+    let result = tracing::debug_span!("safe_path_validation", input_len).in_scope(validate);
+    #[cfg(not(feature = "tracing"))]
+    let result = validate();
+
+    #[cfg(feature = "metrics")]
+    {
+        metrics::histogram!("axum_safe_path_validation_duration_seconds").record(started.elapsed());
+        if let Err(rejection) = &result {
+            metrics::counter!("axum_safe_path_rejections_total", "reason" => rejection.code())
+                .increment(1);
+        }
+    }
+
+    let slot = extensions.get::<OffendingValueSlot>();
+    let value = match (&result, slot) {
+        (Err(_), Some(slot)) => Some(slot.set(input)).filter(|_| slot.log),
+        _ => None,
+    };
+
+    #[cfg(feature = "tracing")]
+    if let Err(rejection) = &result {
+        let route = extensions.get::<MatchedPath>().map(MatchedPath::as_str);
+        let client_addr = extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| tracing::field::display(addr));
+        tracing::warn!(
+            reason = rejection.code(),
+            route,
+            input_len,
+            value = value.map(tracing::field::debug),
+            client_addr,
+            "Path rejected",
+        );
+    }
+
+    #[cfg(feature = "otel")]
+    {
+        let (outcome, reason) = match &result {
+            Ok(_) => ("accepted", None),
+            Err(rejection) => ("rejected", Some(rejection.code())),
+        };
+        let span = tracing::Span::current();
+        span.record("safe_path.outcome", outcome);
+        if let Some(reason) = reason {
+            span.record("safe_path.reason", reason);
+        }
+        tracing::debug!(
+            safe_path.outcome = outcome,
+            safe_path.reason = reason,
+            "Path validated"
+        );
+    }
+
+    result
+}
+
+#[cfg(all(test, feature = "axum08", feature = "tracing"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    #[cfg(feature = "otel")]
+    use axum::http::Extensions;
+    use axum::{Router, routing::get};
+    use axum_test::TestServer;
+    #[cfg(feature = "otel")]
+    use tracing::field::Empty;
+    use tracing_subscriber::fmt::MakeWriter;
+    #[cfg(feature = "otel")]
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    #[cfg(feature = "otel")]
+    use super::validation;
+    #[cfg(feature = "otel")]
+    use crate::SafePathRejection;
+    use crate::{SafePath, SafePathConfig, SafePathPolicyLayer};
+
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl MakeWriter<'_> for Logs {
+        type Writer = Self;
+
+        fn make_writer(&self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    async fn handler(SafePath(path): SafePath) -> String {
+        path.display().to_string()
+    }
+
+    #[tokio::test]
+    async fn warns_on_rejection() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(logs.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new().route("/files/{*path}", get(handler));
+        let server = TestServer::new(app).unwrap();
+        server.get("/files/a/..%2F..%2Fsecret").await;
+        server.get("/files/fine").await;
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(logs.matches("Path rejected").count(), 1, "{logs}");
+        assert!(logs.contains("reason=\"path_traversal\""), "{logs}");
+        assert!(logs.contains("route=\"/files/{*path}\""), "{logs}");
+        assert!(logs.contains("input_len=18"), "{logs}");
+        assert!(!logs.contains("secret"), "{logs}");
+    }
+
+    #[tokio::test]
+    async fn logs_offending_value() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(logs.clone())
+            .with_ansi(false)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route("/files/{*path}", get(handler))
+            .layer(SafePathPolicyLayer::new(SafePathConfig::new()).log_offending_values(true));
+        let server = TestServer::new(app).unwrap();
+        let res = server.get("/files/a/..%2F..%2Fsecret").await;
+        assert!(!res.text().contains("secret"));
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("value=\"a/..%2F..%2Fsecret\""), "{logs}");
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn records_outcome_on_current_span() {
+        let logs = Logs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(logs.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(FmtSpan::CLOSE)
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let extensions = Extensions::new();
+        let span = tracing::info_span!(
+            "request",
+            safe_path.outcome = Empty,
+            safe_path.reason = Empty
+        );
+        span.in_scope(|| {
+            validation(&extensions, "a", || Err::<(), _>(SafePathRejection::Hidden)).unwrap_err();
+        });
+        drop(span);
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(
+            logs.contains("request{safe_path.outcome=\"rejected\" safe_path.reason=\"hidden\"}: "),
+            "{logs}"
+        );
+        assert!(logs.contains("Path validated"), "{logs}");
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+#[allow(clippy::unwrap_used)]
+mod metrics_tests {
+    use axum::http::Extensions;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use super::*;
+
+    #[test]
+    fn records_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let extensions = Extensions::new();
+        metrics::with_local_recorder(&recorder, || {
+            validation(&extensions, ".a", || {
+                Err::<(), _>(SafePathRejection::Hidden)
+            })
+            .unwrap_err();
+            validation(&extensions, ".a", || {
+                Err::<(), _>(SafePathRejection::Hidden)
+            })
+            .unwrap_err();
+            validation(&extensions, "a", || Ok(())).unwrap();
+        });
+
+        let mut rejections = 0;
+        let mut durations = 0;
+        let snapshot = snapshotter.snapshot().into_vec();
+        assert_eq!(snapshot.len(), 2);
+        for (key, _, _, value) in snapshot {
+            match (key.key().name(), value) {
+                ("axum_safe_path_rejections_total", DebugValue::Counter(count)) => {
+                    let labels: Vec<_> = key.key().labels().map(|l| (l.key(), l.value())).collect();
+                    assert_eq!(labels, [("reason", "hidden")]);
+                    rejections += count;
+                }
+                ("axum_safe_path_validation_duration_seconds", DebugValue::Histogram(values)) => {
+                    durations += values.len();
+                }
+                _ => (),
+            }
+        }
+        assert_eq!(rejections, 2);
+        assert_eq!(durations, 3);
+    }
+}
@@ -0,0 +1,353 @@
+//! Validation of the whole request path and its nested part.
+
+use std::path::PathBuf;
+
+use axum::{
+    extract::{FromRequestParts, NestedPath},
+    http::{Uri, request::Parts},
+};
+
+use crate::{SafePath, SafePathConfig, SafePathRejection, Traversal, raw};
+
+/// The whole request path, validated and with dot-segments removed.
+///
+/// Meant for proxy-style services forwarding the request path to a backend
+/// rather than using a route capture. Dot-segments, including encoded ones
+/// like `%2E%2E`, are removed as described in [RFC 3986][rfc], while a `..`
+/// going above the root is rejected as a traversal. The path without the
+/// leading slash is then checked with the current [`SafePathConfig`].
+///
+/// The value stays percent-encoded, so it can be forwarded as is:
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::SafeUri;
+///
+/// async fn proxy(SafeUri(path): SafeUri) -> String {
+///     format!("https://backend.internal{path}")
+/// }
+///
+/// let app: Router = Router::new().fallback(get(proxy));
+/// ```
+///
+/// [rfc]: https://www.rfc-editor.org/rfc/rfc3986#section-5.2.4
+#[derive(Debug, Clone)]
+pub struct SafeUri(pub String);
+
+impl AsRef<str> for SafeUri {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for SafeUri
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let path = parts.uri.path();
+        SafePathConfig::validate_current(&mut parts.extensions, path, |config| {
+            Self::validate(config, path)
+        })
+    }
+}
+
+impl SafeUri {
+    /// Validates the path of the URI with the default config
+    ///
+    /// # Errors
+    ///
+    /// Returns the rejection describing the first failed check.
+    pub fn from_uri(uri: &Uri) -> Result<Self, SafePathRejection> {
+        Self::validate(&SafePathConfig::default(), uri.path())
+    }
+
+    /// Normalizes the raw path and validates it with the config
+    fn validate(config: &SafePathConfig, path: &str) -> Result<Self, SafePathRejection> {
+        normalize(config, path).map(|(normalized, _)| Self(normalized))
+    }
+}
+
+/// The request path relative to the point the router is nested at, validated
+/// and decoded.
+///
+/// Axum strips the prefix of [nested](axum::Router::nest) routers from the
+/// URI, so the path doesn't depend on where the router is mounted. It's
+/// normalized and checked the same way as [`SafeUri`], which makes it handy
+/// for fallbacks serving a whole subtree:
+///
+/// ```
+/// use axum::{Router, routing::get};
+/// use axum_safe_path::NestedSafePath;
+///
+/// async fn docs(path: NestedSafePath) -> String {
+///     // `/docs` and `guide/intro.md` for `/docs/guide/intro.md`
+///     format!("{} {}", path.nested_at, path.path.display())
+/// }
+///
+/// let app: Router = Router::new().nest("/docs", Router::new().fallback(get(docs)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct NestedSafePath {
+    /// The path the router is nested at, see [`NestedPath`], or `/` if it
+    /// isn't nested
+    pub nested_at: String,
+    /// The decoded path relative to the nesting point
+    pub path: PathBuf,
+}
+
+impl AsRef<std::path::Path> for NestedSafePath {
+    fn as_ref(&self) -> &std::path::Path {
+        self.path.as_ref()
+    }
+}
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for NestedSafePath
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let nested_at = parts
+            .extensions
+            .get::<NestedPath>()
+            .map_or("/", NestedPath::as_str)
+            .to_owned();
+        let path = parts.uri.path();
+        let path = SafePathConfig::validate_current(&mut parts.extensions, path, |config| {
+            normalize(config, path).map(|(_, path)| path)
+        })?;
+        Ok(Self {
+            nested_at,
+            path: path.0,
+        })
+    }
+}
+
+/// A guard rejecting requests with unsafe paths, for
+/// [`from_extractor`](axum::middleware::from_extractor).
+///
+/// It protects a whole router, even if the handlers don't take a
+/// [`SafePath`] themselves. The request path relative to the point the router
+/// is nested at is decoded and checked with the current [`SafePathConfig`].
+/// Unlike [`SafeUri`], dot-segments aren't removed but rejected, as the
+/// handlers get path parameters with them:
+///
+/// ```
+/// use axum::{Router, extract::Path, middleware, routing::get};
+/// use axum_safe_path::RequireSafePath;
+///
+/// async fn download(Path((user, file)): Path<(String, String)>) -> String {
+///     format!("{user}: {file}")
+/// }
+///
+/// let users = Router::new()
+///     .route("/{user}/files/{*file}", get(download))
+///     .route_layer(middleware::from_extractor::<RequireSafePath>());
+/// let app: Router = Router::new().nest("/users", users);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequireSafePath;
+
+#[cfg_attr(all(feature = "axum07", not(feature = "axum08")), axum::async_trait)]
+impl<S> FromRequestParts<S> for RequireSafePath
+where
+    S: Send + Sync,
+{
+    type Rejection = SafePathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let path = parts.uri.path();
+        SafePathConfig::validate_current(&mut parts.extensions, path, |config| {
+            validate_request_path(config, path)
+        })?;
+        Ok(Self)
+    }
+}
+
+/// Decodes the raw request path and validates it without the leading slash
+pub fn validate_request_path(
+    config: &SafePathConfig,
+    path: &str,
+) -> Result<SafePath, SafePathRejection> {
+    let encoded = path.trim_start_matches('/');
+    let decoded = String::from_utf8(raw::percent_decode(encoded))
+        .map_err(|_| SafePathRejection::InvalidUtf8)?;
+    config.validate_encoded(PathBuf::from(decoded), Some(encoded))
+}
+
+/// Removes dot-segments from the raw path and validates its decoded form
+/// without the leading slash, returning both
+fn normalize(config: &SafePathConfig, path: &str) -> Result<(String, SafePath), SafePathRejection> {
+    let normalized = remove_dot_segments(path)?;
+    let decoded = String::from_utf8(raw::percent_decode(&normalized))
+        .map_err(|_| SafePathRejection::InvalidUtf8)?;
+    let relative = PathBuf::from(decoded.trim_start_matches('/'));
+    let path = config.validate_encoded(relative, Some(&normalized))?;
+    Ok((normalized, path))
+}
+
+/// Removes `.` and `..` segments, including percent-encoded ones, failing if
+/// the path goes above the root
+pub fn remove_dot_segments(path: &str) -> Result<String, SafePathRejection> {
+    let mut segments: Vec<&str> = Vec::new();
+    // A path ending with a dot-segment refers to a directory
+    let mut trailing_slash = false;
+    for (index, segment) in path
+        .strip_prefix('/')
+        .unwrap_or(path)
+        .split('/')
+        .enumerate()
+    {
+        let decoded = segment.to_ascii_lowercase().replace("%2e", ".");
+        trailing_slash = matches!(decoded.as_str(), "." | "..");
+        match decoded.as_str() {
+            "." => (),
+            ".." => {
+                segments
+                    .pop()
+                    .ok_or(SafePathRejection::TraversalAttack(Traversal::ParentDir {
+                        index,
+                    }))?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    let mut normalized = format!("/{}", segments.join("/"));
+    if trailing_slash && !segments.is_empty() {
+        normalized.push('/');
+    }
+    Ok(normalized)
+}
+
+#[cfg(all(test, feature = "axum08"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use axum::{Extension, Router, extract::Path, http::StatusCode, routing::get};
+    use axum_test::TestServer;
+
+    use super::*;
+
+    async fn handler(SafeUri(path): SafeUri) -> String {
+        path
+    }
+
+    #[test]
+    fn dot_segments() {
+        for (path, expected) in [
+            ("/", "/"),
+            ("/a/b", "/a/b"),
+            ("/a/./b", "/a/b"),
+            ("/a/b/../c", "/a/c"),
+            ("/a/b/..", "/a/"),
+            ("/a/.", "/a/"),
+            ("/a/%2E%2e/b", "/b"),
+            ("/a/.%2E/b%20c", "/b%20c"),
+            ("/a//b", "/a//b"),
+            ("/a/..b", "/a/..b"),
+        ] {
+            assert_eq!(remove_dot_segments(path).unwrap(), expected, "{path:?}");
+        }
+        for path in ["/..", "/a/../..", "/%2e%2E/etc/passwd"] {
+            assert!(
+                matches!(
+                    remove_dot_segments(path),
+                    Err(SafePathRejection::TraversalAttack(_))
+                ),
+                "{path:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_uri() {
+        let uri = Uri::from_static("/a/./b/../c.txt?x=1");
+        assert_eq!(SafeUri::from_uri(&uri).unwrap().0, "/a/c.txt");
+        for uri in ["/a/..%2F..%2Fetc", "/a/%00"] {
+            assert!(SafeUri::from_uri(&Uri::from_static(uri)).is_err(), "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn nested() {
+        async fn nested_handler(path: NestedSafePath) -> String {
+            format!("{} {}", path.nested_at, path.path.display())
+        }
+
+        let docs = Router::new()
+            .route("/pages/{*page}", get(nested_handler))
+            .fallback(get(nested_handler));
+        let app = Router::new()
+            .nest("/docs", docs)
+            .route("/top/{*path}", get(nested_handler));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/docs/pages/a%20b.md").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "/docs pages/a b.md");
+
+        let res = server.get("/docs/guide/./intro.md").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "/docs guide/intro.md");
+
+        let res = server.get("/top/level.md").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "/ top/level.md");
+
+        let res = server.get("/docs/a/..%2F..%2Fsecret").await;
+        assert_eq!(res.status_code(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn extractor() {
+        let app = Router::new()
+            .fallback(get(handler))
+            .layer(Extension(SafePathConfig::new().deny_hidden(true)));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/api/users/1").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "/api/users/1");
+
+        for uri in ["/api/.env", "/api/%2e%2e%2F..%2Fsecret", "/api/%FF"] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn guard() {
+        let users = Router::new()
+            .route(
+                "/{user}/files/{*file}",
+                get(|Path((user, file)): Path<(String, String)>| async move {
+                    format!("{user}: {file}")
+                }),
+            )
+            .route_layer(axum::middleware::from_extractor::<RequireSafePath>());
+        let app = Router::new().nest("/users", users).layer(Extension(
+            SafePathConfig::new().deny_hidden(true).max_depth(3),
+        ));
+        let server = TestServer::new(app).unwrap();
+
+        let res = server.get("/users/alice/files/a.txt").await;
+        assert_eq!(res.status_code(), StatusCode::OK);
+        assert_eq!(res.text(), "alice: a.txt");
+
+        for uri in [
+            "/users/alice/files/.env",
+            "/users/alice/files/..%2F..%2Fsecret",
+            "/users/alice/files/a/b.txt",
+            "/users/alice/files/a%00.txt",
+        ] {
+            let res = server.get(uri).await;
+            assert_eq!(res.status_code(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+    }
+}